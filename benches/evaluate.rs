@@ -0,0 +1,147 @@
+//! Compares parsing and then evaluating with the single pass `evaluate_bytes`.
+//!
+//! By default this uses a generated pickle that looks like a PyTorch state
+//! dict with a lot of tensors. Set `REPUGNANT_BENCH_FILE` to use a real
+//! `data.pkl` instead. With the `torch` feature enabled, that can also be a
+//! PyTorch checkpoint file, the `data.pkl` inside it will get used.
+use std::{env, fs};
+
+use anyhow::{anyhow, Result};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use repugnant_pickle as rp;
+
+/// Generate a protocol 2 pickle that's similar to what PyTorch saves.
+fn generate_state_dict(tensors: usize) -> Vec<u8> {
+    let mut out = vec![0x80, 2];
+    let mut next_mid = 0u32;
+    let mut put = |out: &mut Vec<u8>| {
+        out.push(b'r');
+        out.extend(next_mid.to_le_bytes());
+        next_mid += 1;
+        next_mid - 1
+    };
+    let unicode = |out: &mut Vec<u8>, s: &str| {
+        out.push(b'X');
+        out.extend((s.len() as u32).to_le_bytes());
+        out.extend(s.as_bytes());
+    };
+    let get = |out: &mut Vec<u8>, mid: u32| {
+        out.push(b'j');
+        out.extend(mid.to_le_bytes());
+    };
+    let binint = |out: &mut Vec<u8>, i: u32| {
+        out.push(b'J');
+        out.extend(i.to_le_bytes());
+    };
+
+    out.extend(b"ccollections\nOrderedDict\n");
+    let ordereddict = put(&mut out);
+    out.extend(b")R");
+    put(&mut out);
+    out.push(b'(');
+    let (mut rebuild, mut storage) = (None, None);
+    for idx in 0..tensors {
+        unicode(&mut out, &format!("layers.{}.weight", idx));
+        put(&mut out);
+        match rebuild {
+            Some(mid) => get(&mut out, mid),
+            None => {
+                out.extend(b"ctorch._utils\n_rebuild_tensor_v2\n");
+                rebuild = Some(put(&mut out));
+            }
+        }
+        out.extend(b"((");
+        unicode(&mut out, "storage");
+        put(&mut out);
+        match storage {
+            Some(mid) => get(&mut out, mid),
+            None => {
+                out.extend(b"ctorch\nFloatStorage\n");
+                storage = Some(put(&mut out));
+            }
+        }
+        unicode(&mut out, &idx.to_string());
+        put(&mut out);
+        unicode(&mut out, "cpu");
+        put(&mut out);
+        binint(&mut out, 4096 * 1024);
+        out.push(b't');
+        put(&mut out);
+        out.push(b'Q');
+        binint(&mut out, 0);
+        binint(&mut out, 4096);
+        binint(&mut out, 1024);
+        out.push(0x86);
+        put(&mut out);
+        binint(&mut out, 1024);
+        binint(&mut out, 1);
+        out.push(0x86);
+        put(&mut out);
+        out.push(0x89);
+        get(&mut out, ordereddict);
+        out.extend(b")R");
+        put(&mut out);
+        out.push(b't');
+        put(&mut out);
+        out.push(b'R');
+        put(&mut out);
+    }
+    out.extend(b"u.");
+    out
+}
+
+fn load_file(path: &str) -> Result<Vec<u8>> {
+    #[cfg(feature = "torch")]
+    if !path.ends_with(".pkl") {
+        use std::io::Read;
+
+        let mut zp = zip::ZipArchive::new(fs::File::open(path)?)?;
+        let datafn = zp
+            .file_names()
+            .find(|s| s.ends_with("/data.pkl"))
+            .map(str::to_owned)
+            .ok_or_else(|| anyhow!("Could not find data.pkl in archive"))?;
+        let mut buf = vec![];
+        zp.by_name(&datafn)?.read_to_end(&mut buf)?;
+        return Ok(buf);
+    }
+    Ok(fs::read(path)?)
+}
+
+fn bench_evaluate(c: &mut Criterion) {
+    let data = match env::var("REPUGNANT_BENCH_FILE") {
+        Ok(path) => load_file(&path).expect("Could not load benchmark file"),
+        Err(_) => generate_state_dict(20_000),
+    };
+    // No point timing them if they don't produce the same thing.
+    let (_, ops) = rp::parse_ops::<nom::error::Error<&[u8]>>(&data).expect("Parse error");
+    assert_eq!(
+        rp::evaluate_bytes(&data, true).expect("Fused evaluation failed"),
+        rp::evaluate(&ops, true).expect("Evaluation failed"),
+        "evaluate_bytes doesn't match parse_ops followed by evaluate"
+    );
+    drop(ops);
+    let mut group = c.benchmark_group("evaluate");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.sample_size(20);
+
+    group.bench_function("two_stage", |b| {
+        b.iter(|| -> Result<_> {
+            let (_, ops) = rp::parse_ops::<nom::error::Error<&[u8]>>(black_box(&data))
+                .map_err(|e| anyhow!("Parse error: {e:?}"))?;
+            let (vals, _memo) = rp::evaluate(&ops, true)?;
+            Ok(vals.len())
+        })
+    });
+    group.bench_function("fused", |b| {
+        b.iter(|| -> Result<_> {
+            let (vals, _memo) = rp::evaluate_bytes(black_box(&data), true)?;
+            Ok(vals.len())
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_evaluate);
+criterion_main!(benches);
@@ -0,0 +1,30 @@
+use std::{env::args, fs::File, io::Read};
+
+use anyhow::{bail, Result};
+
+use repugnant_pickle as rp;
+
+fn read_file(fname: &str) -> Result<Vec<u8>> {
+    let mut fp = File::open(fname)?;
+    let mut buf = Vec::with_capacity(fp.metadata().map(|md| md.len() as usize).unwrap_or(16384));
+    let _ = fp.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+fn main() -> Result<()> {
+    let (oldfn, newfn) = match (args().nth(1), args().nth(2)) {
+        (Some(oldfn), Some(newfn)) => (oldfn, newfn),
+        _ => bail!("Specify old and new pickle filenames!"),
+    };
+    let options = rp::DiffOptions {
+        float_tolerance: args().nth(3).map_or(Ok(0.0), |s| s.parse())?,
+    };
+    let (oldbuf, newbuf) = (read_file(&oldfn)?, read_file(&newfn)?);
+    let (old, _memo) = rp::evaluate_bytes(&oldbuf, true)?;
+    let (new, _memo) = rp::evaluate_bytes(&newbuf, true)?;
+    match (old.first(), new.first()) {
+        (Some(old), Some(new)) => old.diff(new, &options).iter().for_each(|d| println!("{d}")),
+        _ => bail!("Empty pickle"),
+    }
+    Ok(())
+}
@@ -16,7 +16,9 @@ fn main() -> Result<()> {
     match rp::parsers::parse_ops::<nom::error::VerboseError<&[u8]>>(&buf) {
         Ok((_i, ops)) => {
             let (values, _memo) = rp::eval::evaluate(&ops, true)?;
-            println!("{values:#?}");
+            for value in &values {
+                println!("{value:#}");
+            }
         }
         Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
             println!("ERROR: {:#?}", e.code);
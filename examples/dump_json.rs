@@ -0,0 +1,25 @@
+#![allow(unused_imports)]
+use std::{env::args, fs::File, io::Read};
+
+use anyhow::{bail, Result};
+
+use repugnant_pickle as rp;
+
+#[cfg(feature = "serde")]
+fn main() -> Result<()> {
+    let mut fp = if let Some(fname) = args().nth(1) {
+        File::open(fname)?
+    } else {
+        bail!("Specify pickle filename!");
+    };
+    let mut buf = Vec::with_capacity(fp.metadata().map(|md| md.len() as usize).unwrap_or(16384));
+    let _ = fp.read_to_end(&mut buf)?;
+    let (values, _memo) = rp::evaluate_bytes(&buf, true)?;
+    println!("{}", serde_json::to_string_pretty(&values)?);
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+fn main() {
+    println!("Compiled without Serde feature.");
+}
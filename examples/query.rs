@@ -0,0 +1,23 @@
+use std::{env::args, fs::File, io::Read};
+
+use anyhow::{bail, Result};
+
+use repugnant_pickle as rp;
+
+fn main() -> Result<()> {
+    let (fname, query) = match (args().nth(1), args().nth(2)) {
+        (Some(fname), Some(query)) => (fname, query),
+        _ => bail!("Specify pickle filename and query!"),
+    };
+    let query = rp::Query::new(&query)?;
+    let mut fp = File::open(fname)?;
+    let mut buf = Vec::with_capacity(fp.metadata().map(|md| md.len() as usize).unwrap_or(16384));
+    let _ = fp.read_to_end(&mut buf)?;
+    let (values, _memo) = rp::evaluate_bytes(&buf, true)?;
+    for value in &values {
+        for found in query.run(value) {
+            println!("/{}: {:#}", found.path, found.value);
+        }
+    }
+    Ok(())
+}
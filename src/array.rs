@@ -0,0 +1,325 @@
+use anyhow::Result;
+use num_bigint::BigInt;
+
+use crate::{compat::decode_string, eval::StringEncoding, value::*};
+
+#[derive(Debug, Clone, PartialEq)]
+/// The items of an `array.array`, with the width and signedness they had
+/// in the pickle.
+pub enum ArrayData {
+    I8(Vec<i8>),
+    U8(Vec<u8>),
+    I16(Vec<i16>),
+    U16(Vec<u16>),
+    I32(Vec<i32>),
+    U32(Vec<u32>),
+    I64(Vec<i64>),
+    U64(Vec<u64>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+    /// The `u` and `w` typecodes.
+    Unicode(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// An `array.array`. You get these when evaluating with
+/// `EvalOptions::arrays` set.
+///
+/// Protocol 3 and later pickle arrays as their bytes and a machine format
+/// code that says how to read them, so the data has whatever width the
+/// machine that made the pickle used. That means an `l` array from Windows
+/// ends up as `ArrayData::I32`. Older protocols pickle them as a list,
+/// which gets the usual widths from Linux.
+pub struct PyArray {
+    /// The typecode, like `'f'` or `'q'`.
+    pub typecode: char,
+    pub data: ArrayData,
+}
+
+/// Split bytes into `N` byte chunks and convert them, if they split evenly.
+fn decode<const N: usize, T>(bytes: &[u8], f: fn([u8; N]) -> T) -> Option<Vec<T>> {
+    if !bytes.len().is_multiple_of(N) {
+        return None;
+    }
+    Some(
+        bytes
+            .chunks_exact(N)
+            .map(|c| f(c.try_into().expect("Impossible: Bad chunk size")))
+            .collect(),
+    )
+}
+
+impl ArrayData {
+    /// The name of the type of the items, like `"f32"`, or `"unicode"` for
+    /// `ArrayData::Unicode`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::I8(_) => "i8",
+            Self::U8(_) => "u8",
+            Self::I16(_) => "i16",
+            Self::U16(_) => "u16",
+            Self::I32(_) => "i32",
+            Self::U32(_) => "u32",
+            Self::I64(_) => "i64",
+            Self::U64(_) => "u64",
+            Self::F32(_) => "f32",
+            Self::F64(_) => "f64",
+            Self::Unicode(_) => "unicode",
+        }
+    }
+
+    /// Make the data from items, with `name` saying what type they should
+    /// be like `ArrayData::name` does. Unicode can also be a single string.
+    /// You get `None` if the name is unknown or an item doesn't fit.
+    pub fn from_values(name: &str, items: &[Value<'_>]) -> Option<Self> {
+        fn ints<T: TryFrom<i64> + for<'b> TryFrom<&'b BigInt>>(
+            items: &[Value<'_>],
+        ) -> Option<Vec<T>> {
+            items
+                .iter()
+                .map(|v| match v {
+                    Value::Int(i) => T::try_from(*i).ok(),
+                    Value::BigInt(i) => T::try_from(i).ok(),
+                    _ => None,
+                })
+                .collect()
+        }
+        let floats = || {
+            items
+                .iter()
+                .map(|v| match v {
+                    Value::Float(f) => Some(*f),
+                    Value::Int(i) => Some(*i as f64),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+        };
+        Some(match name {
+            "i8" => Self::I8(ints(items)?),
+            "u8" => Self::U8(ints(items)?),
+            "i16" => Self::I16(ints(items)?),
+            "u16" => Self::U16(ints(items)?),
+            "i32" => Self::I32(ints(items)?),
+            "u32" => Self::U32(ints(items)?),
+            "i64" => Self::I64(ints(items)?),
+            "u64" => Self::U64(ints(items)?),
+            "f32" => Self::F32(floats()?.into_iter().map(|f| f as f32).collect()),
+            "f64" => Self::F64(floats()?),
+            "unicode" => match items {
+                [Value::String(s)] => Self::Unicode(s.to_string()),
+                _ => Self::Unicode(
+                    items
+                        .iter()
+                        .map(|v| match v {
+                            Value::String(s) if s.chars().count() == 1 => Some(s.as_ref()),
+                            _ => None,
+                        })
+                        .collect::<Option<_>>()?,
+                ),
+            },
+            _ => return None,
+        })
+    }
+}
+
+impl PyArray {
+    /// Read the bytes the way `array._array_reconstructor` would with the
+    /// machine format code `mformat`, which says how wide the items are and
+    /// what byte order they have. You get `None` if the code is unknown or
+    /// the bytes don't fit it.
+    pub fn from_bytes(typecode: char, mformat: i64, bytes: &[u8]) -> Option<Self> {
+        let utf32 = |units: Vec<u32>| units.into_iter().map(char::from_u32).collect::<Option<_>>();
+        let data = match mformat {
+            0 => ArrayData::U8(bytes.to_vec()),
+            1 => ArrayData::I8(decode(bytes, i8::from_le_bytes)?),
+            2 => ArrayData::U16(decode(bytes, u16::from_le_bytes)?),
+            3 => ArrayData::U16(decode(bytes, u16::from_be_bytes)?),
+            4 => ArrayData::I16(decode(bytes, i16::from_le_bytes)?),
+            5 => ArrayData::I16(decode(bytes, i16::from_be_bytes)?),
+            6 => ArrayData::U32(decode(bytes, u32::from_le_bytes)?),
+            7 => ArrayData::U32(decode(bytes, u32::from_be_bytes)?),
+            8 => ArrayData::I32(decode(bytes, i32::from_le_bytes)?),
+            9 => ArrayData::I32(decode(bytes, i32::from_be_bytes)?),
+            10 => ArrayData::U64(decode(bytes, u64::from_le_bytes)?),
+            11 => ArrayData::U64(decode(bytes, u64::from_be_bytes)?),
+            12 => ArrayData::I64(decode(bytes, i64::from_le_bytes)?),
+            13 => ArrayData::I64(decode(bytes, i64::from_be_bytes)?),
+            14 => ArrayData::F32(decode(bytes, f32::from_le_bytes)?),
+            15 => ArrayData::F32(decode(bytes, f32::from_be_bytes)?),
+            16 => ArrayData::F64(decode(bytes, f64::from_le_bytes)?),
+            17 => ArrayData::F64(decode(bytes, f64::from_be_bytes)?),
+            18 => ArrayData::Unicode(String::from_utf16(&decode(bytes, u16::from_le_bytes)?).ok()?),
+            19 => ArrayData::Unicode(String::from_utf16(&decode(bytes, u16::from_be_bytes)?).ok()?),
+            20 => ArrayData::Unicode(utf32(decode(bytes, u32::from_le_bytes)?)?),
+            21 => ArrayData::Unicode(utf32(decode(bytes, u32::from_be_bytes)?)?),
+            _ => return None,
+        };
+        Some(Self { typecode, data })
+    }
+
+    /// Make an array from items like the ones `array.tolist` gives you.
+    /// You get `None` if the typecode is unknown or an item doesn't fit.
+    pub fn from_values(typecode: char, items: &[Value<'_>]) -> Option<Self> {
+        let name = match typecode {
+            'b' => "i8",
+            'B' => "u8",
+            'h' => "i16",
+            'H' => "u16",
+            'i' => "i32",
+            'I' => "u32",
+            'l' | 'q' => "i64",
+            'L' | 'Q' => "u64",
+            'f' => "f32",
+            'd' => "f64",
+            'u' | 'w' => "unicode",
+            _ => return None,
+        };
+        let data = ArrayData::from_values(name, items)?;
+        Some(Self { typecode, data })
+    }
+
+    /// The number of items.
+    pub fn len(&self) -> usize {
+        match &self.data {
+            ArrayData::I8(v) => v.len(),
+            ArrayData::U8(v) => v.len(),
+            ArrayData::I16(v) => v.len(),
+            ArrayData::U16(v) => v.len(),
+            ArrayData::I32(v) => v.len(),
+            ArrayData::U32(v) => v.len(),
+            ArrayData::I64(v) => v.len(),
+            ArrayData::U64(v) => v.len(),
+            ArrayData::F32(v) => v.len(),
+            ArrayData::F64(v) => v.len(),
+            ArrayData::Unicode(s) => s.chars().count(),
+        }
+    }
+
+    /// Whether there aren't any items.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The items as values, like `array.tolist`. Integers that don't fit
+    /// in `i64` are `Value::BigInt` and `u` arrays give you one character
+    /// strings.
+    pub fn to_values(&self) -> Vec<Value<'static>> {
+        fn ints<T: Copy + Into<i128>>(v: &[T]) -> Vec<Value<'static>> {
+            v.iter()
+                .map(|&i| match i64::try_from(i.into()) {
+                    Ok(i) => Value::Int(i),
+                    Err(_) => Value::BigInt(BigInt::from(i.into())),
+                })
+                .collect()
+        }
+        match &self.data {
+            ArrayData::I8(v) => ints(v),
+            ArrayData::U8(v) => ints(v),
+            ArrayData::I16(v) => ints(v),
+            ArrayData::U16(v) => ints(v),
+            ArrayData::I32(v) => ints(v),
+            ArrayData::U32(v) => ints(v),
+            ArrayData::I64(v) => ints(v),
+            ArrayData::U64(v) => ints(v),
+            ArrayData::F32(v) => v.iter().map(|&f| Value::Float(f as f64)).collect(),
+            ArrayData::F64(v) => v.iter().map(|&f| Value::Float(f)).collect(),
+            ArrayData::Unicode(s) => s
+                .chars()
+                .map(|c| Value::String(c.to_string().into()))
+                .collect(),
+        }
+    }
+}
+
+/// The machine format code for a typecode on a little-endian machine with
+/// a 64-bit `long`, which is what Python 2 arrays usually came from.
+fn native_format(typecode: char) -> Option<i64> {
+    Some(match typecode {
+        'B' => 0,
+        'b' => 1,
+        'H' => 2,
+        'h' => 4,
+        'I' => 6,
+        'i' => 8,
+        'L' | 'Q' => 10,
+        'l' | 'q' => 12,
+        'f' => 14,
+        'd' => 16,
+        'u' | 'w' => 20,
+        _ => return None,
+    })
+}
+
+/// Make a `Value::Array` from a call to `array._array_reconstructor` or to
+/// `array.array` with a list. Older versions of Python 2 pickle arrays as a
+/// `str` of their bytes, which get read with `native_format`, or with just
+/// the typecode if they're empty. If the `str` got decoded, `encoding` says
+/// how to get the bytes back.
+pub(crate) fn reduce_array<'a>(
+    val: Value<'a>,
+    encoding: StringEncoding,
+    resolve: &mut impl FnMut(Value<'a>) -> Result<Value<'a>>,
+) -> Result<Value<'a>> {
+    let reconstructor = match &val {
+        Value::Global(class, args) if args.len() == 1 => match class.as_global() {
+            Some(("array", "_array_reconstructor")) => true,
+            Some(("array", "array")) => false,
+            _ => return Ok(val),
+        },
+        _ => return Ok(val),
+    };
+    let args = match &val {
+        Value::Global(_, args) => match args.as_slice() {
+            [Value::Seq(SequenceType::Tuple, targs)] => targs.clone(),
+            _ => return Ok(val),
+        },
+        _ => unreachable!("Impossible: Not a call"),
+    };
+    // Python 2 strings from `STRING` don't get decoded by default.
+    let py2_str = |v: Value<'a>| match &v {
+        Value::Raw(op) => Ok(decode_string(op, StringEncoding::Bytes)?.unwrap_or(v)),
+        _ => Ok(v),
+    };
+    let mut args = args
+        .into_iter()
+        .map(|v| py2_str(fix_value(resolve(v)?)?))
+        .collect::<Result<Vec<_>>>()?;
+    let typecode = |tc: &Value<'_>| match tc {
+        Value::String(s) if s.chars().count() == 1 => s.chars().next(),
+        Value::Bytes(b) if b.len() == 1 => Some(b[0] as char),
+        _ => None,
+    };
+    let str_bytes = |s: &str| match encoding {
+        StringEncoding::Latin1 => s.chars().map(|c| u8::try_from(c).ok()).collect(),
+        _ => Some(s.as_bytes().to_vec()),
+    };
+    let array = match (reconstructor, args.as_mut_slice()) {
+        (true, [class, tc, Value::Int(mformat), Value::Bytes(b)])
+            if class.as_global() == Some(("array", "array")) =>
+        {
+            typecode(tc).and_then(|tc| PyArray::from_bytes(tc, *mformat, b))
+        }
+        (false, [tc, Value::Seq(SequenceType::List, items)]) => match typecode(tc) {
+            Some(tc) => {
+                let items = std::mem::take(items)
+                    .into_iter()
+                    .map(|v| fix_value(resolve(v)?))
+                    .collect::<Result<Vec<_>>>()?;
+                PyArray::from_values(tc, &items)
+            }
+            None => None,
+        },
+        (false, [tc, Value::Bytes(b)]) => {
+            typecode(tc).and_then(|tc| PyArray::from_bytes(tc, native_format(tc)?, b))
+        }
+        (false, [tc, Value::String(s)]) => {
+            typecode(tc).and_then(|tc| PyArray::from_bytes(tc, native_format(tc)?, &str_bytes(s)?))
+        }
+        (false, [tc]) => typecode(tc).and_then(|tc| PyArray::from_values(tc, &[])),
+        _ => None,
+    };
+    Ok(match array {
+        Some(array) => Value::Array(Box::new(array)),
+        None => val,
+    })
+}
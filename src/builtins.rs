@@ -0,0 +1,180 @@
+use std::{
+    borrow::Cow,
+    mem::{replace, take},
+};
+
+use anyhow::Result;
+
+use crate::value::*;
+
+#[derive(Debug, Clone)]
+/// Builtins we know how to call.
+enum Builtin {
+    /// `_codecs.encode`, which is how Python 3 pickles `bytes` with
+    /// protocols that don't support them.
+    Encode,
+    Bytes,
+    ByteArray,
+    /// `set` or `frozenset`.
+    Set(SequenceType),
+    Complex,
+    Slice,
+    /// `range` or Python 2's `xrange`.
+    Range,
+}
+
+impl Builtin {
+    fn from_class(class: &Value<'_>) -> Option<Self> {
+        let (module, name) = match class {
+            Value::Class { module, name } => (module.as_ref(), name.as_ref()),
+            _ => return None,
+        };
+        Some(match (module, name) {
+            ("_codecs", "encode") => Self::Encode,
+            ("builtins" | "__builtin__", name) => match name {
+                "bytes" => Self::Bytes,
+                "bytearray" => Self::ByteArray,
+                "set" => Self::Set(SequenceType::Set),
+                "frozenset" => Self::Set(SequenceType::FrozenSet),
+                "complex" => Self::Complex,
+                "slice" => Self::Slice,
+                "range" | "xrange" => Self::Range,
+                _ => return None,
+            },
+            _ => return None,
+        })
+    }
+
+    /// Call the builtin. You get `None` if the arguments aren't what we
+    /// expected. Anything we need gets moved out of `args`.
+    fn call<'a>(self, args: &mut [Value<'a>]) -> Option<Value<'a>> {
+        let int_or_none =
+            |v: &Value<'_>| matches!(v, Value::Int(_) | Value::BigInt(_) | Value::None);
+        let int = |v: &Value<'_>| matches!(v, Value::Int(_) | Value::BigInt(_));
+        let boxed = |v: &mut Value<'a>| Box::new(replace(v, Value::None));
+
+        Some(match (self, args) {
+            (Self::ByteArray, args) => match Self::Bytes.call(args)? {
+                Value::Bytes(b) => Value::ByteArray(b),
+                _ => return None,
+            },
+            (Self::Encode, [Value::String(s)]) => Value::Bytes(encode_str(s, "utf-8")?),
+            (Self::Encode | Self::Bytes, [Value::String(s), Value::String(enc)]) => {
+                Value::Bytes(encode_str(s, enc)?)
+            }
+            (Self::Bytes, []) => Value::Bytes(Cow::Borrowed(&[])),
+            (Self::Bytes, [Value::Bytes(b)]) => Value::Bytes(take(b)),
+            (Self::Bytes, [Value::Seq(SequenceType::List | SequenceType::Tuple, items)]) => {
+                Value::Bytes(Cow::Owned(
+                    items
+                        .iter()
+                        .map(|v| match v {
+                            Value::Int(i) => u8::try_from(*i).ok(),
+                            _ => None,
+                        })
+                        .collect::<Option<_>>()?,
+                ))
+            }
+            (Self::Set(st), []) => Value::Seq(st, vec![]),
+            (Self::Set(st), [Value::Seq(_, items)]) => Value::Seq(st, take(items)),
+            (Self::Complex, [re]) => Value::Complex(to_f64(re)?, 0.0),
+            (Self::Complex, [re, im]) => Value::Complex(to_f64(re)?, to_f64(im)?),
+            (Self::Slice, [stop]) if int_or_none(stop) => {
+                Value::Slice(Box::new(Value::None), boxed(stop), Box::new(Value::None))
+            }
+            (Self::Slice, [start, stop]) if int_or_none(start) && int_or_none(stop) => {
+                Value::Slice(boxed(start), boxed(stop), Box::new(Value::None))
+            }
+            (Self::Slice, [start, stop, step])
+                if int_or_none(start) && int_or_none(stop) && int_or_none(step) =>
+            {
+                Value::Slice(boxed(start), boxed(stop), boxed(step))
+            }
+            (Self::Range, [stop]) if int(stop) => Value::Range(
+                Box::new(Value::Int(0)),
+                boxed(stop),
+                Box::new(Value::Int(1)),
+            ),
+            (Self::Range, [start, stop]) if int(start) && int(stop) => {
+                Value::Range(boxed(start), boxed(stop), Box::new(Value::Int(1)))
+            }
+            (Self::Range, [start, stop, step])
+                if int(start) && int(stop) && int(step) && *step != Value::Int(0) =>
+            {
+                Value::Range(boxed(start), boxed(stop), boxed(step))
+            }
+            _ => return None,
+        })
+    }
+}
+
+fn to_f64(val: &Value<'_>) -> Option<f64> {
+    Some(match val {
+        Value::Float(f) => *f,
+        Value::Int(i) => *i as f64,
+        Value::Bool(b) => *b as u8 as f64,
+        _ => return None,
+    })
+}
+
+/// Like `encode` but the result only borrows if the string does.
+fn encode_str<'a>(s: &Cow<'a, str>, encoding: &str) -> Option<Cow<'a, [u8]>> {
+    Some(match (encode(s, encoding)?, s) {
+        // Borrowing means the encoded bytes are the string's bytes.
+        (Cow::Borrowed(_), Cow::Borrowed(s)) => Cow::Borrowed(s.as_bytes()),
+        (b, _) => Cow::Owned(b.into_owned()),
+    })
+}
+
+/// Encode a string like Python's `str.encode`, for the encodings that
+/// show up in pickles.
+fn encode<'a>(s: &'a str, encoding: &str) -> Option<Cow<'a, [u8]>> {
+    match encoding.to_ascii_lowercase().replace('_', "-").as_str() {
+        "utf-8" | "utf8" => Some(Cow::Borrowed(s.as_bytes())),
+        "ascii" | "us-ascii" => s.is_ascii().then_some(Cow::Borrowed(s.as_bytes())),
+        "latin1" | "latin-1" | "iso-8859-1" | "iso8859-1" | "l1" => {
+            if s.is_ascii() {
+                return Some(Cow::Borrowed(s.as_bytes()));
+            }
+            s.chars()
+                .map(|c| u8::try_from(c).ok())
+                .collect::<Option<_>>()
+                .map(Cow::Owned)
+        }
+        _ => None,
+    }
+}
+
+/// Evaluate a call to one of the builtins that doesn't have side effects,
+/// like `set` or `_codecs.encode`.
+pub(crate) fn reduce_builtin<'a>(
+    val: Value<'a>,
+    resolve: &mut impl FnMut(Value<'a>) -> Result<Value<'a>>,
+) -> Result<Value<'a>> {
+    let (callable, builtin, args) = match val {
+        Value::Global(callable, mut args) if args.len() == 1 => {
+            match (Builtin::from_class(&callable), args.pop()) {
+                (Some(builtin), Some(Value::Seq(SequenceType::Tuple, targs))) => {
+                    (callable, builtin, targs)
+                }
+                (_, arg) => return Ok(Value::Global(callable, arg.into_iter().collect())),
+            }
+        }
+        val => return Ok(val),
+    };
+    let mut args = args
+        .into_iter()
+        .map(|v| fix_value(resolve(v)?))
+        .collect::<Result<Vec<_>>>()?;
+    // Making bytes from a list needs the actual ints.
+    if let (Builtin::Bytes | Builtin::ByteArray, [Value::Seq(_, items)]) = (&builtin, &mut args[..])
+    {
+        *items = take(items)
+            .into_iter()
+            .map(|v| fix_value(resolve(v)?))
+            .collect::<Result<Vec<_>>>()?;
+    }
+    Ok(builtin
+        .call(&mut args)
+        .unwrap_or_else(|| Value::Global(callable, vec![Value::Seq(SequenceType::Tuple, args)])))
+}
@@ -0,0 +1,202 @@
+use anyhow::Result;
+
+use crate::{object::PyObject, value::*};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// The `collections` types we know about.
+pub enum CollectionKind {
+    /// `collections.OrderedDict`. The value is a dict.
+    OrderedDict,
+
+    /// `collections.defaultdict`. The value is a dict, and `extra` is the
+    /// `default_factory`.
+    DefaultDict,
+
+    /// `collections.Counter`. The value is a dict.
+    Counter,
+
+    /// `collections.deque`. The value is a list, and `extra` is `maxlen`.
+    Deque,
+
+    /// A class made with `collections.namedtuple`. The value is a tuple.
+    /// These are only a guess, see `EvalOptions::guess_namedtuples`.
+    NamedTuple,
+}
+
+impl CollectionKind {
+    /// The name Python uses for the type, like `"OrderedDict"`. Namedtuples
+    /// are just `"namedtuple"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::OrderedDict => "OrderedDict",
+            Self::DefaultDict => "defaultdict",
+            Self::Counter => "Counter",
+            Self::Deque => "deque",
+            Self::NamedTuple => "namedtuple",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A dict, list or tuple that's really one of the `collections` types.
+/// You get these when evaluating with `EvalOptions::collections` set, and
+/// namedtuples with `EvalOptions::guess_namedtuples` as well.
+pub struct Collection<'a> {
+    pub kind: CollectionKind,
+
+    /// The class. For namedtuples this is the only way to tell which one
+    /// it is.
+    pub class: Value<'a>,
+
+    /// The items as a `Value::Dict` or `Value::Seq`.
+    pub value: Value<'a>,
+
+    /// The `default_factory` of a `defaultdict` or the `maxlen` of a
+    /// `deque`. It's `Value::None` if there isn't one.
+    pub extra: Value<'a>,
+}
+
+impl<'a> Collection<'a> {
+    /// The name of the type, like `"OrderedDict"`. For namedtuples it's
+    /// the name of the class.
+    pub fn type_name(&self) -> &str {
+        match (self.kind, &self.class) {
+            (CollectionKind::NamedTuple, Value::Class { name, .. }) => name,
+            (kind, _) => kind.name(),
+        }
+    }
+
+    /// The `maxlen` of a `deque`, if it has one.
+    pub fn maxlen(&self) -> Option<u64> {
+        match (self.kind, &self.extra) {
+            (CollectionKind::Deque, Value::Int(n)) => u64::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+
+    /// The `default_factory` of a `defaultdict`, if it has one.
+    pub fn default_factory(&self) -> Option<&Value<'a>> {
+        match (self.kind, &self.extra) {
+            (CollectionKind::DefaultDict, Value::None) => None,
+            (CollectionKind::DefaultDict, factory) => Some(factory),
+            _ => None,
+        }
+    }
+}
+
+fn collection<'a>(
+    kind: CollectionKind,
+    class: Value<'a>,
+    value: Value<'a>,
+    extra: Value<'a>,
+) -> Value<'a> {
+    Value::Collection(Box::new(Collection {
+        kind,
+        class,
+        value,
+        extra,
+    }))
+}
+
+/// Make a `Value::Collection` from a call to one of the `collections` types,
+/// or to `copyreg._reconstructor` for a namedtuple. `collections.OrderedDict`
+/// is expected to have been through `reduce_ordereddict` already.
+pub(crate) fn reduce_collection<'a>(
+    val: Value<'a>,
+    resolve: &mut impl FnMut(Value<'a>) -> Result<Value<'a>>,
+) -> Result<Value<'a>> {
+    let (class, args) = match val {
+        Value::Global(class, args) => (class, args),
+        val => return Ok(val),
+    };
+    let kind = match class.as_global() {
+        Some(("collections", "OrderedDict")) => CollectionKind::OrderedDict,
+        Some(("collections", "defaultdict")) => CollectionKind::DefaultDict,
+        Some(("collections", "Counter")) => CollectionKind::Counter,
+        Some(("collections", "deque")) => CollectionKind::Deque,
+        Some(("copyreg" | "copy_reg", "_reconstructor")) => CollectionKind::NamedTuple,
+        _ => return Ok(Value::Global(class, args)),
+    };
+    let mut argtuple = match args.as_slice() {
+        [Value::Seq(SequenceType::Tuple, argtuple)] => argtuple.clone(),
+        [Value::Seq(SequenceType::Tuple, argtuple), Value::Dict(_)]
+            if kind == CollectionKind::OrderedDict && argtuple.is_empty() =>
+        {
+            vec![]
+        }
+        _ => return Ok(Value::Global(class, args)),
+    };
+    let (value, extra) = match (kind, argtuple.len()) {
+        (CollectionKind::OrderedDict, 0) => match args.into_iter().nth(1) {
+            Some(items) => (items, Value::None),
+            None => (Value::Dict(vec![]), Value::None),
+        },
+        (CollectionKind::DefaultDict, 0) => (Value::Dict(vec![]), Value::None),
+        (CollectionKind::DefaultDict, 1) => (Value::Dict(vec![]), argtuple.remove(0)),
+        (CollectionKind::Counter, 0) => (Value::Dict(vec![]), Value::None),
+        (CollectionKind::Counter, 1) => match resolve(argtuple.remove(0))? {
+            items @ Value::Dict(_) => (items, Value::None),
+            _ => return Ok(Value::Global(class, args)),
+        },
+        (CollectionKind::Deque, 0..=2) => {
+            let mut argtuple = argtuple.into_iter();
+            let items = match argtuple.next().map(&mut *resolve).transpose()? {
+                None => vec![],
+                Some(Value::Seq(SequenceType::List | SequenceType::Tuple, items)) => items,
+                Some(_) => return Ok(Value::Global(class, args)),
+            };
+            let maxlen = match argtuple
+                .next()
+                .map(&mut *resolve)
+                .transpose()?
+                .map(fix_value)
+            {
+                None => Value::None,
+                Some(Ok(maxlen @ (Value::Int(_) | Value::None))) => maxlen,
+                Some(_) => return Ok(Value::Global(class, args)),
+            };
+            (Value::Seq(SequenceType::List, items), maxlen)
+        }
+        // This is `tuple.__new__(cls, items)`.
+        (CollectionKind::NamedTuple, 3) => {
+            let mut argtuple = argtuple.into_iter().map(&mut *resolve);
+            let (cls, base, items) = (
+                argtuple.next().expect("Impossible: Missing class")?,
+                argtuple.next().expect("Impossible: Missing base")?,
+                argtuple.next().expect("Impossible: Missing items")?,
+            );
+            let is_tuple =
+                base.is_class("builtins", "tuple") || base.is_class("__builtin__", "tuple");
+            return Ok(match items {
+                items @ Value::Seq(SequenceType::Tuple, _) if is_tuple => {
+                    collection(CollectionKind::NamedTuple, cls, items, Value::None)
+                }
+                _ => Value::Global(class, args),
+            });
+        }
+        _ => return Ok(Value::Global(class, args)),
+    };
+    Ok(collection(kind, *class, value, extra))
+}
+
+/// `NEWOBJ` with arguments might be a namedtuple. If it turns out not to be,
+/// `BUILD` turns it back into an object with `unnamedtuple`.
+pub(crate) fn namedtuple<'a>(class: Value<'a>, items: Vec<Value<'a>>) -> Value<'a> {
+    collection(
+        CollectionKind::NamedTuple,
+        class,
+        Value::Seq(SequenceType::Tuple, items),
+        Value::None,
+    )
+}
+
+/// Turn something that looked like a namedtuple back into the object it
+/// really was, because it got state from `BUILD`.
+pub(crate) fn unnamedtuple<'a>(coll: Collection<'a>, objects: bool) -> Value<'a> {
+    match (objects, coll.value) {
+        (true, Value::Seq(SequenceType::Tuple, items)) => {
+            Value::PyObject(Box::new(PyObject::new(coll.class, items)))
+        }
+        (_, args) => Value::Object(Box::new(coll.class), vec![args]),
+    }
+}
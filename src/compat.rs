@@ -0,0 +1,424 @@
+use std::borrow::Cow;
+
+use anyhow::{bail, Result};
+
+use crate::{eval::StringEncoding, ops::PickleOp, value::Value};
+
+/// Modules that got renamed in Python 3. This is `IMPORT_MAPPING` from
+/// Python's `_compat_pickle`, sorted so it can be searched.
+static IMPORT_MAPPING: &[(&str, &str)] = &[
+    ("BaseHTTPServer", "http.server"),
+    ("CGIHTTPServer", "http.server"),
+    ("ConfigParser", "configparser"),
+    ("Cookie", "http.cookies"),
+    ("Dialog", "tkinter.dialog"),
+    ("DocXMLRPCServer", "xmlrpc.server"),
+    ("FileDialog", "tkinter.filedialog"),
+    ("HTMLParser", "html.parser"),
+    ("Queue", "queue"),
+    ("ScrolledText", "tkinter.scrolledtext"),
+    ("SimpleDialog", "tkinter.simpledialog"),
+    ("SimpleHTTPServer", "http.server"),
+    ("SimpleXMLRPCServer", "xmlrpc.server"),
+    ("SocketServer", "socketserver"),
+    ("StringIO", "io"),
+    ("Tix", "tkinter.tix"),
+    ("Tkconstants", "tkinter.constants"),
+    ("Tkdnd", "tkinter.dnd"),
+    ("Tkinter", "tkinter"),
+    ("UserDict", "collections"),
+    ("UserList", "collections"),
+    ("UserString", "collections"),
+    ("__builtin__", "builtins"),
+    ("_abcoll", "collections.abc"),
+    ("_elementtree", "xml.etree.ElementTree"),
+    ("_winreg", "winreg"),
+    ("anydbm", "dbm"),
+    ("cPickle", "pickle"),
+    ("cStringIO", "io"),
+    ("commands", "subprocess"),
+    ("cookielib", "http.cookiejar"),
+    ("copy_reg", "copyreg"),
+    ("dbhash", "dbm.bsd"),
+    ("dbm", "dbm.ndbm"),
+    ("dumbdbm", "dbm.dumb"),
+    ("dummy_thread", "_dummy_thread"),
+    ("gdbm", "dbm.gnu"),
+    ("htmlentitydefs", "html.entities"),
+    ("httplib", "http.client"),
+    ("markupbase", "_markupbase"),
+    ("repr", "reprlib"),
+    ("robotparser", "urllib.robotparser"),
+    ("test.test_support", "test.support"),
+    ("thread", "_thread"),
+    ("tkColorChooser", "tkinter.colorchooser"),
+    ("tkCommonDialog", "tkinter.commondialog"),
+    ("tkFileDialog", "tkinter.filedialog"),
+    ("tkFont", "tkinter.font"),
+    ("tkMessageBox", "tkinter.messagebox"),
+    ("tkSimpleDialog", "tkinter.simpledialog"),
+    ("ttk", "tkinter.ttk"),
+    ("urllib2", "urllib.request"),
+    ("urlparse", "urllib.parse"),
+    ("whichdb", "dbm"),
+    ("xmlrpclib", "xmlrpc.client"),
+];
+
+/// A module and a name in it.
+type Name = (&'static str, &'static str);
+
+/// Classes and functions that moved in Python 3. This is `NAME_MAPPING` from
+/// Python's `_compat_pickle`, sorted so it can be searched.
+static NAME_MAPPING: &[(Name, Name)] = &[
+    (
+        ("UserDict", "IterableUserDict"),
+        ("collections", "UserDict"),
+    ),
+    (("UserDict", "UserDict"), ("collections", "UserDict")),
+    (("UserList", "UserList"), ("collections", "UserList")),
+    (("UserString", "UserString"), ("collections", "UserString")),
+    (("__builtin__", "basestring"), ("builtins", "str")),
+    (("__builtin__", "intern"), ("sys", "intern")),
+    (("__builtin__", "long"), ("builtins", "int")),
+    (("__builtin__", "reduce"), ("functools", "reduce")),
+    (("__builtin__", "unichr"), ("builtins", "chr")),
+    (("__builtin__", "unicode"), ("builtins", "str")),
+    (("__builtin__", "xrange"), ("builtins", "range")),
+    (
+        ("_multiprocessing", "Connection"),
+        ("multiprocessing.connection", "Connection"),
+    ),
+    (("_socket", "fromfd"), ("socket", "fromfd")),
+    (
+        ("exceptions", "ArithmeticError"),
+        ("builtins", "ArithmeticError"),
+    ),
+    (
+        ("exceptions", "AssertionError"),
+        ("builtins", "AssertionError"),
+    ),
+    (
+        ("exceptions", "AttributeError"),
+        ("builtins", "AttributeError"),
+    ),
+    (
+        ("exceptions", "BaseException"),
+        ("builtins", "BaseException"),
+    ),
+    (("exceptions", "BufferError"), ("builtins", "BufferError")),
+    (("exceptions", "BytesWarning"), ("builtins", "BytesWarning")),
+    (
+        ("exceptions", "DeprecationWarning"),
+        ("builtins", "DeprecationWarning"),
+    ),
+    (("exceptions", "EOFError"), ("builtins", "EOFError")),
+    (
+        ("exceptions", "EnvironmentError"),
+        ("builtins", "EnvironmentError"),
+    ),
+    (("exceptions", "Exception"), ("builtins", "Exception")),
+    (
+        ("exceptions", "FloatingPointError"),
+        ("builtins", "FloatingPointError"),
+    ),
+    (
+        ("exceptions", "FutureWarning"),
+        ("builtins", "FutureWarning"),
+    ),
+    (
+        ("exceptions", "GeneratorExit"),
+        ("builtins", "GeneratorExit"),
+    ),
+    (("exceptions", "IOError"), ("builtins", "IOError")),
+    (("exceptions", "ImportError"), ("builtins", "ImportError")),
+    (
+        ("exceptions", "ImportWarning"),
+        ("builtins", "ImportWarning"),
+    ),
+    (
+        ("exceptions", "IndentationError"),
+        ("builtins", "IndentationError"),
+    ),
+    (("exceptions", "IndexError"), ("builtins", "IndexError")),
+    (("exceptions", "KeyError"), ("builtins", "KeyError")),
+    (
+        ("exceptions", "KeyboardInterrupt"),
+        ("builtins", "KeyboardInterrupt"),
+    ),
+    (("exceptions", "LookupError"), ("builtins", "LookupError")),
+    (("exceptions", "MemoryError"), ("builtins", "MemoryError")),
+    (("exceptions", "NameError"), ("builtins", "NameError")),
+    (
+        ("exceptions", "NotImplementedError"),
+        ("builtins", "NotImplementedError"),
+    ),
+    (("exceptions", "OSError"), ("builtins", "OSError")),
+    (
+        ("exceptions", "OverflowError"),
+        ("builtins", "OverflowError"),
+    ),
+    (
+        ("exceptions", "PendingDeprecationWarning"),
+        ("builtins", "PendingDeprecationWarning"),
+    ),
+    (
+        ("exceptions", "ReferenceError"),
+        ("builtins", "ReferenceError"),
+    ),
+    (("exceptions", "RuntimeError"), ("builtins", "RuntimeError")),
+    (
+        ("exceptions", "RuntimeWarning"),
+        ("builtins", "RuntimeWarning"),
+    ),
+    (("exceptions", "StandardError"), ("builtins", "Exception")),
+    (
+        ("exceptions", "StopIteration"),
+        ("builtins", "StopIteration"),
+    ),
+    (("exceptions", "SyntaxError"), ("builtins", "SyntaxError")),
+    (
+        ("exceptions", "SyntaxWarning"),
+        ("builtins", "SyntaxWarning"),
+    ),
+    (("exceptions", "SystemError"), ("builtins", "SystemError")),
+    (("exceptions", "SystemExit"), ("builtins", "SystemExit")),
+    (("exceptions", "TabError"), ("builtins", "TabError")),
+    (("exceptions", "TypeError"), ("builtins", "TypeError")),
+    (
+        ("exceptions", "UnboundLocalError"),
+        ("builtins", "UnboundLocalError"),
+    ),
+    (
+        ("exceptions", "UnicodeDecodeError"),
+        ("builtins", "UnicodeDecodeError"),
+    ),
+    (
+        ("exceptions", "UnicodeEncodeError"),
+        ("builtins", "UnicodeEncodeError"),
+    ),
+    (("exceptions", "UnicodeError"), ("builtins", "UnicodeError")),
+    (
+        ("exceptions", "UnicodeTranslateError"),
+        ("builtins", "UnicodeTranslateError"),
+    ),
+    (
+        ("exceptions", "UnicodeWarning"),
+        ("builtins", "UnicodeWarning"),
+    ),
+    (("exceptions", "UserWarning"), ("builtins", "UserWarning")),
+    (("exceptions", "ValueError"), ("builtins", "ValueError")),
+    (("exceptions", "Warning"), ("builtins", "Warning")),
+    (
+        ("exceptions", "ZeroDivisionError"),
+        ("builtins", "ZeroDivisionError"),
+    ),
+    (("itertools", "ifilter"), ("builtins", "filter")),
+    (("itertools", "ifilterfalse"), ("itertools", "filterfalse")),
+    (("itertools", "imap"), ("builtins", "map")),
+    (("itertools", "izip"), ("builtins", "zip")),
+    (("itertools", "izip_longest"), ("itertools", "zip_longest")),
+    (
+        ("multiprocessing", "AuthenticationError"),
+        ("multiprocessing.context", "AuthenticationError"),
+    ),
+    (
+        ("multiprocessing", "BufferTooShort"),
+        ("multiprocessing.context", "BufferTooShort"),
+    ),
+    (
+        ("multiprocessing", "ProcessError"),
+        ("multiprocessing.context", "ProcessError"),
+    ),
+    (
+        ("multiprocessing", "TimeoutError"),
+        ("multiprocessing.context", "TimeoutError"),
+    ),
+    (
+        ("multiprocessing.forking", "Popen"),
+        ("multiprocessing.popen_fork", "Popen"),
+    ),
+    (
+        ("multiprocessing.process", "Process"),
+        ("multiprocessing.context", "Process"),
+    ),
+    (("socket", "_socketobject"), ("socket", "SocketType")),
+    (
+        ("urllib", "ContentTooShortError"),
+        ("urllib.error", "ContentTooShortError"),
+    ),
+    (("urllib", "getproxies"), ("urllib.request", "getproxies")),
+    (
+        ("urllib", "pathname2url"),
+        ("urllib.request", "pathname2url"),
+    ),
+    (("urllib", "quote"), ("urllib.parse", "quote")),
+    (("urllib", "quote_plus"), ("urllib.parse", "quote_plus")),
+    (("urllib", "unquote"), ("urllib.parse", "unquote")),
+    (("urllib", "unquote_plus"), ("urllib.parse", "unquote_plus")),
+    (
+        ("urllib", "url2pathname"),
+        ("urllib.request", "url2pathname"),
+    ),
+    (("urllib", "urlcleanup"), ("urllib.request", "urlcleanup")),
+    (("urllib", "urlencode"), ("urllib.parse", "urlencode")),
+    (("urllib", "urlopen"), ("urllib.request", "urlopen")),
+    (("urllib", "urlretrieve"), ("urllib.request", "urlretrieve")),
+    (("urllib2", "HTTPError"), ("urllib.error", "HTTPError")),
+    (("urllib2", "URLError"), ("urllib.error", "URLError")),
+    (("whichdb", "whichdb"), ("dbm", "whichdb")),
+];
+
+/// Map a Python 2 module and name to the Python 3 equivalent the same way
+/// Python's unpickler does with `fix_imports`. Names that don't need to be
+/// changed are returned as is.
+pub(crate) fn fix_import<'a>(module: &'a str, name: &'a str) -> (&'a str, &'a str) {
+    if let Ok(idx) = NAME_MAPPING.binary_search_by_key(&(module, name), |(k, _)| *k) {
+        return NAME_MAPPING[idx].1;
+    }
+    match IMPORT_MAPPING.binary_search_by_key(&module, |(k, _)| k) {
+        Ok(idx) => (IMPORT_MAPPING[idx].1, name),
+        Err(_) => (module, name),
+    }
+}
+
+/// Decode a Python 2 `str` from `STRING`, `BINSTRING` or `SHORT_BINSTRING`.
+/// You get `None` if `op` is something else.
+pub(crate) fn decode_string<'a>(
+    op: &PickleOp<'a>,
+    encoding: StringEncoding,
+) -> Result<Option<Value<'a>>> {
+    let bytes = match op {
+        PickleOp::BINSTRING(b) | PickleOp::SHORT_BINSTRING(b) => Cow::Borrowed(*b),
+        PickleOp::STRING(s) => unescape(s)?,
+        _ => return Ok(None),
+    };
+    let bytes_to_str = |bytes: Cow<'a, [u8]>| match bytes {
+        Cow::Borrowed(b) => std::str::from_utf8(b).map(Cow::Borrowed).ok(),
+        Cow::Owned(b) => String::from_utf8(b).map(Cow::Owned).ok(),
+    };
+    Ok(Some(match encoding {
+        StringEncoding::Bytes => Value::Bytes(bytes),
+        StringEncoding::Utf8OrBytes => match std::str::from_utf8(&bytes) {
+            Ok(_) => Value::String(bytes_to_str(bytes).expect("Impossible: Bad UTF8")),
+            Err(_) => Value::Bytes(bytes),
+        },
+        StringEncoding::Utf8 => match bytes_to_str(bytes) {
+            Some(s) => Value::String(s),
+            None => bail!("Could not decode Python 2 string as UTF8"),
+        },
+        StringEncoding::Ascii if !bytes.is_ascii() => {
+            bail!("Could not decode Python 2 string as ASCII")
+        }
+        StringEncoding::Ascii => Value::String(bytes_to_str(bytes).expect("Impossible: Bad ASCII")),
+        StringEncoding::Latin1 if bytes.is_ascii() => {
+            Value::String(bytes_to_str(bytes).expect("Impossible: Bad ASCII"))
+        }
+        StringEncoding::Latin1 => {
+            Value::String(Cow::Owned(bytes.iter().map(|b| *b as char).collect()))
+        }
+    }))
+}
+
+/// Decode the text from a `UNICODE` op, which is raw-unicode-escaped:
+/// `\uXXXX` and `\UXXXXXXXX` are escapes and every other byte is a
+/// Latin-1 character. Pickle escapes backslashes and newlines this way too.
+pub(crate) fn decode_unicode(b: &[u8]) -> Result<Cow<'_, str>> {
+    if b.is_ascii() && !b.contains(&b'\\') {
+        return Ok(Cow::Borrowed(
+            std::str::from_utf8(b).expect("Impossible: Bad ASCII"),
+        ));
+    }
+    let mut result = String::with_capacity(b.len());
+    let mut it = b.iter().copied();
+    while let Some(c) = it.next() {
+        if c != b'\\' {
+            result.push(c as char);
+            continue;
+        }
+        let len = match it.next() {
+            Some(b'u') => 4,
+            Some(b'U') => 8,
+            // Anything else is just a backslash, including a backslash
+            // that escapes another one.
+            Some(c) => {
+                result.extend(['\\', c as char]);
+                continue;
+            }
+            None => {
+                result.push('\\');
+                break;
+            }
+        };
+        let mut val = 0;
+        for _ in 0..len {
+            match it.next().and_then(|d| (d as char).to_digit(16)) {
+                Some(d) => val = val * 16 + d,
+                None => bail!("Truncated \\u escape in UNICODE"),
+            }
+        }
+        match char::from_u32(val) {
+            Some(c) => result.push(c),
+            None => bail!("Bad character {val:#x} in UNICODE"),
+        }
+    }
+    Ok(Cow::Owned(result))
+}
+
+/// Undo the escaping in a `STRING` op. These are Python 2 string literals
+/// (including the quotes) and they get unescaped like `codecs.escape_decode`.
+fn unescape(s: &str) -> Result<Cow<'_, [u8]>> {
+    let s = match s.as_bytes() {
+        [q, s @ .., q2] if q == q2 && (*q == b'\'' || *q == b'"') => s,
+        _ => bail!("Bad quoting for STRING"),
+    };
+    if !s.contains(&b'\\') {
+        return Ok(Cow::Borrowed(s));
+    }
+    let mut result = Vec::with_capacity(s.len());
+    let mut it = s.iter().copied().peekable();
+    while let Some(c) = it.next() {
+        if c != b'\\' {
+            result.push(c);
+            continue;
+        }
+        let c = match it.next() {
+            Some(c) => c,
+            None => bail!("Trailing backslash in STRING"),
+        };
+        match c {
+            b'\n' => (),
+            b'\\' | b'\'' | b'"' => result.push(c),
+            b'a' => result.push(7),
+            b'b' => result.push(8),
+            b'f' => result.push(12),
+            b'n' => result.push(b'\n'),
+            b'r' => result.push(b'\r'),
+            b't' => result.push(b'\t'),
+            b'v' => result.push(11),
+            b'x' => {
+                let hex = [it.next(), it.next()];
+                let digit = |d: Option<u8>| d.and_then(|d| (d as char).to_digit(16));
+                match hex.map(digit) {
+                    [Some(hi), Some(lo)] => result.push((hi * 16 + lo) as u8),
+                    _ => bail!("Bad \\x escape in STRING"),
+                }
+            }
+            b'0'..=b'7' => {
+                let mut val = (c - b'0') as u32;
+                for _ in 0..2 {
+                    match it.peek() {
+                        Some(d @ b'0'..=b'7') => {
+                            val = val * 8 + (d - b'0') as u32;
+                            it.next();
+                        }
+                        _ => break,
+                    }
+                }
+                result.push(val as u8);
+            }
+            // Unknown escapes are left alone.
+            c => result.extend([b'\\', c]),
+        }
+    }
+    Ok(Cow::Owned(result))
+}
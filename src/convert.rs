@@ -0,0 +1,291 @@
+//! `FromValue` gets plain Rust types out of a value without going through
+//! Serde. It's implemented for `i64`, `i32`, `u64`, `u32`, `usize`, `f64`,
+//! `bool`, `String`, `&str`, `Vec<u8>` (from bytes or a bytearray), `Vec<T>`,
+//! `HashMap<K, V>`, `BTreeMap<K, V>`, `Option<T>` (where `None` is Python's
+//! `None`) and tuples of up to 12 items. All of them can also be converted
+//! with `TryFrom<&Value>`.
+//!
+//! ```
+//! # use repugnant_pickle::{SequenceType, Value};
+//! let val = Value::Seq(SequenceType::Tuple, vec![Value::Int(2), Value::Int(-3)]);
+//! let err = val.extract::<Vec<usize>>().unwrap_err();
+//! assert_eq!(err.to_string(), "-3 doesn't fit in usize at 1");
+//! let (a, b): (i64, i64) = (&val).try_into().unwrap();
+//! assert_eq!((a, b), (2, -3));
+//! ```
+//!
+//! Sequences can be any kind of `Value::Seq`, and things like `deque`s and
+//! namedtuples work too. Dicts can be anything `Value::as_dict_pairs` works
+//! for. Enum members convert like their values. References don't get looked
+//! up, see [`ValueView`](crate::ValueView).
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    hash::Hash,
+};
+
+use crate::{
+    path::{Path, PathKey},
+    repr::ReprOptions,
+    value::*,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Why converting a value with `FromValue` failed.
+pub struct ConvertError {
+    /// Where the value that couldn't be converted is, starting from the
+    /// one you were converting.
+    pub path: Path<'static>,
+
+    /// What went wrong.
+    pub message: String,
+}
+
+impl ConvertError {
+    fn new(message: String) -> Self {
+        Self {
+            path: Path::default(),
+            message,
+        }
+    }
+
+    fn expected(what: &str, val: &Value<'_>) -> Self {
+        Self::new(format!("Expected {what}, found {}", val.type_name()))
+    }
+
+    /// Put a key in front of the path, for errors that came from inside
+    /// a container.
+    fn under(mut self, key: PathKey<'_>) -> Self {
+        self.path.0.insert(0, key.into_owned());
+        self
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.0.is_empty() {
+            true => f.write_str(&self.message),
+            false => write!(f, "{} at {}", self.message, self.path),
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+/// Types you can get out of a value. `'v` is how long the value is borrowed
+/// for, so things like `&str` can borrow from it.
+pub trait FromValue<'v>: Sized {
+    fn from_value(val: &'v Value<'_>) -> Result<Self, ConvertError>;
+}
+
+impl<'a> Value<'a> {
+    /// Convert this into something like an `i64` or a `Vec<String>`. See
+    /// `FromValue`.
+    pub fn extract<'v, T: FromValue<'v>>(&'v self) -> Result<T, ConvertError> {
+        T::from_value(self)
+    }
+}
+
+/// Enum members convert like their values.
+fn target<'v, 'a>(val: &'v Value<'a>) -> &'v Value<'a> {
+    match val {
+        Value::Enum { value, .. } => target(value),
+        val => val,
+    }
+}
+
+fn items<'v, 'a>(val: &'v Value<'a>) -> Result<&'v [Value<'a>], ConvertError> {
+    match target(val) {
+        Value::Seq(_, items) => Ok(items),
+        Value::Collection(coll) => match &coll.value {
+            Value::Seq(_, items) => Ok(items),
+            _ => Err(ConvertError::expected("a sequence", val)),
+        },
+        val => Err(ConvertError::expected("a sequence", val)),
+    }
+}
+
+fn item<'v, T: FromValue<'v>>(items: &'v [Value<'_>], idx: usize) -> Result<T, ConvertError> {
+    T::from_value(&items[idx]).map_err(|e| e.under(PathKey::from(idx)))
+}
+
+/// Convert the key/value pairs of a dict, with the keys in error paths.
+fn pairs<'v, K: FromValue<'v>, V: FromValue<'v>, C: FromIterator<(K, V)>>(
+    val: &'v Value<'_>,
+) -> Result<C, ConvertError> {
+    let pairs = target(val)
+        .as_dict_pairs()
+        .ok_or_else(|| ConvertError::expected("a dict", val))?;
+    pairs
+        .iter()
+        .map(|(k, v)| {
+            let key = || match k {
+                Value::String(s) => PathKey::Str(s.to_string().into()),
+                Value::Int(i) => PathKey::Int(*i),
+                k => PathKey::Str(k.repr(ReprOptions::default()).to_string().into()),
+            };
+            let k = K::from_value(k).map_err(|e| e.under(key()))?;
+            let v = V::from_value(v).map_err(|e| e.under(key()))?;
+            Ok((k, v))
+        })
+        .collect()
+}
+
+macro_rules! int_impl {
+    ($($t:ty),*) => {$(
+        impl<'v> FromValue<'v> for $t {
+            fn from_value(val: &'v Value<'_>) -> Result<Self, ConvertError> {
+                let too_big = |i: &dyn fmt::Display| {
+                    ConvertError::new(format!("{i} doesn't fit in {}", stringify!($t)))
+                };
+                match target(val) {
+                    Value::Int(i) => Self::try_from(*i).map_err(|_| too_big(i)),
+                    Value::BigInt(i) => Self::try_from(i).map_err(|_| too_big(i)),
+                    _ => Err(ConvertError::expected("an int", val)),
+                }
+            }
+        }
+    )*};
+}
+
+int_impl!(i64, i32, u64, u32, usize);
+
+impl<'v> FromValue<'v> for f64 {
+    fn from_value(val: &'v Value<'_>) -> Result<Self, ConvertError> {
+        match target(val) {
+            Value::Float(f) => Ok(*f),
+            Value::Int(i) => Ok(*i as f64),
+            _ => Err(ConvertError::expected("a float", val)),
+        }
+    }
+}
+
+impl<'v> FromValue<'v> for bool {
+    fn from_value(val: &'v Value<'_>) -> Result<Self, ConvertError> {
+        match target(val) {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(ConvertError::expected("a bool", val)),
+        }
+    }
+}
+
+impl<'v> FromValue<'v> for &'v str {
+    fn from_value(val: &'v Value<'_>) -> Result<Self, ConvertError> {
+        match target(val) {
+            Value::String(s) => Ok(s),
+            _ => Err(ConvertError::expected("a string", val)),
+        }
+    }
+}
+
+impl<'v> FromValue<'v> for String {
+    fn from_value(val: &'v Value<'_>) -> Result<Self, ConvertError> {
+        <&str>::from_value(val).map(str::to_string)
+    }
+}
+
+impl<'v> FromValue<'v> for Vec<u8> {
+    fn from_value(val: &'v Value<'_>) -> Result<Self, ConvertError> {
+        match target(val) {
+            Value::Bytes(b) | Value::ByteArray(b) => Ok(b.to_vec()),
+            _ => Err(ConvertError::expected("bytes", val)),
+        }
+    }
+}
+
+impl<'v, T: FromValue<'v>> FromValue<'v> for Vec<T> {
+    fn from_value(val: &'v Value<'_>) -> Result<Self, ConvertError> {
+        let items = items(val)?;
+        (0..items.len()).map(|idx| item(items, idx)).collect()
+    }
+}
+
+impl<'v, T: FromValue<'v>> FromValue<'v> for Option<T> {
+    fn from_value(val: &'v Value<'_>) -> Result<Self, ConvertError> {
+        match val {
+            Value::None => Ok(None),
+            val => T::from_value(val).map(Some),
+        }
+    }
+}
+
+impl<'v, K: FromValue<'v> + Eq + Hash, V: FromValue<'v>> FromValue<'v> for HashMap<K, V> {
+    fn from_value(val: &'v Value<'_>) -> Result<Self, ConvertError> {
+        pairs(val)
+    }
+}
+
+impl<'v, K: FromValue<'v> + Ord, V: FromValue<'v>> FromValue<'v> for BTreeMap<K, V> {
+    fn from_value(val: &'v Value<'_>) -> Result<Self, ConvertError> {
+        pairs(val)
+    }
+}
+
+macro_rules! tuple_impl {
+    ($len:literal: $($name:ident $idx:tt),+) => {
+        impl<'v, $($name: FromValue<'v>),+> FromValue<'v> for ($($name,)+) {
+            fn from_value(val: &'v Value<'_>) -> Result<Self, ConvertError> {
+                let items = items(val)?;
+                if items.len() != $len {
+                    return Err(ConvertError::new(format!(
+                        "Expected {} items, found {}",
+                        $len,
+                        items.len()
+                    )));
+                }
+                Ok(($(item::<$name>(items, $idx)?,)+))
+            }
+        }
+
+        impl<'v, 'a, $($name: FromValue<'v>),+> TryFrom<&'v Value<'a>> for ($($name,)+) {
+            type Error = ConvertError;
+
+            fn try_from(val: &'v Value<'a>) -> Result<Self, ConvertError> {
+                Self::from_value(val)
+            }
+        }
+    };
+}
+
+tuple_impl!(1: A 0);
+tuple_impl!(2: A 0, B 1);
+tuple_impl!(3: A 0, B 1, C 2);
+tuple_impl!(4: A 0, B 1, C 2, D 3);
+tuple_impl!(5: A 0, B 1, C 2, D 3, E 4);
+tuple_impl!(6: A 0, B 1, C 2, D 3, E 4, F 5);
+tuple_impl!(7: A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+tuple_impl!(8: A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+tuple_impl!(9: A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+tuple_impl!(10: A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+tuple_impl!(11: A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+tuple_impl!(12: A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+
+macro_rules! try_from_impl {
+    ($([$($gen:tt)*] $t:ty),* $(,)?) => {$(
+        impl<'v, 'a, $($gen)*> TryFrom<&'v Value<'a>> for $t {
+            type Error = ConvertError;
+
+            fn try_from(val: &'v Value<'a>) -> Result<Self, ConvertError> {
+                Self::from_value(val)
+            }
+        }
+    )*};
+}
+
+try_from_impl!(
+    [] i64,
+    [] i32,
+    [] u64,
+    [] u32,
+    [] usize,
+    [] f64,
+    [] bool,
+    [] &'v str,
+    [] String,
+    [] Vec<u8>,
+    [T: FromValue<'v>] Vec<T>,
+    [T: FromValue<'v>] Option<T>,
+    [K: FromValue<'v> + Eq + Hash, V: FromValue<'v>] HashMap<K, V>,
+    [K: FromValue<'v> + Ord, V: FromValue<'v>] BTreeMap<K, V>,
+);
@@ -0,0 +1,319 @@
+use std::{fmt, slice};
+
+use serde::de::{
+    self, Deserialize, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+
+use crate::{array::ArrayData, value::*};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An error from deserializing a `Value`.
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+/// Deserialize something from a `Value`. You'll want to resolve references
+/// first (`evaluate` does that by default), a `Value::Ref` is an error.
+///
+/// Dicts work for structs and maps, and any kind of `Value::Seq` works for
+/// sequences and tuples. Objects get deserialized from their state: That's
+/// the dict items for things like `collections.OrderedDict`, otherwise
+/// the state from `BUILD`. A `Value::Class` deserializes as a string
+/// like `"module.name"`. A `Value::Enum` deserializes as the name of the
+/// member if it has one and you want a string or an enum, otherwise as
+/// its value. A `Value::Array` is a sequence of its items, except `u`
+/// arrays which are strings.
+///
+/// Strings and bytes can be borrowed from the value.
+pub fn from_value<'de, 'a: 'de, T: Deserialize<'de>>(value: &'de Value<'a>) -> Result<T, Error> {
+    T::deserialize(value)
+}
+
+/// What an object gets deserialized from.
+enum Content<'de, 'a> {
+    Value(&'de Value<'a>),
+    Map(&'de [(Value<'a>, Value<'a>)]),
+    Seq(&'de [Value<'a>]),
+}
+
+/// Find the state for an object. You get `None` if it's not an object
+/// or we can't tell what the state is.
+fn object_content<'de, 'a>(val: &'de Value<'a>) -> Option<Content<'de, 'a>> {
+    Some(match val {
+        Value::PyObject(obj) => match (&obj.state, &obj.slotstate) {
+            (Some(state), _) | (None, Some(state)) => Content::Value(state),
+            _ if obj.list_items.is_empty() => Content::Map(&obj.dict_items),
+            _ => Content::Seq(&obj.list_items),
+        },
+        // Things like OrderedDict get built with extra state, but the
+        // items are what we want.
+        Value::Build(target, state) => match object_content(target) {
+            Some(Content::Map(items)) if !items.is_empty() => Content::Map(items),
+            _ => Content::Value(state),
+        },
+        Value::Collection(coll) => match &coll.value {
+            Value::Dict(items) => Content::Map(items),
+            Value::Seq(_, items) => Content::Seq(items),
+            value => Content::Value(value),
+        },
+        Value::Global(..) | Value::Object(..) => Content::Map(val.dict_items()?),
+        _ => return None,
+    })
+}
+
+fn visit_content<'de, 'a: 'de, V: Visitor<'de>>(
+    content: Content<'de, 'a>,
+    visitor: V,
+) -> Result<V::Value, Error> {
+    match content {
+        Content::Value(val) => de::Deserializer::deserialize_any(val, visitor),
+        Content::Map(items) => visitor.visit_map(MapDeserializer::new(items)),
+        Content::Seq(items) => visitor.visit_seq(SeqDeserializer(items.iter())),
+    }
+}
+
+/// Visit the items of an array as a sequence.
+fn visit_items<'de, T, V>(items: &[T], visitor: V) -> Result<V::Value, Error>
+where
+    T: Copy + IntoDeserializer<'de, Error>,
+    V: Visitor<'de>,
+{
+    let seq = de::value::SeqDeserializer::new(items.iter().copied());
+    de::Deserializer::deserialize_any(seq, visitor)
+}
+
+impl<'de, 'a: 'de> de::Deserializer<'de> for &'de Value<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        deeper(|| match self {
+            Value::None => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(*b),
+            Value::Int(i) => visitor.visit_i64(*i),
+            Value::BigInt(bi) => {
+                if let Ok(i) = i128::try_from(bi) {
+                    visitor.visit_i128(i)
+                } else if let Ok(u) = u128::try_from(bi) {
+                    visitor.visit_u128(u)
+                } else {
+                    Err(de::Error::custom(format!("Integer {bi} is too big")))
+                }
+            }
+            Value::Float(f) => visitor.visit_f64(*f),
+            Value::String(s) => visitor.visit_borrowed_str(s),
+            Value::Bytes(b) | Value::ByteArray(b) => visitor.visit_borrowed_bytes(b),
+            Value::Seq(_, items) => visitor.visit_seq(SeqDeserializer(items.iter())),
+            Value::Dict(items) => visitor.visit_map(MapDeserializer::new(items)),
+            Value::Class { module, name } => visitor.visit_string(format!("{module}.{name}")),
+            Value::Enum { value, .. } => value.deserialize_any(visitor),
+            Value::Array(array) => match &array.data {
+                ArrayData::I8(items) => visit_items(items, visitor),
+                ArrayData::U8(items) => visit_items(items, visitor),
+                ArrayData::I16(items) => visit_items(items, visitor),
+                ArrayData::U16(items) => visit_items(items, visitor),
+                ArrayData::I32(items) => visit_items(items, visitor),
+                ArrayData::U32(items) => visit_items(items, visitor),
+                ArrayData::I64(items) => visit_items(items, visitor),
+                ArrayData::U64(items) => visit_items(items, visitor),
+                ArrayData::F32(items) => visit_items(items, visitor),
+                ArrayData::F64(items) => visit_items(items, visitor),
+                ArrayData::Unicode(s) => visitor.visit_borrowed_str(s),
+            },
+            Value::Ref(_) => Err(de::Error::custom(
+                "Can't deserialize an unresolved reference",
+            )),
+            val => match object_content(val) {
+                Some(content) => visit_content(content, visitor),
+                None => Err(de::Error::custom(format!(
+                    "Can't deserialize {}",
+                    val.type_name()
+                ))),
+            },
+        })
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::None => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        // Either just the variant name, or a dict with the variant
+        // name and its value like serde's externally tagged enums.
+        match self {
+            Value::String(_) | Value::Enum { name: Some(_), .. } => {
+                visitor.visit_enum(EnumDeserializer(self, None))
+            }
+            Value::Enum { value, .. } => value.deserialize_enum(_name, _variants, visitor),
+            Value::Dict(items) if items.len() == 1 => {
+                visitor.visit_enum(EnumDeserializer(&items[0].0, Some(&items[0].1)))
+            }
+            val => Err(de::Error::custom(format!(
+                "Can't deserialize {} as an enum",
+                val.type_name()
+            ))),
+        }
+    }
+
+    // Enum members are their names when you want a string and their values
+    // otherwise, so an `IntEnum` still works for an int.
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Enum {
+                name: Some(name), ..
+            } => visitor.visit_borrowed_str(name),
+            val => val.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        ignored_any
+    }
+}
+
+struct SeqDeserializer<'de, 'a>(slice::Iter<'de, Value<'a>>);
+
+impl<'de, 'a: 'de> SeqAccess<'de> for SeqDeserializer<'de, 'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        self.0.next().map(|val| seed.deserialize(val)).transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct MapDeserializer<'de, 'a> {
+    items: slice::Iter<'de, (Value<'a>, Value<'a>)>,
+    value: Option<&'de Value<'a>>,
+}
+
+impl<'de, 'a> MapDeserializer<'de, 'a> {
+    fn new(items: &'de [(Value<'a>, Value<'a>)]) -> Self {
+        Self {
+            items: items.iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de, 'a: 'de> MapAccess<'de> for MapDeserializer<'de, 'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let Some((k, v)) = self.items.next() else {
+            return Ok(None);
+        };
+        self.value = Some(v);
+        seed.deserialize(k).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let val = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("Map value requested before key"))?;
+        seed.deserialize(val)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+/// The variant name and its value, if there is one.
+struct EnumDeserializer<'de, 'a>(&'de Value<'a>, Option<&'de Value<'a>>);
+
+impl<'de, 'a: 'de> EnumAccess<'de> for EnumDeserializer<'de, 'a> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        Ok((seed.deserialize(self.0)?, self))
+    }
+}
+
+impl<'de, 'a: 'de> VariantAccess<'de> for EnumDeserializer<'de, 'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.1 {
+            None | Some(Value::None) => Ok(()),
+            Some(val) => Err(de::Error::custom(format!(
+                "Expected a unit variant, got {}",
+                val.type_name()
+            ))),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        match self.1 {
+            Some(val) => seed.deserialize(val),
+            None => seed.deserialize(().into_deserializer()),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        match self.1 {
+            Some(val) => de::Deserializer::deserialize_any(val, visitor),
+            None => Err(de::Error::custom("Expected a tuple variant")),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.1 {
+            Some(val) => de::Deserializer::deserialize_any(val, visitor),
+            None => Err(de::Error::custom("Expected a struct variant")),
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use std::{borrow::Cow, collections::HashMap, fmt};
+
+use crate::{
+    key::{self, KeyMode, Norm},
+    object::PyObject,
+    path::{Path, PathKey},
+    query::{child_items, class_name, Child},
+    value::*,
+};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// Options for `diff`.
+pub struct DiffOptions {
+    /// Floats that differ by this much or less count as the same, including
+    /// the parts of complex numbers, the items of arrays and floats inside
+    /// things that get compared as a whole, like the arguments of an object.
+    /// The default of `0.0` means they have to be exactly the same, although
+    /// NaN is always the same as NaN.
+    pub float_tolerance: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// What happened to a value.
+pub enum Change<'m, 'a, 'b> {
+    /// Only the new value has this path.
+    Added(&'m Value<'b>),
+
+    /// Only the old value has this path.
+    Removed(&'m Value<'a>),
+
+    /// The value is a different type, like an int that turned into a
+    /// string or an object of a different class. What's inside doesn't
+    /// get compared.
+    TypeChanged(&'m Value<'a>, &'m Value<'b>),
+
+    /// The value is the same type but different. For things like dicts
+    /// and lists you get the changes to what's in them instead, so this
+    /// is mostly for things like numbers and strings.
+    ValueChanged(&'m Value<'a>, &'m Value<'b>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A difference between two values, from `diff`. These display on one line
+/// like `~ /lr: 0.001 -> 0.0005`, with `+` for added, `-` for removed, `!`
+/// for type changes and `~` for value changes.
+pub struct Difference<'m, 'a, 'b> {
+    /// Where the difference is. This works with `Value::at` on the old or
+    /// new value, as long as the key was a string or an int.
+    pub path: Path<'m>,
+
+    pub change: Change<'m, 'a, 'b>,
+}
+
+impl fmt::Display for Difference<'_, '_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = &self.path;
+        match &self.change {
+            Change::Added(new) => write!(f, "+ /{path}: {new:#}"),
+            Change::Removed(old) => write!(f, "- /{path}: {old:#}"),
+            Change::TypeChanged(old, new) => write!(f, "! /{path}: {old:#} -> {new:#}"),
+            Change::ValueChanged(old, new) => write!(f, "~ /{path}: {old:#} -> {new:#}"),
+        }
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Compare with another value. See `diff`.
+    pub fn diff<'m, 'b>(
+        &'m self,
+        new: &'m Value<'b>,
+        options: &DiffOptions,
+    ) -> Vec<Difference<'m, 'a, 'b>> {
+        diff(self, new, options)
+    }
+}
+
+/// Find out what changed between two values. Dict entries (and attributes)
+/// are matched up by key, set members by value and sequence items by index,
+/// using the same paths as `Value::at`. References don't get looked up, see
+/// [`ValueView`](crate::ValueView).
+///
+/// The differences are in the order they appear in the old value, with
+/// additions after the other changes in the same container.
+pub fn diff<'m, 'a, 'b>(
+    old: &'m Value<'a>,
+    new: &'m Value<'b>,
+    options: &DiffOptions,
+) -> Vec<Difference<'m, 'a, 'b>> {
+    let mut found = Vec::new();
+    compare(&mut found, Path::default(), old, new, options);
+    found
+}
+
+fn compare<'m, 'a, 'b>(
+    found: &mut Vec<Difference<'m, 'a, 'b>>,
+    path: Path<'m>,
+    old: &'m Value<'a>,
+    new: &'m Value<'b>,
+    options: &DiffOptions,
+) {
+    deeper(|| compare_inner(found, path, old, new, options))
+}
+
+fn compare_inner<'m, 'a, 'b>(
+    found: &mut Vec<Difference<'m, 'a, 'b>>,
+    path: Path<'m>,
+    old: &'m Value<'a>,
+    new: &'m Value<'b>,
+    options: &DiffOptions,
+) {
+    let (fold, fnew) = (fixed(old), fixed(new));
+    if type_of(&fold) != type_of(&fnew) {
+        found.push(Difference {
+            path,
+            change: Change::TypeChanged(old, new),
+        });
+        return;
+    }
+    let tolerance = options.float_tolerance;
+    let (ochildren, nchildren) = (child_items(old), child_items(new));
+    if ochildren.is_empty() && nchildren.is_empty() {
+        if !key::near(&fold, &fnew, KeyMode::Strict, tolerance) {
+            found.push(Difference {
+                path,
+                change: Change::ValueChanged(old, new),
+            });
+        }
+        return;
+    }
+    // Things a path can't get to get compared as part of the container.
+    let (oextra, nextra) = (opaque_parts(old), opaque_parts(new));
+    if oextra.len() != nextra.len()
+        || oextra
+            .iter()
+            .zip(&nextra)
+            .any(|(o, n)| !key::near(o, n, KeyMode::Strict, tolerance))
+    {
+        found.push(Difference {
+            path: path.clone(),
+            change: Change::ValueChanged(old, new),
+        });
+    }
+    // Like Python, the last one wins if there are duplicate keys.
+    let members = matches!(
+        fold.as_ref(),
+        Value::Seq(SequenceType::Set | SequenceType::FrozenSet, _)
+    );
+    let (oslots, nslots) = (slots(&ochildren, members), slots(&nchildren, members));
+    let nkeys = nslots
+        .iter()
+        .enumerate()
+        .map(|(idx, slot)| (slot, idx))
+        .collect::<HashMap<_, _>>();
+    let mut matched = vec![false; nchildren.len()];
+    let okeys = oslots
+        .iter()
+        .enumerate()
+        .map(|(idx, slot)| (slot, idx))
+        .collect::<HashMap<_, _>>();
+    for (idx, ((key, _, oval), slot)) in ochildren.iter().zip(&oslots).enumerate() {
+        if okeys[slot] != idx {
+            continue;
+        }
+        let path = path.join(key.clone());
+        match nkeys.get(slot) {
+            Some(&nidx) => {
+                matched[nidx] = true;
+                compare(found, path, oval, nchildren[nidx].2, options);
+            }
+            None => found.push(Difference {
+                path,
+                change: Change::Removed(oval),
+            }),
+        }
+    }
+    for (idx, ((key, _, nval), slot)) in nchildren.iter().zip(&nslots).enumerate() {
+        if !matched[idx] && nkeys[slot] == idx {
+            found.push(Difference {
+                path: path.join(key.clone()),
+                change: Change::Added(nval),
+            });
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash)]
+/// What children get matched up by. Dict keys are compared as values, so the
+/// string `"(1, 2)"` and the tuple `(1, 2)` aren't the same key even though
+/// their paths look the same. Set members are their own keys, since their
+/// order doesn't mean anything.
+enum Slot<'m> {
+    Key(Norm<'m>),
+    Index(PathKey<'m>),
+}
+
+fn slots<'m>(children: &[Child<'m, '_>], members: bool) -> Vec<Slot<'m>> {
+    children
+        .iter()
+        .map(|(path, key, val)| match key {
+            Some(key) => Slot::Key(Norm::new(key, KeyMode::Strict)),
+            None if members => Slot::Key(Norm::new(val, KeyMode::Strict)),
+            None => Slot::Index(path.clone()),
+        })
+        .collect()
+}
+
+/// Fix up a `Value::Raw` so it compares like what it would have been.
+fn fixed<'m, 'a>(val: &'m Value<'a>) -> Cow<'m, Value<'a>> {
+    match val {
+        Value::Raw(_) => fix_value(val.clone()).map_or(Cow::Borrowed(val), Cow::Owned),
+        val => Cow::Borrowed(val),
+    }
+}
+
+/// What counts as the type of a value. Calls and objects are the class they
+/// were made from, so an `OrderedDict` is the same type with or without
+/// `EvalOptions::object_model` or `EvalOptions::collections`.
+fn type_of(val: &Value<'_>) -> Cow<'static, str> {
+    match val {
+        Value::Global(..)
+        | Value::Object(..)
+        | Value::App(..)
+        | Value::Build(..)
+        | Value::PyObject(_)
+        | Value::Collection(_)
+        | Value::Enum { .. } => class_name(val).map_or(Cow::Borrowed(val.type_name()), Cow::Owned),
+        Value::BigInt(_) => Cow::Borrowed("int"),
+        _ => Cow::Borrowed(val.type_name()),
+    }
+}
+
+/// The parts of a container that `child_values` doesn't include, like the
+/// state of an object when it isn't a dict.
+fn opaque_parts<'m, 'a>(val: &'m Value<'a>) -> Vec<&'m Value<'a>> {
+    let opaque_state = |state: &'m Value<'a>| match state {
+        Value::Seq(SequenceType::Tuple, items) if items.len() == 2 => items
+            .iter()
+            .filter(|st| st.dict_items().is_none())
+            .collect(),
+        state if state.dict_items().is_none() => vec![state],
+        _ => vec![],
+    };
+    match val {
+        Value::Build(target, state) => {
+            let mut parts = opaque_parts(target);
+            parts.extend(opaque_state(state));
+            parts
+        }
+        Value::PyObject(obj) => {
+            let PyObject {
+                args,
+                kwargs,
+                state,
+                slotstate,
+                list_items,
+                ..
+            } = obj.as_ref();
+            let mut parts = Vec::new();
+            if !list_items.is_empty() {
+                parts.extend(args);
+            }
+            parts.extend(kwargs.iter().flat_map(|(k, v)| [k, v]));
+            parts.extend(state.iter().chain(slotstate).flat_map(opaque_state));
+            parts
+        }
+        Value::Collection(coll) => vec![&coll.extra],
+        _ => vec![],
+    }
+}
@@ -0,0 +1,128 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use anyhow::Result;
+
+use crate::{
+    key::{same, KeyMode},
+    object::PyObject,
+    value::*,
+};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// Enum classes you know about, and the names of their members. Pickles
+/// only have the value of an enum member, so this is how you get the names
+/// back.
+///
+/// ```
+/// # use repugnant_pickle::{EnumTable, Value};
+/// let mut table = EnumTable::default();
+/// table.add_class(
+///     "myapp.config",
+///     "Color",
+///     [(Value::Int(1), "RED"), (Value::Int(2), "GREEN")],
+/// );
+/// ```
+pub struct EnumTable {
+    classes: HashMap<(String, String), Vec<(Value<'static>, String)>>,
+}
+
+impl EnumTable {
+    /// Add an enum class with the values and names of its members. Adding
+    /// a class that's already there adds to its members.
+    pub fn add_class<N: Into<String>>(
+        &mut self,
+        module: &str,
+        name: &str,
+        members: impl IntoIterator<Item = (Value<'static>, N)>,
+    ) -> &mut Self {
+        self.classes
+            .entry((module.to_string(), name.to_string()))
+            .or_default()
+            .extend(members.into_iter().map(|(v, n)| (v, n.into())));
+        self
+    }
+
+    /// Whether the class is in the table.
+    pub fn contains(&self, module: &str, name: &str) -> bool {
+        self.classes
+            .contains_key(&(module.to_string(), name.to_string()))
+    }
+
+    /// The name of the member of the class with this value, if we know it.
+    pub fn member_name(&self, module: &str, name: &str, value: &Value<'_>) -> Option<&str> {
+        self.classes
+            .get(&(module.to_string(), name.to_string()))?
+            .iter()
+            .rfind(|(v, _)| same(v, value, KeyMode::Strict))
+            .map(|(_, n)| n.as_str())
+    }
+
+    /// Whether the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+}
+
+/// Make a `Value::Enum` from a call to a class in `table` with one argument.
+/// With `guess` set, other classes count too as long as the argument is an
+/// int or a string and it isn't something we know, like a `decimal.Decimal`.
+pub(crate) fn reduce_enum<'a>(
+    val: Value<'a>,
+    table: &EnumTable,
+    guess: bool,
+    resolve: &mut impl FnMut(Value<'a>) -> Result<Value<'a>>,
+) -> Result<Value<'a>> {
+    let (module, name) = match &val {
+        Value::Global(class, args) => match (class.as_global(), args.as_slice()) {
+            (Some(global), [Value::Seq(SequenceType::Tuple, targs)]) if targs.len() == 1 => global,
+            _ => return Ok(val),
+        },
+        _ => return Ok(val),
+    };
+    let known = table.contains(module, name);
+    if !known && (!guess || matches!(module, "builtins" | "__builtin__")) {
+        return Ok(val);
+    }
+    let Value::Global(class, mut args) = val else {
+        unreachable!("Impossible: Not a call")
+    };
+    let value = match args.pop() {
+        Some(Value::Seq(_, mut targs)) => targs.pop().expect("Impossible: Missing value"),
+        _ => unreachable!("Impossible: Missing args"),
+    };
+    let value = match resolve(value)? {
+        value @ Value::Raw(_) => fix_value(value.clone()).unwrap_or(value),
+        value => value,
+    };
+    if !known {
+        let call =
+            |class, value| Value::Global(class, vec![Value::Seq(SequenceType::Tuple, vec![value])]);
+        // Things like `decimal.Decimal("1.5")` look like enum members too.
+        let scalar = matches!(value, Value::Int(_) | Value::BigInt(_) | Value::String(_));
+        if !scalar || call(class.clone(), value.clone()).as_std().is_some() {
+            return Ok(call(class, value));
+        }
+    }
+    let name = match class.as_global() {
+        Some((module, name)) => table.member_name(module, name, &value),
+        None => None,
+    };
+    Ok(Value::Enum {
+        name: name.map(|n| Cow::Owned(n.to_string())),
+        class,
+        value: Box::new(value),
+    })
+}
+
+/// Turn something that looked like an enum member back into the call it
+/// really was, because it got state from `BUILD`.
+pub(crate) fn unenum<'a>(class: Value<'a>, value: Value<'a>, objects: bool) -> Result<Value<'a>> {
+    let val = Value::Global(
+        Box::new(class),
+        vec![Value::Seq(SequenceType::Tuple, vec![value])],
+    );
+    Ok(match objects {
+        true => Value::PyObject(Box::new(PyObject::from_reduce(val, &mut Ok)?)),
+        false => val,
+    })
+}
@@ -1,8 +1,17 @@
-use crate::{ops::*, value::*};
+use crate::{
+    array::reduce_array,
+    builtins::reduce_builtin,
+    collection::{namedtuple, reduce_collection, unnamedtuple, Collection, CollectionKind},
+    compat::{decode_string, fix_import},
+    enums::{reduce_enum, unenum, EnumTable},
+    object::PyObject,
+    ops::*,
+    value::*,
+};
 
 use std::{
     borrow::Cow,
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     ops::{Deref, DerefMut},
 };
 
@@ -22,7 +31,7 @@ impl<'a> PickleStack<'a> {
 
     pub fn pop_mark(&mut self) -> Result<Vec<Value<'a>>> {
         let markidx = self.find_mark()?;
-        let postmark = self.0[markidx + 1..].to_owned();
+        let postmark = self.0.split_off(markidx + 1);
         self.truncate(markidx);
         Ok(postmark)
     }
@@ -70,15 +79,43 @@ impl<'a> PickleMemo<'a> {
             }
             op = val.to_owned();
             count += 1;
-            if count >= MAX_DEPTH {
-                // It be how it be.
-                break;
-            }
+            ensure!(count < MAX_DEPTH, "Reference chain too long (recursive?)");
         }
 
         Ok(op)
     }
 
+    /// Like `resolve` but you get a reference instead of a copy, and it's
+    /// always recursive.
+    pub fn resolve_ref<'m>(&'m self, mut op: &'m Value<'a>) -> Result<&'m Value<'a>> {
+        let mut count = 0;
+        while let Value::Ref(mid) = op {
+            op = self.0.get(mid).ok_or_else(|| anyhow!("Bad memo id"))?;
+            count += 1;
+            ensure!(count < MAX_DEPTH, "Reference chain too long (recursive?)");
+        }
+        Ok(op)
+    }
+
+    /// The memo ids in `mids` and everything they refer to.
+    fn reachable(&self, mids: impl IntoIterator<Item = u32>) -> BTreeSet<u32> {
+        let mut found = BTreeSet::new();
+        let mut todo = mids.into_iter().collect::<Vec<_>>();
+        while let Some(mid) = todo.pop() {
+            if !found.insert(mid) {
+                continue;
+            }
+            if let Some(val) = self.0.get(&mid) {
+                val.each_node(|val| {
+                    if let Value::Ref(mid) = val {
+                        todo.push(*mid);
+                    }
+                });
+            }
+        }
+        found
+    }
+
     pub fn insert(&mut self, mid: u32, val: Value<'a>) {
         self.0.insert(mid, val);
     }
@@ -106,10 +143,7 @@ impl<'a> PickleMemo<'a> {
                 break;
             }
             count += 1;
-            if count >= MAX_DEPTH {
-                // We did our best but it fell short.
-                break;
-            }
+            ensure!(count < MAX_DEPTH, "Reference chain too long (recursive?)");
         }
         // This unwrap is safe since it's impossible to get here without looking it up
         // non-mut style first.
@@ -122,53 +156,492 @@ impl<'a> PickleMemo<'a> {
     /// Try to resolve all references in an iterable of
     /// Value. If `fix_values` is true then it will
     /// try to fixup the values.
+    ///
+    /// `depth` is the nesting depth we're starting at, the default
+    /// `EvalLimits` apply.
     pub fn resolve_all_refs_iter(
         &self,
         depth: usize,
         vals: impl IntoIterator<Item = Value<'a>>,
         fix_values: bool,
     ) -> Result<Vec<Value<'a>>> {
-        if depth >= MAX_DEPTH {
-            // Sometimes things just don't work out the way you hoped.
-            return Ok(vals.into_iter().collect::<Vec<_>>());
-        }
+        let limits = EvalLimits::default();
+        let mut resolver = Resolver::new(Cow::Borrowed(self), fix_values, Budget::new(&limits));
         vals.into_iter()
-            .map(|val| self.resolve_all_refs(depth + 1, val, fix_values))
+            .map(|val| resolver.resolve(depth, val))
             .collect::<Result<Vec<_>>>()
     }
 
     /// Try to resolve all references.
     /// If `fix_values` is true, it will try to fixup
     /// the values.
+    ///
+    /// `depth` is the nesting depth we're starting at, the default
+    /// `EvalLimits` apply.
     pub fn resolve_all_refs(
         &self,
         depth: usize,
         val: Value<'a>,
         fix_values: bool,
     ) -> Result<Value<'a>> {
-        if depth >= MAX_DEPTH {
-            // It be how it be.
-            return Ok(val);
+        self.resolve_all_refs_with_limits(depth, val, fix_values, &EvalLimits::default())
+    }
+
+    /// Like `resolve_all_refs` but you can specify the limits.
+    pub fn resolve_all_refs_with_limits(
+        &self,
+        depth: usize,
+        val: Value<'a>,
+        fix_values: bool,
+        limits: &EvalLimits,
+    ) -> Result<Value<'a>> {
+        Resolver::new(Cow::Borrowed(self), fix_values, Budget::new(limits)).resolve(depth, val)
+    }
+}
+
+/// Keeps track of the state for resolving references. This recurses, but
+/// it grows the stack when it needs to, so deeply nested values won't
+/// overflow it. The only limits are the ones in `EvalLimits`.
+struct Resolver<'m, 'a> {
+    memo: Cow<'m, PickleMemo<'a>>,
+    /// If this is set, the memo entries that aren't in it only get used once,
+    /// so they're moved out of the memo instead of copied.
+    pinned: Option<BTreeSet<u32>>,
+    fix_values: bool,
+    budget: Budget<'m>,
+    /// Memo ids we're currently in the middle of expanding. If we run into
+    /// one of these again then the structure is recursive.
+    expanding: BTreeSet<u32>,
+}
+
+impl<'m, 'a> Resolver<'m, 'a> {
+    fn new(memo: Cow<'m, PickleMemo<'a>>, fix_values: bool, budget: Budget<'m>) -> Self {
+        Self {
+            memo,
+            pinned: None,
+            fix_values,
+            budget,
+            expanding: BTreeSet::default(),
         }
-        let rar = |v| self.resolve_all_refs(depth + 1, v, fix_values);
-        let rir = |i| self.resolve_all_refs_iter(depth + 1, i, fix_values);
-
-        let output = match val {
-            val @ Value::Ref(_) => rar(self.resolve(val, true)?)?,
-            Value::App(apped, apps) => Value::App(Box::new(rar(*apped)?), rir(apps)?),
-            Value::Object(apped, apps) => Value::Object(Box::new(rar(*apped)?), rir(apps)?),
-            Value::Build(apped, apps) => {
-                Value::Build(Box::new(rar(*apped)?), Box::new(rar(*apps)?))
-            }
-            Value::Global(target, args) => Value::Global(Box::new(rar(*target)?), rir(args)?),
-            Value::Seq(st, args) => Value::Seq(st, rir(args)?),
-            Value::PersId(pid) => Value::PersId(Box::new(rar(*pid)?)),
+    }
+
+    fn resolve_iter(&mut self, depth: usize, vals: Vec<Value<'a>>) -> Result<Vec<Value<'a>>> {
+        vals.into_iter()
+            .map(|val| self.resolve(depth, val))
+            .collect::<Result<Vec<_>>>()
+    }
+
+    fn resolve_pairs(
+        &mut self,
+        depth: usize,
+        items: Vec<(Value<'a>, Value<'a>)>,
+    ) -> Result<Vec<(Value<'a>, Value<'a>)>> {
+        items
+            .into_iter()
+            .map(|(k, v)| Ok((self.resolve(depth, k)?, self.resolve(depth, v)?)))
+            .collect()
+    }
+
+    fn resolve_box(&mut self, depth: usize, val: Value<'a>) -> Result<Box<Value<'a>>> {
+        Ok(Box::new(self.resolve(depth, val)?))
+    }
+
+    fn resolve(&mut self, depth: usize, val: Value<'a>) -> Result<Value<'a>> {
+        deeper(|| self.resolve_one(depth, val))
+    }
+
+    fn resolve_one(&mut self, depth: usize, val: Value<'a>) -> Result<Value<'a>> {
+        let limits = self.budget.limits;
+        if depth > limits.max_nesting_depth {
+            return Err(LimitExceeded::NestingDepth(limits.max_nesting_depth).into());
+        }
+        self.budget.charge(1)?;
+
+        let next = depth + 1;
+        Ok(match val {
+            Value::Ref(mid) if self.expanding.contains(&mid) => {
+                // Recursive structure, the best we can do is leave the reference.
+                Value::Ref(mid)
+            }
+            Value::Ref(mid) if self.pinned.as_ref().is_some_and(|p| !p.contains(&mid)) => {
+                // Nothing else refers to this, so it can't be recursive either.
+                let val = self.memo.to_mut().0.remove(&mid);
+                self.resolve(depth, val.ok_or_else(|| anyhow!("Bad memo id"))?)?
+            }
+            val @ Value::Ref(mid) => {
+                self.expanding.insert(mid);
+                let val = self.memo.resolve(val, true)?;
+                let val = self.resolve(depth, val);
+                self.expanding.remove(&mid);
+                val?
+            }
+            Value::App(target, args) => Value::App(
+                self.resolve_box(next, *target)?,
+                self.resolve_iter(next, args)?,
+            ),
+            Value::Object(target, args) => Value::Object(
+                self.resolve_box(next, *target)?,
+                self.resolve_iter(next, args)?,
+            ),
+            Value::Global(target, args) => Value::Global(
+                self.resolve_box(next, *target)?,
+                self.resolve_iter(next, args)?,
+            ),
+            Value::Build(target, state) => Value::Build(
+                self.resolve_box(next, *target)?,
+                self.resolve_box(next, *state)?,
+            ),
+            Value::Seq(st, items) => Value::Seq(st, self.resolve_iter(next, items)?),
+            Value::Dict(items) => Value::Dict(self.resolve_pairs(next, items)?),
+            Value::PersId(pid) => Value::PersId(self.resolve_box(next, *pid)?),
+            Value::PyObject(obj) => {
+                let PyObject {
+                    class,
+                    args,
+                    kwargs,
+                    state,
+                    slotstate,
+                    list_items,
+                    dict_items,
+                } = *obj;
+                let class = self.resolve(next, class)?;
+                let args = self.resolve_iter(next, args)?;
+                let kwargs = self.resolve_pairs(next, kwargs)?;
+                let mut opt =
+                    |val: Option<Value<'a>>| val.map(|v| self.resolve(next, v)).transpose();
+                let (state, slotstate) = (opt(state)?, opt(slotstate)?);
+                Value::PyObject(Box::new(PyObject {
+                    class,
+                    args,
+                    kwargs,
+                    state,
+                    slotstate,
+                    list_items: self.resolve_iter(next, list_items)?,
+                    dict_items: self.resolve_pairs(next, dict_items)?,
+                }))
+            }
+            Value::Collection(coll) => {
+                let Collection {
+                    kind,
+                    class,
+                    value,
+                    extra,
+                } = *coll;
+                Value::Collection(Box::new(Collection {
+                    kind,
+                    class: self.resolve(next, class)?,
+                    value: self.resolve(next, value)?,
+                    extra: self.resolve(next, extra)?,
+                }))
+            }
+            Value::Enum { class, value, name } => Value::Enum {
+                class: self.resolve_box(next, *class)?,
+                value: self.resolve_box(next, *value)?,
+                name,
+            },
+            val if self.fix_values => fix_value(val)?,
             val => val,
-        };
-        if fix_values {
-            fix_value(output)
-        } else {
-            Ok(output)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Limits on how much work evaluating a pickle is allowed to do. A malicious
+/// (or just very weird) pickle can use references to make a value that's
+/// exponentially large once it's resolved, so you probably don't want to
+/// turn these off for pickles you don't trust.
+pub struct EvalLimits {
+    /// Maximum number of items on the stack.
+    pub max_stack_depth: usize,
+
+    /// Maximum number of entries in the memo.
+    pub max_memo_entries: usize,
+
+    /// Maximum number of values produced, counting both evaluating
+    /// and resolving references.
+    pub max_nodes: usize,
+
+    /// Maximum nesting depth for values when resolving references.
+    /// Resolving, printing, keys, diffs, visitors and serializing work at
+    /// any depth, but cloning, `==` and `Debug` on a `Value` are recursive.
+    /// Lower this if you need those on deep values with a small stack.
+    /// Values that didn't get resolved aren't checked.
+    pub max_nesting_depth: usize,
+}
+
+impl Default for EvalLimits {
+    fn default() -> Self {
+        Self {
+            max_stack_depth: 1 << 20,
+            max_memo_entries: 1 << 24,
+            max_nodes: 1 << 25,
+            max_nesting_depth: 10_000,
+        }
+    }
+}
+
+impl EvalLimits {
+    /// No limits at all. Only use this for pickles you trust. See the note
+    /// for `max_nesting_depth`.
+    pub fn unlimited() -> Self {
+        Self {
+            max_stack_depth: usize::MAX,
+            max_memo_entries: usize::MAX,
+            max_nodes: usize::MAX,
+            max_nesting_depth: usize::MAX,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The error you get when evaluating runs into one of the `EvalLimits`.
+/// It's wrapped in an `anyhow::Error` so you'll need to use `downcast_ref`
+/// if you want to check for it specifically.
+pub enum LimitExceeded {
+    StackDepth(usize),
+    MemoEntries(usize),
+    Nodes(usize),
+    NestingDepth(usize),
+}
+
+impl std::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::StackDepth(n) => write!(f, "Stack depth limit ({n}) exceeded"),
+            Self::MemoEntries(n) => write!(f, "Memo entry limit ({n}) exceeded"),
+            Self::Nodes(n) => write!(f, "Value count limit ({n}) exceeded"),
+            Self::NestingDepth(n) => write!(f, "Nesting depth limit ({n}) exceeded"),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// Tracks how many values we've produced against the limits.
+struct Budget<'l> {
+    limits: &'l EvalLimits,
+    nodes: usize,
+}
+
+impl<'l> Budget<'l> {
+    fn new(limits: &'l EvalLimits) -> Self {
+        Self { limits, nodes: 0 }
+    }
+
+    fn charge(&mut self, nodes: usize) -> Result<()> {
+        self.nodes = self.nodes.saturating_add(nodes);
+        if self.nodes > self.limits.max_nodes {
+            return Err(LimitExceeded::Nodes(self.limits.max_nodes).into());
+        }
+        Ok(())
+    }
+
+    /// Resolve a reference, charging for the copy we end up making.
+    fn resolve<'a>(&mut self, memo: &PickleMemo<'a>, val: Value<'a>) -> Result<Value<'a>> {
+        if !matches!(val, Value::Ref(_)) {
+            return Ok(val);
+        }
+        let val = memo.resolve(val, true)?;
+        self.charge(val.node_count())?;
+        Ok(val)
+    }
+}
+
+/// `collections.OrderedDict` usually gets reduced without arguments and then has
+/// its items set, but it can also be reduced with a list of `[key, value]` pairs.
+/// Either way, we want to end up with the items in a `Value::Dict` at the end of
+/// the arguments.
+fn reduce_ordereddict<'a>(
+    memo: &PickleMemo<'a>,
+    budget: &mut Budget<'_>,
+    target: Value<'a>,
+    args: Value<'a>,
+) -> Result<Value<'a>> {
+    if !target.is_class("collections", "OrderedDict") {
+        return Ok(Value::Global(Box::new(target), vec![args]));
+    }
+    let items = match &args {
+        Value::Seq(SequenceType::Tuple, targs) => match targs.as_slice() {
+            [items] => budget.resolve(memo, items.clone())?,
+            _ => return Ok(Value::Global(Box::new(target), vec![args])),
+        },
+        _ => return Ok(Value::Global(Box::new(target), vec![args])),
+    };
+    let items = match items {
+        Value::Seq(SequenceType::List | SequenceType::Tuple, items) => items
+            .into_iter()
+            .map(|item| match budget.resolve(memo, item)? {
+                Value::Seq(SequenceType::List | SequenceType::Tuple, kv) if kv.len() == 2 => {
+                    let mut kv = kv.into_iter();
+                    Ok((kv.next().unwrap(), kv.next().unwrap()))
+                }
+                _ => bail!("Bad item when reducing collections.OrderedDict"),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        _ => return Ok(Value::Global(Box::new(target), vec![args])),
+    };
+    Ok(Value::Global(
+        Box::new(target),
+        vec![Value::Seq(SequenceType::Tuple, vec![]), Value::Dict(items)],
+    ))
+}
+
+/// Turn the result of `REDUCE` into something more specific if it's a call
+/// to a class we know about and the options say to: a builtin like `set`,
+/// one of the `collections` types, an `array.array` or an enum member. If
+/// it's something else or the arguments aren't what we expected, you just get
+/// it back. `resolve` gets used to look up memo references.
+fn reduce_known<'a>(
+    val: Value<'a>,
+    options: &EvalOptions,
+    resolve: &mut impl FnMut(Value<'a>) -> Result<Value<'a>>,
+) -> Result<Value<'a>> {
+    let Value::Global(class, _) = &val else {
+        return Ok(val);
+    };
+    let guess_namedtuples =
+        options.collections && options.guess_namedtuples && !options.object_model;
+    match class.as_global() {
+        Some(("_codecs", "encode") | ("builtins" | "__builtin__", _)) if options.builtins => {
+            reduce_builtin(val, resolve)
+        }
+        Some(("collections", "OrderedDict" | "defaultdict" | "Counter" | "deque"))
+            if options.collections =>
+        {
+            reduce_collection(val, resolve)
+        }
+        Some(("copyreg" | "copy_reg", "_reconstructor")) if guess_namedtuples => {
+            reduce_collection(val, resolve)
+        }
+        Some(("array", "array" | "_array_reconstructor")) if options.arrays => {
+            reduce_array(val, options.string_encoding, resolve)
+        }
+        Some(_) if options.guess_enums || !options.enum_table.is_empty() => {
+            reduce_enum(val, &options.enum_table, options.guess_enums, resolve)
+        }
+        _ => Ok(val),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// How to decode Python 2 `str` values, which are really just bytes.
+/// These come from `STRING`, `BINSTRING` and `SHORT_BINSTRING`. This is
+/// like the `encoding` argument to Python's `pickle.load` except that
+/// the default is to guess.
+pub enum StringEncoding {
+    /// Use a `Value::String` if it's valid UTF8, otherwise use
+    /// `Value::Bytes`.
+    #[default]
+    Utf8OrBytes,
+
+    /// Always use `Value::Bytes`, like `encoding="bytes"`.
+    Bytes,
+
+    /// Decode as ASCII like `pickle.load` does by default. Anything else
+    /// is an error.
+    Ascii,
+
+    /// Decode as UTF8. Anything that isn't valid UTF8 is an error.
+    Utf8,
+
+    /// Decode as Latin-1, like `encoding="latin1"`. This always works,
+    /// and it's what you want for things like NumPy arrays from Python 2.
+    Latin1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// How picky evaluation should be.
+pub enum EvalMode {
+    /// Anything we don't know what to do with just gets pushed
+    /// onto the stack as a `Value::Raw` and we carry on.
+    #[default]
+    Lenient,
+
+    /// Fail on any op we can't give real semantics to. Also fail if
+    /// there are leftover `MARK`s or more than one item on the stack
+    /// when we hit `STOP` (or if there's no `STOP` at all).
+    /// Mainly useful for validating pickles.
+    Strict,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Options for `evaluate_with_options`.
+pub struct EvalOptions {
+    /// Resolve references and fix up values after evaluating.
+    /// See `evaluate`.
+    pub resolve_refs: bool,
+
+    /// How picky to be. Lenient by default.
+    pub mode: EvalMode,
+
+    /// Limits on how much work evaluating is allowed to do.
+    pub limits: EvalLimits,
+
+    /// Put objects together the way Python would, producing `Value::PyObject`
+    /// instead of `Value::Global`, `Value::Object` and `Value::Build`.
+    /// See `PyObject`.
+    pub object_model: bool,
+
+    /// Evaluate calls to builtins that don't have side effects, so you get
+    /// `Value::Bytes` instead of `_codecs.encode(...)`, `Value::Seq` instead
+    /// of `set([...])` and so on. This also handles `bytearray`, `frozenset`,
+    /// `complex`, `slice` and `range`. On by default.
+    pub builtins: bool,
+
+    /// Recognize `OrderedDict`, `defaultdict`, `Counter` and `deque` from
+    /// the `collections` module and give them to you as a
+    /// `Value::Collection`. On by default.
+    pub collections: bool,
+
+    /// Guess which objects are namedtuples and make them a
+    /// `Value::Collection` too. Pickles don't say which classes are
+    /// namedtuples, so this treats anything made with `NEWOBJ` with
+    /// arguments (or as a subclass of `tuple` with `copyreg._reconstructor`)
+    /// that doesn't get any state afterwards as one. Subclasses of `str` or
+    /// `int` look like that too, so this is off by default. It needs
+    /// `collections`, and doesn't do anything with `object_model`.
+    pub guess_namedtuples: bool,
+
+    /// Decode `array.array` into a `Value::Array` with the items as a typed
+    /// vector. On by default.
+    pub arrays: bool,
+
+    /// Enum classes you know about, and the names of their members. Calls
+    /// to these classes with one argument become `Value::Enum`.
+    pub enum_table: EnumTable,
+
+    /// Guess which calls are enum members and make them `Value::Enum` too.
+    /// Enum members get pickled as a call to the class with the value, so
+    /// this looks for calls with one int or string argument that don't get
+    /// any state afterwards. Lots of other things look like that too, so
+    /// this is off by default.
+    pub guess_enums: bool,
+
+    /// How to decode Python 2 strings. See `StringEncoding`.
+    pub string_encoding: StringEncoding,
+
+    /// Map Python 2 module and class names to the Python 3 ones like
+    /// `pickle.load` does, so `__builtin__.set` becomes `builtins.set`,
+    /// `copy_reg` becomes `copyreg` and so on. Like Python, this only
+    /// applies to protocols older than 3. On by default.
+    pub fix_imports: bool,
+}
+
+impl Default for EvalOptions {
+    fn default() -> Self {
+        Self {
+            resolve_refs: true,
+            mode: EvalMode::default(),
+            limits: EvalLimits::default(),
+            object_model: false,
+            builtins: true,
+            collections: true,
+            guess_namedtuples: false,
+            arrays: true,
+            enum_table: EnumTable::default(),
+            guess_enums: false,
+            string_encoding: StringEncoding::default(),
+            fix_imports: true,
         }
     }
 }
@@ -177,31 +650,346 @@ impl<'a> PickleMemo<'a> {
 /// Values. You'll also get the memo map back in case you
 /// need a way to look up references this crate couldn't handle.
 /// You can also pass `resolve_refs` as false and handle
-/// the references yourself.
+/// the references yourself. When references get resolved, memo
+/// entries that are only used once are moved into the values, so
+/// the memo just has the ones that are shared.
 pub fn evaluate<'a>(
     x: &'a [PickleOp],
     resolve_refs: bool,
 ) -> Result<(Vec<Value<'a>>, PickleMemo<'a>)> {
+    evaluate_with_options(
+        x,
+        &EvalOptions {
+            resolve_refs,
+            ..Default::default()
+        },
+    )
+}
+
+/// Like `evaluate` but you can specify the options.
+pub fn evaluate_with_options<'a>(
+    x: &'a [PickleOp],
+    options: &EvalOptions,
+) -> Result<(Vec<Value<'a>>, PickleMemo<'a>)> {
+    evaluate_inner(borrowed_ops(x), options, None)
+}
+
+/// Parse and evaluate pickle data in a single pass, without building
+/// a Vec of ops first. This uses less memory than `parse_ops` followed
+/// by `evaluate` and is a bit faster, and the result is the same.
+/// Evaluation stops at `STOP` so anything after that is ignored. In
+/// strict mode, hitting something that can't be parsed before that is
+/// an error.
+pub fn evaluate_bytes(x: &[u8], resolve_refs: bool) -> Result<(Vec<Value<'_>>, PickleMemo<'_>)> {
+    evaluate_bytes_with_options(
+        x,
+        &EvalOptions {
+            resolve_refs,
+            ..Default::default()
+        },
+    )
+}
+
+/// Like `evaluate_bytes` but you can specify the options.
+pub fn evaluate_bytes_with_options<'a>(
+    x: &'a [u8],
+    options: &EvalOptions,
+) -> Result<(Vec<Value<'a>>, PickleMemo<'a>)> {
+    let strict = options.mode == EvalMode::Strict;
+    evaluate_inner(parsed_ops(x, strict), options, None)
+}
+
+type OpResult<'a> = Result<Cow<'a, PickleOp<'a>>>;
+
+fn borrowed_ops<'a>(x: &'a [PickleOp<'a>]) -> impl Iterator<Item = OpResult<'a>> {
+    x.iter().map(|op| Ok(Cow::Borrowed(op)))
+}
+
+/// Parse ops as they're needed. Like `parse_ops`, parsing just stops at
+/// the first op that can't be parsed unless `strict` is set.
+fn parsed_ops(data: &[u8], strict: bool) -> impl Iterator<Item = OpResult<'_>> {
+    let mut x = data;
+    std::iter::from_fn(move || {
+        if x.is_empty() {
+            return None;
+        }
+        let result = crate::parsers::parse_op::<nom::error::Error<&[u8]>>(x);
+        let offset = data.len() - x.len();
+        // Don't keep trying after an error.
+        x = &[];
+        let err = match result {
+            Result::Ok((rest, op)) => {
+                x = rest;
+                return Some(Ok(Cow::Owned(op)));
+            }
+            _ if !strict => return None,
+            Err(nom::Err::Error(e) | nom::Err::Failure(e)) => format!("{:?}", e.code),
+            Err(nom::Err::Incomplete(_)) => "Incomplete op".to_string(),
+        };
+        Some(Err(anyhow!("Parse error at offset {offset}: {err}")))
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Something that got added to the outermost container when
+/// using `evaluate_streaming`.
+pub enum StreamItem<'a> {
+    /// An item from `APPEND`, `APPENDS` or `ADDITEMS`.
+    Item(Value<'a>),
+
+    /// A key and value from `SETITEM` or `SETITEMS`.
+    Entry(Value<'a>, Value<'a>),
+}
+
+/// Evaluate a pickle that's one huge list or dict without having to keep the
+/// whole thing in memory. The pickle gets parsed as it's evaluated, like with
+/// `evaluate_bytes`. Items that get added to the outermost container (the
+/// thing at the bottom of the stack) get passed to `emit` as soon as they're
+/// added instead of being stored, so when evaluation finishes you'll get that
+/// container back empty. This works for lists, dicts, sets and things like
+/// `collections.OrderedDict`. Anything else at the top level is an error.
+///
+/// If `options.resolve_refs` is set, references in the items are resolved
+/// before they're emitted. Memo entries for containers and objects are
+/// thrown away once the item they were part of has been emitted, otherwise
+/// the memo would end up holding pretty much everything anyway. Entries that
+/// a later `GET` still needs (and whatever they refer to) are kept until
+/// that has happened, which means the ops get parsed once up front to count
+/// them. Simple values like strings are always kept. Set `keep_memo` to keep
+/// everything and skip the counting.
+///
+/// The node limit in `options.limits` applies to each batch of items rather
+/// than the whole pickle.
+pub fn evaluate_streaming<'a>(
+    x: &'a [u8],
+    options: &EvalOptions,
+    keep_memo: bool,
+    mut emit: impl FnMut(StreamItem<'a>) -> Result<()>,
+) -> Result<(Vec<Value<'a>>, PickleMemo<'a>)> {
+    let strict = options.mode == EvalMode::Strict;
+    let forget_memo = !keep_memo && options.resolve_refs;
+    let mut gets = BTreeMap::new();
+    if forget_memo {
+        // Parse errors get reported when evaluating.
+        for op in parsed_ops(x, false).map_while(|op| op.ok()) {
+            let mid = match op.as_ref() {
+                PickleOp::GET(mids) => mids.parse().ok(),
+                PickleOp::BINGET(mid) => Some(*mid as u32),
+                PickleOp::LONG_BINGET(mid) => Some(*mid),
+                _ => None,
+            };
+            if let Some(mid) = mid {
+                *gets.entry(mid).or_insert(0) += 1;
+            }
+        }
+    }
+    let mut streamer = Streamer {
+        emit: &mut emit,
+        forget_memo,
+        gets,
+        recent: vec![],
+        waiting: vec![],
+        forgotten: 0,
+        emitted: false,
+    };
+    evaluate_inner(parsed_ops(x, strict), options, Some(&mut streamer))
+}
+
+/// State for streaming evaluation.
+struct Streamer<'s, 'a> {
+    emit: &'s mut dyn FnMut(StreamItem<'a>) -> Result<()>,
+    forget_memo: bool,
+    /// How many `GET`s of each memo id are still to come.
+    gets: BTreeMap<u32, usize>,
+    /// Memo ids that were added since we last emitted something.
+    recent: Vec<u32>,
+    /// Memo ids that couldn't be thrown away yet because something still
+    /// needs them.
+    waiting: Vec<u32>,
+    /// How many memo entries we threw away. `MEMOIZE` uses the memo
+    /// size for the id, so we need to account for these.
+    forgotten: usize,
+    /// Whether anything got emitted.
+    emitted: bool,
+}
+
+impl<'s, 'a> Streamer<'s, 'a> {
+    fn inserted(&mut self, mid: u32) {
+        if self.forget_memo {
+            self.recent.push(mid);
+        }
+    }
+
+    fn fetched(&mut self, mid: u32) {
+        if let Some(count) = self.gets.get_mut(&mid) {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    fn emit(
+        &mut self,
+        memo: &mut PickleMemo<'a>,
+        budget: &mut Budget<'_>,
+        options: &EvalOptions,
+        container: &Value<'a>,
+        items: impl IntoIterator<Item = StreamItem<'a>>,
+    ) -> Result<()> {
+        let target = memo.resolve_ref(container)?;
+        ensure!(
+            matches!(
+                target,
+                Value::Seq(SequenceType::List | SequenceType::Set, _)
+                    | Value::Dict(_)
+                    | Value::Global(..)
+                    | Value::Object(..)
+                    | Value::PyObject(_)
+                    | Value::Collection(_)
+            ),
+            "Can't stream items for {}",
+            target.type_name()
+        );
+        self.emitted = true;
+        for item in items {
+            let item = if options.resolve_refs {
+                let memo = Cow::Borrowed(&*memo);
+                let mut resolver = Resolver::new(memo, true, Budget::new(budget.limits));
+                match item {
+                    StreamItem::Item(v) => StreamItem::Item(resolver.resolve(0, v)?),
+                    StreamItem::Entry(k, v) => {
+                        StreamItem::Entry(resolver.resolve(0, k)?, resolver.resolve(0, v)?)
+                    }
+                }
+            } else {
+                item
+            };
+            (self.emit)(item)?;
+        }
+        // Whatever we emitted doesn't take up space anymore.
+        budget.nodes = 0;
+
+        // Keep the container, anything that gets fetched again and whatever
+        // those refer to.
+        let mut candidates = std::mem::take(&mut self.waiting);
+        candidates.append(&mut self.recent);
+        let fetched = candidates
+            .iter()
+            .copied()
+            .filter(|mid| self.gets.get(mid).is_some_and(|&count| count > 0));
+        let needed = match container {
+            Value::Ref(mid) => memo.reachable(fetched.chain([*mid])),
+            _ => memo.reachable(fetched),
+        };
+        for mid in candidates {
+            let compound = matches!(
+                memo.0.get(&mid),
+                Some(
+                    Value::App(..)
+                        | Value::Object(..)
+                        | Value::Build(..)
+                        | Value::PersId(..)
+                        | Value::Global(..)
+                        | Value::Seq(..)
+                        | Value::Dict(..)
+                        | Value::PyObject(..)
+                        | Value::Collection(..)
+                        | Value::Enum { .. }
+                )
+            );
+            if !compound {
+                continue;
+            }
+            if needed.contains(&mid) {
+                self.waiting.push(mid);
+            } else {
+                memo.0.remove(&mid);
+                self.forgotten += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn evaluate_inner<'a>(
+    ops: impl IntoIterator<Item = OpResult<'a>>,
+    options: &EvalOptions,
+    mut stream: Option<&mut Streamer<'_, 'a>>,
+) -> Result<(Vec<Value<'a>>, PickleMemo<'a>)> {
+    let strict = options.mode == EvalMode::Strict;
+    let objects = options.object_model;
+    let limits = &options.limits;
+    let mut budget = Budget::new(limits);
+    let mut stopped = false;
+    // Protocols 0 and 1 don't have PROTO.
+    let mut proto = 0;
     let mut stack = PickleStack::default();
     let mut memo = PickleMemo::default();
+    // Memo ids that got fetched with GET or copied with DUP.
+    let mut shared = BTreeSet::new();
 
-    fn make_kvlist(items: Vec<Value<'_>>) -> Result<Vec<Value<'_>>> {
+    // Python 3 only fixes imports for protocols older than 3.
+    let class = |module, name, proto| {
+        let (module, name) = if options.fix_imports && proto < 3 {
+            fix_import(module, name)
+        } else {
+            (module, name)
+        };
+        Value::class(module, name)
+    };
+
+    fn make_kvlist(items: Vec<Value<'_>>) -> Result<Vec<(Value<'_>, Value<'_>)>> {
         ensure!(items.len() & 1 == 0, "Bad value for setitems");
-        let mut kvitems = Vec::with_capacity(items.len());
+        let mut kvitems = Vec::with_capacity(items.len() / 2);
         let mut it = items.into_iter();
         while let Some(k) = it.next() {
             let v = it.next().expect("Impossible: Missing value item");
-            kvitems.push(Value::Seq(SequenceType::Tuple, vec![k, v]));
+            kvitems.push((k, v));
         }
         Ok(kvitems)
     }
 
-    for op in x.iter() {
+    // Get the dict items for the target of SETITEM(S). Calls that aren't
+    // dicts themselves get their items collected in a Dict right after the
+    // argument tuple, so a dict that was passed as an argument to something
+    // like INST doesn't get mistaken for it. INST and OBJ take their
+    // arguments directly, so those get put in a tuple first.
+    fn dict_target<'a, 'b>(val: &'b mut Value<'a>) -> Option<&'b mut Vec<(Value<'a>, Value<'a>)>> {
+        let args = match val {
+            Value::Dict(items) => return Some(items),
+            Value::PyObject(obj) => return Some(&mut obj.dict_items),
+            Value::Collection(coll) => match &mut coll.value {
+                Value::Dict(items) => return Some(items),
+                _ => return None,
+            },
+            Value::Global(_, args) | Value::Object(_, args) => args,
+            _ => return None,
+        };
+        match args.as_slice() {
+            [Value::Seq(SequenceType::Tuple, _)] => args.push(Value::Dict(vec![])),
+            [Value::Seq(SequenceType::Tuple, _), Value::Dict(_)] => (),
+            _ => {
+                *args = vec![
+                    Value::Seq(SequenceType::Tuple, std::mem::take(args)),
+                    Value::Dict(vec![]),
+                ]
+            }
+        }
+        match args.as_mut_slice() {
+            [Value::Seq(SequenceType::Tuple, _), Value::Dict(items)] => Some(items),
+            _ => None,
+        }
+    }
+
+    for op in ops {
+        let op = op?;
         let stack = &mut stack;
+        budget.charge(1)?;
 
-        match op {
-            PickleOp::MARK => stack.push(Value::Raw(Cow::Borrowed(op))),
-            PickleOp::STOP => break,
+        match op.as_ref() {
+            PickleOp::MARK => stack.push(Value::Raw(Cow::Borrowed(&PickleOp::MARK))),
+            PickleOp::STOP => {
+                stopped = true;
+                break;
+            }
             PickleOp::POP => {
                 let _ = stack.pop()?;
             }
@@ -213,36 +1001,107 @@ pub fn evaluate<'a>(
                     .last()
                     .ok_or_else(|| anyhow!("Cannot DUP with empty stack"))?
                     .to_owned();
+                budget.charge(item.node_count())?;
+                item.each_node(|val| {
+                    if let Value::Ref(mid) = val {
+                        shared.insert(*mid);
+                    }
+                });
                 stack.push(item);
             }
-            PickleOp::PERSID(pid) => stack.push(Value::PersId(Box::new(Value::String(pid)))),
+            PickleOp::PERSID(pid) => {
+                stack.push(Value::PersId(Box::new(Value::String(Cow::Borrowed(pid)))))
+            }
             PickleOp::BINPERSID => {
                 let pid = stack.pop()?;
                 stack.push(Value::PersId(Box::new(pid)));
             }
             PickleOp::REDUCE => {
-                let args = memo.resolve(stack.pop()?, true)?;
-                let target = memo.resolve(stack.pop()?, true)?;
-                stack.push(Value::Global(Box::new(target), vec![args]));
+                let args = budget.resolve(&memo, stack.pop()?)?;
+                let target = budget.resolve(&memo, stack.pop()?)?;
+                let val = reduce_ordereddict(&memo, &mut budget, target, args)?;
+                let val = reduce_known(val, options, &mut |v| budget.resolve(&memo, v))?;
+                stack.push(if objects && matches!(val, Value::Global(..)) {
+                    let obj = PyObject::from_reduce(val, &mut |v| budget.resolve(&memo, v))?;
+                    Value::PyObject(Box::new(obj))
+                } else {
+                    val
+                });
             }
             PickleOp::BUILD => {
-                let args = Box::new(memo.resolve(stack.pop()?, true)?);
-                let target = Box::new(memo.resolve(stack.pop()?, true)?);
-                stack.push(Value::Build(target, args));
-            }
-            PickleOp::EMPTY_DICT => stack.push(Value::Seq(SequenceType::Dict, Default::default())),
-            PickleOp::GET(mids) => stack.push(Value::Ref(mids.parse()?)),
-            PickleOp::BINGET(mid) => stack.push(Value::Ref(*mid as u32)),
-            PickleOp::LONG_BINGET(mid) => stack.push(Value::Ref(*mid)),
+                let args = budget.resolve(&memo, stack.pop()?)?;
+                // Namedtuples and enum members don't get state, so this wasn't
+                // one after all.
+                let top = stack
+                    .last_mut()
+                    .ok_or_else(|| anyhow!("Unexpected empty stack"))?;
+                let rtop = memo.resolve_mut(top, true)?;
+                let stateless = matches!(rtop, Value::Enum { .. })
+                    || matches!(rtop, Value::Collection(coll)
+                        if coll.kind == CollectionKind::NamedTuple);
+                if stateless {
+                    *rtop = match std::mem::replace(rtop, Value::None) {
+                        Value::Collection(coll) => unnamedtuple(*coll, objects),
+                        Value::Enum { class, value, .. } => unenum(*class, *value, objects)?,
+                        val => val,
+                    };
+                }
+                let top = stack
+                    .last()
+                    .ok_or_else(|| anyhow!("Unexpected empty stack"))?;
+                if objects && matches!(memo.resolve_ref(top)?, Value::PyObject(_)) {
+                    // Objects get built in place, so anything else referencing them
+                    // sees the state as well.
+                    let (state, slotstate) =
+                        PyObject::split_state(args, &mut |v| budget.resolve(&memo, v))?;
+                    let top = stack.last_mut().expect("Impossible: Empty stack");
+                    if let Value::PyObject(target) = memo.resolve_mut(top, true)? {
+                        target.apply_state(state, slotstate);
+                    }
+                } else {
+                    let target = budget.resolve(&memo, stack.pop()?)?;
+                    stack.push(Value::Build(Box::new(target), Box::new(args)));
+                }
+            }
+            PickleOp::EMPTY_DICT => stack.push(Value::Dict(Default::default())),
+            PickleOp::GET(mids) => {
+                let mid = mids.parse()?;
+                shared.insert(mid);
+                stack.push(Value::Ref(mid));
+                if let Some(st) = stream.as_mut() {
+                    st.fetched(mid);
+                }
+            }
+            PickleOp::BINGET(mid) => {
+                let mid = *mid as u32;
+                shared.insert(mid);
+                stack.push(Value::Ref(mid));
+                if let Some(st) = stream.as_mut() {
+                    st.fetched(mid);
+                }
+            }
+            PickleOp::LONG_BINGET(mid) => {
+                shared.insert(*mid);
+                stack.push(Value::Ref(*mid));
+                if let Some(st) = stream.as_mut() {
+                    st.fetched(*mid);
+                }
+            }
             PickleOp::EMPTY_LIST => stack.push(Value::Seq(SequenceType::List, Default::default())),
             PickleOp::BINPUT(mid) => {
                 let mid = *mid as u32;
                 memo.insert(mid, stack.pop()?);
                 stack.push(Value::Ref(mid));
+                if let Some(st) = stream.as_mut() {
+                    st.inserted(mid);
+                }
             }
             PickleOp::LONG_BINPUT(mid) => {
                 memo.insert(*mid, stack.pop()?);
                 stack.push(Value::Ref(*mid));
+                if let Some(st) = stream.as_mut() {
+                    st.inserted(*mid);
+                }
             }
             PickleOp::TUPLE => {
                 let postmark = stack.pop_mark()?;
@@ -254,32 +1113,37 @@ pub fn evaluate<'a>(
             PickleOp::SETITEM => {
                 let v = stack.pop()?;
                 let k = stack.pop()?;
+                if let (Some(st), [container]) = (stream.as_mut(), stack.as_slice()) {
+                    let items = [StreamItem::Entry(k, v)];
+                    st.emit(&mut memo, &mut budget, options, container, items)?;
+                    continue;
+                }
                 let top = stack
                     .last_mut()
                     .ok_or_else(|| anyhow!("Unexpected empty stack"))?;
                 let rtop = memo.resolve_mut(top, true)?;
-                match rtop {
-                    Value::Global(_, args) | Value::Seq(_, args) => {
-                        args.push(Value::Seq(SequenceType::Tuple, vec![k, v]));
-                    }
-                    _wut => bail!("Bad stack top for SETITEM!"),
-                }
+                dict_target(rtop)
+                    .ok_or_else(|| anyhow!("Bad stack top for SETITEM!"))?
+                    .push((k, v));
             }
             PickleOp::SETITEMS => {
                 let kvitems = make_kvlist(stack.pop_mark()?)?;
+                if let (Some(st), [container]) = (stream.as_mut(), stack.as_slice()) {
+                    let items = kvitems.into_iter().map(|(k, v)| StreamItem::Entry(k, v));
+                    st.emit(&mut memo, &mut budget, options, container, items)?;
+                    continue;
+                }
                 let top = stack
                     .last_mut()
                     .ok_or_else(|| anyhow!("Unexpected empty stack"))?;
                 let rtop = memo.resolve_mut(top, true)?;
-                match rtop {
-                    Value::Global(_, args) | Value::Seq(_, args) => {
-                        args.push(Value::Seq(SequenceType::Tuple, kvitems));
-                    }
-                    _wut => bail!("Bad stack top for SETITEMS"),
-                }
+                dict_target(rtop)
+                    .ok_or_else(|| anyhow!("Bad stack top for SETITEMS"))?
+                    .extend(kvitems);
             }
-            PickleOp::PROTO(proto) => {
-                ensure!(*proto <= MAX_PROTOCOL, "Unsupported protocol {proto}")
+            PickleOp::PROTO(p) => {
+                ensure!(*p <= MAX_PROTOCOL, "Unsupported protocol {p}");
+                proto = *p;
             }
             PickleOp::TUPLE1 => {
                 let t1 = stack.pop()?;
@@ -295,6 +1159,11 @@ pub fn evaluate<'a>(
             }
             PickleOp::APPEND => {
                 let v = stack.pop()?;
+                if let (Some(st), [container]) = (stream.as_mut(), stack.as_slice()) {
+                    let items = [StreamItem::Item(v)];
+                    st.emit(&mut memo, &mut budget, options, container, items)?;
+                    continue;
+                }
                 let top = stack
                     .last_mut()
                     .ok_or_else(|| anyhow!("Unexpected empty stack"))?;
@@ -303,11 +1172,21 @@ pub fn evaluate<'a>(
                     Value::Global(_, args) | Value::Seq(_, args) => {
                         args.push(v);
                     }
+                    Value::PyObject(obj) => obj.list_items.push(v),
+                    Value::Collection(coll) => match &mut coll.value {
+                        Value::Seq(_, items) => items.push(v),
+                        _ => bail!("Bad stack top for APPEND!"),
+                    },
                     _wut => bail!("Bad stack top for APPEND!"),
                 }
             }
             PickleOp::APPENDS => {
                 let postmark = stack.pop_mark()?;
+                if let (Some(st), [container]) = (stream.as_mut(), stack.as_slice()) {
+                    let items = postmark.into_iter().map(StreamItem::Item);
+                    st.emit(&mut memo, &mut budget, options, container, items)?;
+                    continue;
+                }
                 let top = stack
                     .last_mut()
                     .ok_or_else(|| anyhow!("Unexpected empty stack"))?;
@@ -317,12 +1196,17 @@ pub fn evaluate<'a>(
                     Value::Global(_, args) | Value::Seq(_, args) => {
                         args.extend(postmark);
                     }
+                    Value::PyObject(obj) => obj.list_items.extend(postmark),
+                    Value::Collection(coll) => match &mut coll.value {
+                        Value::Seq(_, items) => items.extend(postmark),
+                        _ => bail!("Bad stack top for APPENDS"),
+                    },
                     _wut => bail!("Bad stack top for APPENDS"),
                 }
             }
             PickleOp::DICT => {
                 let kvitems = make_kvlist(stack.pop_mark()?)?;
-                stack.push(Value::Seq(SequenceType::Dict, kvitems));
+                stack.push(Value::Dict(kvitems));
             }
             PickleOp::LIST => {
                 let items = stack.pop_mark()?;
@@ -330,20 +1214,24 @@ pub fn evaluate<'a>(
             }
             PickleOp::INST(mn, cn) => {
                 let args = stack.pop_mark()?;
-                stack.push(Value::Object(
-                    Box::new(Value::Seq(
-                        SequenceType::Tuple,
-                        vec![Value::String(mn), Value::String(cn)],
-                    )),
-                    args,
-                ))
+                stack.push(if objects {
+                    Value::PyObject(Box::new(PyObject::new(class(mn, cn, proto), args)))
+                } else {
+                    Value::Object(Box::new(class(mn, cn, proto)), args)
+                })
             }
             PickleOp::OBJ => {
                 let markidx = stack.find_mark()?;
-                let args = stack.0[markidx + 2..].to_owned();
-                let cls = stack.0[markidx + 1].clone();
+                ensure!(markidx + 1 < stack.len(), "Missing class for OBJ");
+                let args = stack.0.split_off(markidx + 2);
+                let cls = stack.pop()?;
                 stack.0.truncate(markidx);
-                stack.push(Value::Object(Box::new(cls), args));
+                stack.push(if objects {
+                    let cls = budget.resolve(&memo, cls)?;
+                    Value::PyObject(Box::new(PyObject::new(cls, args)))
+                } else {
+                    Value::Object(Box::new(cls), args)
+                });
             }
             PickleOp::PUT(midstr) => {
                 // Note: This technically incorrect since the memo id could actually be a string, but
@@ -351,14 +1239,37 @@ pub fn evaluate<'a>(
                 let mid = midstr.parse()?;
                 memo.insert(mid, stack.pop()?);
                 stack.push(Value::Ref(mid));
+                if let Some(st) = stream.as_mut() {
+                    st.inserted(mid);
+                }
             }
             PickleOp::NEWOBJ => {
                 let (args, cls) = (stack.pop()?, stack.pop()?);
-                stack.push(Value::Object(Box::new(cls), vec![args]))
+                let guess = options.collections && options.guess_namedtuples && !objects;
+                stack.push(if objects || options.collections {
+                    let (cls, args) = (budget.resolve(&memo, cls)?, budget.resolve(&memo, args)?);
+                    match args {
+                        Value::Seq(SequenceType::Tuple, args) if guess && !args.is_empty() => {
+                            namedtuple(cls, args)
+                        }
+                        Value::Seq(SequenceType::Tuple, args) if objects => {
+                            Value::PyObject(Box::new(PyObject::new(cls, args)))
+                        }
+                        _ if objects => bail!("Bad args for NEWOBJ"),
+                        args => Value::Object(Box::new(cls), vec![args]),
+                    }
+                } else {
+                    Value::Object(Box::new(cls), vec![args])
+                })
             }
             PickleOp::EMPTY_SET => stack.push(Value::Seq(SequenceType::Set, vec![])),
             PickleOp::ADDITEMS => {
                 let postmark = stack.pop_mark()?;
+                if let (Some(st), [container]) = (stream.as_mut(), stack.as_slice()) {
+                    let items = postmark.into_iter().map(StreamItem::Item);
+                    st.emit(&mut memo, &mut budget, options, container, items)?;
+                    continue;
+                }
                 let top = stack
                     .last_mut()
                     .ok_or_else(|| anyhow!("Unexpected empty stack"))?;
@@ -377,34 +1288,104 @@ pub fn evaluate<'a>(
             }
             PickleOp::NEWOBJ_EX => {
                 let (kwargs, args, cls) = (stack.pop()?, stack.pop()?, stack.pop()?);
-                stack.push(Value::Object(
-                    Box::new(cls),
-                    vec![Value::Seq(SequenceType::Tuple, vec![args, kwargs])],
-                ))
+                stack.push(if objects {
+                    let mut resolve = |v| budget.resolve(&memo, v);
+                    let obj = PyObject::new_ex(resolve(cls)?, resolve(args)?, resolve(kwargs)?)?;
+                    Value::PyObject(Box::new(obj))
+                } else {
+                    Value::Object(
+                        Box::new(cls),
+                        vec![Value::Seq(SequenceType::Tuple, vec![args, kwargs])],
+                    )
+                })
             }
             PickleOp::STACK_GLOBAL => {
                 let (gn, mn) = (
-                    memo.resolve(stack.pop()?, true)?,
-                    memo.resolve(stack.pop()?, true)?,
+                    fix_value(budget.resolve(&memo, stack.pop()?)?)?,
+                    fix_value(budget.resolve(&memo, stack.pop()?)?)?,
                 );
-                stack.push(Value::Global(
-                    Box::new(Value::Seq(SequenceType::Tuple, vec![gn, mn])),
-                    vec![],
-                ));
+                match (mn, gn) {
+                    (Value::String(module), Value::String(name)) => {
+                        stack.push(Value::Class { module, name })
+                    }
+                    _ => bail!("Bad module or name for STACK_GLOBAL"),
+                }
             }
             PickleOp::MEMOIZE => {
-                let item = stack.last().ok_or_else(|| anyhow!("Stack underrun"))?;
-                memo.insert(memo.0.len() as u32, item.to_owned());
+                // Same deal as PUT: The value lives in the memo and the stack just
+                // gets a reference so later modifications go to the right place.
+                let forgotten = stream.as_ref().map_or(0, |st| st.forgotten);
+                let mid = (memo.0.len() + forgotten) as u32;
+                memo.insert(mid, stack.pop()?);
+                stack.push(Value::Ref(mid));
+                if let Some(st) = stream.as_mut() {
+                    st.inserted(mid);
+                }
             }
 
+            PickleOp::GLOBAL(mn, gn) => stack.push(class(mn, gn, proto)),
+            PickleOp::STRING(_) | PickleOp::BINSTRING(_) | PickleOp::SHORT_BINSTRING(_)
+                if options.string_encoding != StringEncoding::default() =>
+            {
+                let val = decode_string(op.as_ref(), options.string_encoding)?;
+                stack.push(val.expect("Impossible: Not a string"))
+            }
+            // Framing is only a hint for buffering, it doesn't produce a value.
+            PickleOp::FRAME(_) => (),
+
             // Fallthrough case is just to push the op onto the stack as a Value::Raw.
-            op => stack.push(Value::Raw(Cow::Borrowed(op))),
+            _ => {
+                if strict {
+                    // Simple values are still pushed raw and fixed up later,
+                    // we just make sure there actually is a fix for them.
+                    if let Value::Raw(_) | Value::RawNum(_) = fix_value(Value::Raw(op.clone()))? {
+                        bail!("Unhandled op in strict mode: {op:?}");
+                    }
+                }
+                stack.push(Value::Raw(op))
+            }
+        }
+        if stack.len() > limits.max_stack_depth {
+            return Err(LimitExceeded::StackDepth(limits.max_stack_depth).into());
         }
+        if memo.0.len() > limits.max_memo_entries {
+            return Err(LimitExceeded::MemoEntries(limits.max_memo_entries).into());
+        }
+    }
+    if strict {
+        ensure!(stopped, "Missing STOP");
+        ensure!(stack.find_mark().is_err(), "Leftover MARK at STOP");
+        ensure!(
+            stack.len() == 1,
+            "Expected exactly one item on the stack at STOP, got {}",
+            stack.len()
+        );
     }
-    if !resolve_refs {
+    if let (Some(st), Some(top)) = (&stream, stack.first()) {
+        // If nothing got emitted, make sure it's at least the right kind
+        // of thing rather than quietly giving you the whole value.
+        let top = memo.resolve_ref(top)?;
+        let container = matches!(
+            top,
+            Value::Seq(SequenceType::List | SequenceType::Set, _) | Value::Dict(_)
+        ) || matches!(top, Value::Collection(coll)
+            if coll.kind != CollectionKind::NamedTuple);
+        if !(st.emitted || container) {
+            let name = fix_value(top.clone()).map_or("raw op", |v| v.type_name());
+            bail!("Expected a list or dict at the top level when streaming, got {name}");
+        }
+    }
+    if !options.resolve_refs {
         return Ok((stack.0, memo));
     }
-    let stack = memo.resolve_all_refs_iter(0, stack.0, true)?;
+    let mut resolver = Resolver::new(Cow::Owned(memo), true, budget);
+    if stream.is_none() {
+        // Only the things that got fetched or copied while evaluating (and
+        // whatever they refer to) get used more than once. Streaming takes
+        // care of the memo itself.
+        resolver.pinned = Some(resolver.memo.reachable(shared));
+    }
+    let stack = resolver.resolve_iter(0, stack.0)?;
 
-    Ok((stack, memo))
+    Ok((stack, resolver.memo.into_owned()))
 }
@@ -0,0 +1,524 @@
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+use anyhow::Result;
+use num_bigint::BigInt;
+
+use crate::{
+    collection::CollectionKind,
+    object::PyObject,
+    value::*,
+    visit::{self, ValueFolder},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+/// How `ValueKey` compares numbers.
+pub enum KeyMode {
+    /// Values are only the same if they're the same type, so `1`, `1.0`
+    /// and `True` are all different. `0.0` and `-0.0` are the same, and
+    /// so are all NaNs.
+    #[default]
+    Strict,
+
+    /// Like Python, where `1 == 1.0 == True` and a complex number with no
+    /// imaginary part is the same as a float. A set is also the same as a
+    /// frozen set with the same items, an `OrderedDict`, `defaultdict` or
+    /// `Counter` is the same as a dict and a namedtuple is the same as a
+    /// tuple. Arrays with the same items are the same whatever their
+    /// typecodes are. NaNs are still all the same, which
+    /// isn't like Python but you wouldn't be able to find them otherwise.
+    Python,
+}
+
+/// Whether two values would have the same key, even if they don't have the
+/// same lifetime.
+pub(crate) fn same(a: &Value<'_>, b: &Value<'_>, mode: KeyMode) -> bool {
+    Norm::new(a, mode) == Norm::new(b, mode)
+}
+
+/// Like `same`, but floats that differ by `tolerance` or less count as the
+/// same wherever they are in the values.
+pub(crate) fn near(a: &Value<'_>, b: &Value<'_>, mode: KeyMode, tolerance: f64) -> bool {
+    Norm::new(a, mode).near(&Norm::new(b, mode), tolerance)
+}
+
+#[derive(Debug, Clone)]
+/// A value that can be used as a key in a `HashMap`, `HashSet` or `BTreeMap`.
+/// Equality works like Python: dicts and sets are the same if they have the
+/// same items in any order, and an `int` is an `int` whether it's stored as
+/// `Value::Int` or `Value::BigInt`. Things Python can't compare (like lists
+/// and strings) are still ordered, just not in any meaningful way.
+///
+/// `Value::Raw` items get fixed up with `fix_value` first, but references
+/// don't get looked up (see [`ValueView`](crate::ValueView)). Don't mix keys made
+/// with different modes in the same collection.
+pub struct ValueKey<'v, 'a> {
+    /// The value this is the key for.
+    pub value: &'v Value<'a>,
+
+    norm: Norm<'v>,
+}
+
+impl<'v, 'a> ValueKey<'v, 'a> {
+    /// Make a key for a value.
+    pub fn new(value: &'v Value<'a>, mode: KeyMode) -> Self {
+        Self {
+            value,
+            norm: Norm::new(value, mode),
+        }
+    }
+}
+
+impl PartialEq for ValueKey<'_, '_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.norm == other.norm
+    }
+}
+
+impl Eq for ValueKey<'_, '_> {}
+
+impl Hash for ValueKey<'_, '_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.norm.hash(state)
+    }
+}
+
+impl PartialOrd for ValueKey<'_, '_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ValueKey<'_, '_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.norm.cmp(&other.norm)
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Make a key for this value. See `ValueKey`.
+    pub fn key(&self, mode: KeyMode) -> ValueKey<'_, 'a> {
+        ValueKey::new(self, mode)
+    }
+
+    /// Remove duplicate items from sets and duplicate keys from dicts,
+    /// everywhere in this value. Like Python, the first of the duplicate
+    /// set items is kept. For dicts the key stays where it was first set,
+    /// but gets the last value it was set to.
+    pub fn dedup(self, mode: KeyMode) -> Value<'a> {
+        Dedup(mode)
+            .fold_value(self)
+            .expect("Impossible: Dedup failed")
+    }
+}
+
+struct Dedup(KeyMode);
+
+impl<'a> ValueFolder<'a> for Dedup {
+    fn fold_seq(&mut self, typ: SequenceType, items: Vec<Value<'a>>) -> Result<Value<'a>> {
+        let items = items
+            .into_iter()
+            .map(|item| self.fold_value(item))
+            .collect::<Result<Vec<_>>>()?;
+        if matches!(typ, SequenceType::List | SequenceType::Tuple) {
+            return Ok(Value::Seq(typ, items));
+        }
+        let mut seen = HashSet::with_capacity(items.len());
+        let keep = items
+            .iter()
+            .map(|item| seen.insert(item.key(self.0)))
+            .collect::<Vec<_>>();
+        drop(seen);
+        let items = items
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(item, keep)| keep.then_some(item))
+            .collect();
+        Ok(Value::Seq(typ, items))
+    }
+
+    fn fold_dict(&mut self, items: Vec<(Value<'a>, Value<'a>)>) -> Result<Value<'a>> {
+        let items = items
+            .into_iter()
+            .map(|(k, v)| Ok((self.fold_value(k)?, self.fold_value(v)?)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Value::Dict(dedup_pairs(items, self.0)))
+    }
+
+    fn fold_pyobject(&mut self, obj: PyObject<'a>) -> Result<Value<'a>> {
+        Ok(match visit::fold_pyobject(self, obj)? {
+            Value::PyObject(mut obj) => {
+                obj.dict_items = dedup_pairs(std::mem::take(&mut obj.dict_items), self.0);
+                Value::PyObject(obj)
+            }
+            val => val,
+        })
+    }
+}
+
+fn dedup_pairs<'a>(
+    items: Vec<(Value<'a>, Value<'a>)>,
+    mode: KeyMode,
+) -> Vec<(Value<'a>, Value<'a>)> {
+    let mut first = HashMap::with_capacity(items.len());
+    let dest = items
+        .iter()
+        .enumerate()
+        .map(|(idx, (k, _))| *first.entry(k.key(mode)).or_insert(idx))
+        .collect::<Vec<_>>();
+    drop(first);
+    let mut deduped = (0..items.len()).map(|_| None).collect::<Vec<_>>();
+    for ((k, v), dest) in items.into_iter().zip(dest) {
+        match &mut deduped[dest] {
+            Some((_, old)) => *old = v,
+            slot => *slot = Some((k, v)),
+        }
+    }
+    deduped.into_iter().flatten().collect()
+}
+
+/// A float that's equal to itself. NaN and `-0.0` have to be normalized
+/// first, see `canon`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct F64(f64);
+
+impl F64 {
+    fn canon(f: f64) -> Self {
+        Self(if f.is_nan() {
+            f64::NAN
+        } else if f == 0.0 {
+            0.0
+        } else {
+            f
+        })
+    }
+}
+
+impl PartialEq for F64 {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for F64 {}
+
+impl Hash for F64 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state)
+    }
+}
+
+impl PartialOrd for F64 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for F64 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// A number. With `KeyMode::Python` floats that are whole numbers turn into
+/// ints, so an int and a float are never equal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Num {
+    Int(BigInt),
+    Float(F64),
+}
+
+impl PartialOrd for Num {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Num {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => a.cmp(b),
+            (Self::Float(a), Self::Float(b)) => a.cmp(b),
+            (Self::Int(a), Self::Float(b)) => int_float_cmp(a, b.0),
+            (Self::Float(a), Self::Int(b)) => int_float_cmp(b, a.0).reverse(),
+        }
+    }
+}
+
+/// Compare an int with a float that isn't a whole number.
+fn int_float_cmp(i: &BigInt, f: f64) -> Ordering {
+    if f.is_nan() || f == f64::INFINITY {
+        Ordering::Less
+    } else if f == f64::NEG_INFINITY {
+        Ordering::Greater
+    } else if *i <= float_to_bigint(f.floor()) {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}
+
+/// Convert a float that's a whole number into an int without losing anything.
+fn float_to_bigint(f: f64) -> BigInt {
+    let bits = f.to_bits();
+    let exp = ((bits >> 52) & 0x7ff) as i64;
+    if exp == 0 {
+        // Zero or subnormal, and subnormals aren't whole numbers.
+        return BigInt::from(0);
+    }
+    let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+    let exp = exp - 1075;
+    let val = if exp >= 0 {
+        BigInt::from(mantissa) << exp
+    } else {
+        BigInt::from(mantissa >> -exp)
+    };
+    if bits >> 63 == 1 {
+        -val
+    } else {
+        val
+    }
+}
+
+/// The children of a `Norm`. Comparing and hashing are recursive, so this
+/// makes sure there's enough stack for each level.
+#[derive(Debug, Clone)]
+pub(crate) struct Deep<T>(T);
+
+impl<T> Deref for Deep<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: PartialEq> PartialEq for Deep<T> {
+    fn eq(&self, other: &Self) -> bool {
+        deeper(|| self.0 == other.0)
+    }
+}
+
+impl<T: Eq> Eq for Deep<T> {}
+
+impl<T: Hash> Hash for Deep<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        deeper(|| self.0.hash(state))
+    }
+}
+
+impl<T: Ord> PartialOrd for Deep<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for Deep<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        deeper(|| self.0.cmp(&other.0))
+    }
+}
+
+/// A version of a value that can be compared directly. Dicts and sets are
+/// sorted and deduplicated, and numbers are normalized.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Norm<'v> {
+    None,
+    Bool(bool),
+    Num(Num),
+    Float(F64),
+    Complex(F64, F64),
+    Str(Cow<'v, str>),
+    Bytes(Cow<'v, [u8]>),
+    Seq(SequenceType, Deep<Vec<Norm<'v>>>),
+    Dict(Deep<Vec<(Norm<'v>, Norm<'v>)>>),
+    Class(&'v str, &'v str),
+    Node(&'static str, Deep<Vec<Norm<'v>>>),
+    Ref(u32),
+    Raw(String),
+}
+
+impl<'v> Norm<'v> {
+    pub(crate) fn new(val: &'v Value<'_>, mode: KeyMode) -> Self {
+        deeper(|| Self::new_inner(val, mode))
+    }
+
+    fn new_inner(val: &'v Value<'_>, mode: KeyMode) -> Self {
+        let many =
+            |items: &'v [Value<'_>]| items.iter().map(|v| Self::new(v, mode)).collect::<Vec<_>>();
+        let node = |typ, items: &[&'v Value<'_>]| {
+            Self::Node(
+                typ,
+                Deep(items.iter().map(|v| Self::new(v, mode)).collect()),
+            )
+        };
+        match val {
+            Value::Raw(_) => match fix_value(val.clone()) {
+                Ok(Value::String(s)) => Self::Str(Cow::Owned(s.into_owned())),
+                Ok(Value::Bytes(b)) => Self::Bytes(Cow::Owned(b.into_owned())),
+                Ok(Value::ByteArray(b)) => Self::bytearray(Cow::Owned(b.into_owned()), mode),
+                Ok(
+                    fixed @ (Value::Int(_)
+                    | Value::BigInt(_)
+                    | Value::Float(_)
+                    | Value::Bool(_)
+                    | Value::None),
+                ) => scalar(&fixed, mode),
+                _ => Self::Raw(format!("{val:?}")),
+            },
+            Value::None
+            | Value::Bool(_)
+            | Value::Int(_)
+            | Value::BigInt(_)
+            | Value::Float(_)
+            | Value::Complex(..) => scalar(val, mode),
+            Value::String(s) => Self::Str(Cow::Borrowed(s)),
+            Value::Bytes(b) => Self::Bytes(Cow::Borrowed(b)),
+            Value::ByteArray(b) => Self::bytearray(Cow::Borrowed(b), mode),
+            Value::Seq(typ @ (SequenceType::List | SequenceType::Tuple), items) => {
+                Self::Seq(typ.clone(), Deep(many(items)))
+            }
+            Value::Seq(typ, items) => {
+                let typ = match mode {
+                    KeyMode::Python => SequenceType::Set,
+                    KeyMode::Strict => typ.clone(),
+                };
+                let mut items = many(items);
+                items.sort();
+                items.dedup();
+                Self::Seq(typ, Deep(items))
+            }
+            Value::Dict(items) => Self::dict(items, mode),
+            Value::Class { module, name } => Self::Class(module, name),
+            Value::Global(target, args)
+            | Value::Object(target, args)
+            | Value::App(target, args) => Self::Node(
+                val.type_name(),
+                Deep(
+                    std::iter::once(Self::new(target, mode))
+                        .chain(many(args))
+                        .collect(),
+                ),
+            ),
+            Value::Build(target, state) => node("build", &[target, state]),
+            Value::PersId(pid) => node("persistent ID", &[pid]),
+            Value::Enum { class, value, .. } => node("enum", &[class, value]),
+            Value::Slice(start, stop, step) => node("slice", &[start, stop, step]),
+            Value::Range(start, stop, step) => node("range", &[start, stop, step]),
+            Value::PyObject(obj) => {
+                let opt = |val: &'v Option<Value<'_>>| {
+                    val.as_ref()
+                        .map_or(Self::Node("unset", Deep(vec![])), |v| Self::new(v, mode))
+                };
+                Self::Node(
+                    "Python object",
+                    Deep(vec![
+                        Self::new(&obj.class, mode),
+                        Self::Seq(SequenceType::Tuple, Deep(many(&obj.args))),
+                        Self::dict(&obj.kwargs, mode),
+                        opt(&obj.state),
+                        opt(&obj.slotstate),
+                        Self::Seq(SequenceType::List, Deep(many(&obj.list_items))),
+                        Self::dict(&obj.dict_items, mode),
+                    ]),
+                )
+            }
+            Value::Collection(coll) => match (mode, coll.kind) {
+                (KeyMode::Python, CollectionKind::Deque) => node("deque", &[&coll.value]),
+                (KeyMode::Python, _) => Self::new(&coll.value, mode),
+                (KeyMode::Strict, _) => {
+                    node(val.type_name(), &[&coll.class, &coll.extra, &coll.value])
+                }
+            },
+            Value::Array(array) => {
+                let items = array
+                    .to_values()
+                    .into_iter()
+                    .map(|v| match v {
+                        Value::String(s) => Self::Str(Cow::Owned(s.into_owned())),
+                        v => scalar(&v, mode),
+                    })
+                    .collect();
+                let items = Self::Seq(SequenceType::List, Deep(items));
+                match mode {
+                    KeyMode::Python => Self::Node("array", Deep(vec![items])),
+                    KeyMode::Strict => {
+                        let typecode = Self::Str(Cow::Owned(array.typecode.to_string()));
+                        Self::Node("array", Deep(vec![typecode, items]))
+                    }
+                }
+            }
+            Value::Ref(mid) => Self::Ref(*mid),
+            Value::RawNum(_) => Self::Raw(format!("{val:?}")),
+        }
+    }
+
+    /// Like in Python, a `bytearray` is equal to `bytes` with the same
+    /// contents unless we're being strict.
+    fn bytearray(b: Cow<'v, [u8]>, mode: KeyMode) -> Self {
+        match mode {
+            KeyMode::Python => Self::Bytes(b),
+            KeyMode::Strict => Self::Node("bytearray", Deep(vec![Self::Bytes(b)])),
+        }
+    }
+
+    /// Sort the items and keep the last value for duplicate keys.
+    fn dict(items: &'v [(Value<'_>, Value<'_>)], mode: KeyMode) -> Self {
+        let mut items = items
+            .iter()
+            .rev()
+            .map(|(k, v)| (Self::new(k, mode), Self::new(v, mode)))
+            .collect::<Vec<_>>();
+        items.sort_by(|(a, _), (b, _)| a.cmp(b));
+        items.dedup_by(|(a, _), (b, _)| a == b);
+        Self::Dict(Deep(items))
+    }
+
+    fn near(&self, other: &Self, tolerance: f64) -> bool {
+        deeper(|| self.near_inner(other, tolerance))
+    }
+
+    fn near_inner(&self, other: &Self, tolerance: f64) -> bool {
+        let float = |a: &F64, b: &F64| a == b || (a.0 - b.0).abs() <= tolerance;
+        let all = |a: &[Self], b: &[Self]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.near(b, tolerance))
+        };
+        match (self, other) {
+            (Self::Float(a), Self::Float(b))
+            | (Self::Num(Num::Float(a)), Self::Num(Num::Float(b))) => float(a, b),
+            (Self::Complex(ar, ai), Self::Complex(br, bi)) => float(ar, br) && float(ai, bi),
+            (Self::Seq(atyp, a), Self::Seq(btyp, b)) => atyp == btyp && all(a, b),
+            (Self::Node(atyp, a), Self::Node(btyp, b)) => atyp == btyp && all(a, b),
+            (Self::Dict(a), Self::Dict(b)) => {
+                a.len() == b.len()
+                    && (a.iter().zip(b.iter()))
+                        .all(|((ak, av), (bk, bv))| ak == bk && av.near(bv, tolerance))
+            }
+            (a, b) => a == b,
+        }
+    }
+}
+
+/// Normalize a number, bool or `None`.
+fn scalar(val: &Value<'_>, mode: KeyMode) -> Norm<'static> {
+    let python = mode == KeyMode::Python;
+    match val {
+        Value::Bool(b) if python => Norm::Num(Num::Int(BigInt::from(*b as u8))),
+        Value::Bool(b) => Norm::Bool(*b),
+        Value::Int(i) => Norm::Num(Num::Int(BigInt::from(*i))),
+        Value::BigInt(i) => Norm::Num(Num::Int(i.clone())),
+        Value::Float(f) if python && f.is_finite() && f.fract() == 0.0 => {
+            Norm::Num(Num::Int(float_to_bigint(*f)))
+        }
+        Value::Float(f) if python => Norm::Num(Num::Float(F64::canon(*f))),
+        Value::Float(f) => Norm::Float(F64::canon(*f)),
+        Value::Complex(re, im) if python && *im == 0.0 => scalar(&Value::Float(*re), mode),
+        Value::Complex(re, im) => Norm::Complex(F64::canon(*re), F64::canon(*im)),
+        _ => Norm::None,
+    }
+}
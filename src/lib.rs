@@ -4,13 +4,14 @@
 //!
 //! Something to get you started:
 //!
-//! ```rust
-//! use anyhow::Result;
+//! ```rust,no_run
+//! use anyhow::{anyhow, Result};
 //! use repugnant_pickle as rp;
 //!
 //! fn main() -> Result<()> {
 //!     let b = b"some bytes of a pickle here";
-//!     let (remaining_input, ops) = rp::parse_ops(b)?;
+//!     let (remaining_input, ops) = rp::parse_ops::<nom::error::VerboseError<&[u8]>>(b)
+//!         .map_err(|e| anyhow!("Parse error: {:?}", e))?;
 //!     let (values, memo_map) = rp::evaluate(&ops, true)?;
 //!     // Use the values here.
 //!     Ok(())
@@ -20,48 +21,54 @@
 //! And here's an example of what the parsed data might
 //! look like (from a PyTorch model):
 //!
-//! ```rust
+//! ```plaintext
 //! [Build(
-//!   Global(
-//!     Raw(GLOBAL("collections", "OrderedDict"), [
-//!       Seq(Tuple, []),
-//!       Seq(Tuple, [
-//!         Seq(Dict, [
-//!           String("emb.weight"),
-//!           Global(Raw(GLOBAL("torch._utils", "_rebuild_tensor_v2")), [
-//!             Seq(Tuple, [
-//!               PersId(Seq(Tuple, [
-//!                 String("storage"),
-//!                 Raw(GLOBAL("torch", "BFloat16Storage")),
-//!                 String("0"),
-//!                 String("cuda:0"),
-//!                 Int(430348288),
-//!               ])),
-//!               Int(327378944),
-//!               Seq(Tuple, [Int(1024), Int(50277)]),
-//!               Seq(Tuple, [Int(1), Int(1024)]),
-//!               Bool(false),
-//!               Global(Raw(GLOBAL("collections", "OrderedDict")), [
-//!                 Seq(Tuple, [])
-//!               ]),
+//!   Global(Class { module: "collections", name: "OrderedDict" }, [
+//!     Seq(Tuple, []),
+//!     Dict([
+//!       (
+//!         String("emb.weight"),
+//!         Global(Class { module: "torch._utils", name: "_rebuild_tensor_v2" }, [
+//!           Seq(Tuple, [
+//!             PersId(Seq(Tuple, [
+//!               String("storage"),
+//!               Class { module: "torch", name: "BFloat16Storage" },
+//!               String("0"),
+//!               String("cuda:0"),
+//!               Int(430348288),
+//!             ])),
+//!             Int(327378944),
+//!             Seq(Tuple, [Int(1024), Int(50277)]),
+//!             Seq(Tuple, [Int(1), Int(1024)]),
+//!             Bool(false),
+//!             Global(Class { module: "collections", name: "OrderedDict" }, [
+//!               Seq(Tuple, [])
 //!             ]),
 //!           ]),
 //!         ]),
-//!         Seq(Dict, [
-//!           String("blocks.0.ln1.weight"),
-//!           Global(Raw(GLOBAL("torch._utils", "_rebuild_tensor_v2")), [
-//!             // Etc.
-//!           ]),
+//!       ),
+//!       (
+//!         String("blocks.0.ln1.weight"),
+//!         Global(Class { module: "torch._utils", name: "_rebuild_tensor_v2" }, [
+//!           // Etc.
 //!         ]),
-//!       ]),
+//!       ),
 //!     ]),
-//!   ),
+//!   ]),
+//!   // Etc.
 //! )]
 //! ```
 
 /// Functions used for evaluating Pickle operations.
 pub mod eval;
 
+mod builtins;
+
+mod compat;
+
+/// An optional layer for treating things as Python objects.
+pub mod object;
+
 /// Pickle operations.
 pub mod ops;
 
@@ -72,14 +79,93 @@ pub mod parsers;
 /// The Value type you can get from evaluating pickle operations.
 pub mod value;
 
+/// Decoding `array.array` into typed vectors.
+pub mod array;
+
+/// Types from the `collections` module like `OrderedDict` and `deque`.
+pub mod collection;
+
+/// Converting values into plain Rust types.
+pub mod convert;
+
+/// Finding out what changed between two values.
+pub mod diff;
+
+/// Recognizing members of Python enums.
+pub mod enums;
+
+/// Using values as keys in maps and sets.
+pub mod key;
+
+/// Looking things up in values by key, index or path. These don't follow
+/// memo references, `ValueView` has the same lookups for values that
+/// haven't been resolved.
+pub mod path;
+
+/// A jq-like query language for poking around in values.
+pub mod query;
+
+/// Showing values in Python syntax.
+pub mod repr;
+
+/// Recognizing standard library types like `datetime.datetime`.
+pub mod stdlib;
+
+/// Traits for walking through and rewriting values.
+pub mod visit;
+
+/// Borrowed views of values that follow references on demand.
+pub mod view;
+
 #[cfg(feature = "torch")]
 pub mod torch;
 
-pub use crate::eval::evaluate;
+/// Deserializing values with Serde.
+#[cfg(feature = "serde")]
+pub mod de;
+
+/// Serializing values with Serde, in a form that can be read back.
+#[cfg(feature = "serde")]
+pub mod ser;
+
+pub use crate::eval::{
+    evaluate, evaluate_bytes, evaluate_bytes_with_options, evaluate_streaming,
+    evaluate_with_options, EvalLimits, EvalMode, EvalOptions, LimitExceeded, StreamItem,
+    StringEncoding,
+};
+
+pub use crate::array::{ArrayData, PyArray};
+
+pub use crate::collection::{Collection, CollectionKind};
+
+pub use crate::convert::{ConvertError, FromValue};
+
+pub use crate::diff::{Change, DiffOptions, Difference};
+
+pub use crate::enums::EnumTable;
+
+pub use crate::key::{KeyMode, ValueKey};
+
+pub use crate::object::PyObject;
 
 pub use crate::parsers::parse_ops;
 
+pub use crate::path::{Path, PathKey};
+
+pub use crate::query::{Query, QueryMatch};
+
+pub use crate::repr::ReprOptions;
+
+#[cfg(feature = "serde")]
+pub use crate::de::from_value;
+
 #[cfg(feature = "torch")]
 pub use crate::torch::{RepugnantTorchTensor, RepugnantTorchTensors, TensorType};
 
+pub use crate::stdlib::{Date, StdValue, Time, TimeDelta};
+
 pub use crate::value::{SequenceType, Value};
+
+pub use crate::view::ValueView;
+
+pub use crate::visit::{ValueFolder, ValueVisitor};
@@ -0,0 +1,187 @@
+use anyhow::{bail, Result};
+
+use crate::value::*;
+
+#[derive(Debug, Clone, PartialEq)]
+/// An instance of a Python class, put together the way Python's
+/// `__reduce_ex__` protocol would. You only get these if you evaluate
+/// with `EvalOptions::object_model` set.
+///
+/// Note that we can't actually tell the difference between calling a
+/// class and calling some random function with `REDUCE`, so the result
+/// of calling a function also ends up as one of these.
+pub struct PyObject<'a> {
+    /// The class. For `REDUCE` this is whatever got called.
+    pub class: Value<'a>,
+
+    /// Positional arguments for creating the object.
+    pub args: Vec<Value<'a>>,
+
+    /// Keyword arguments for creating the object. Only `NEWOBJ_EX`
+    /// (or `copyreg.__newobj_ex__`) produces these.
+    pub kwargs: Vec<(Value<'a>, Value<'a>)>,
+
+    /// The state from `BUILD`. Usually a dict of attributes, but
+    /// it can be anything if the class has `__setstate__`.
+    pub state: Option<Value<'a>>,
+
+    /// State for attributes in `__slots__`, if `BUILD` got a
+    /// `(state, slotstate)` tuple.
+    pub slotstate: Option<Value<'a>>,
+
+    /// Items added with `APPEND` or `APPENDS`.
+    pub list_items: Vec<Value<'a>>,
+
+    /// Items added with `SETITEM` or `SETITEMS`.
+    pub dict_items: Vec<(Value<'a>, Value<'a>)>,
+}
+
+impl<'a> PyObject<'a> {
+    /// Make an object with the specified class and arguments.
+    pub fn new(class: Value<'a>, args: Vec<Value<'a>>) -> Self {
+        Self {
+            class,
+            args,
+            kwargs: vec![],
+            state: None,
+            slotstate: None,
+            list_items: vec![],
+            dict_items: vec![],
+        }
+    }
+
+    /// The module and name of the class, if it's a `Value::Class`.
+    pub fn class_name(&self) -> Option<(&str, &str)> {
+        match &self.class {
+            Value::Class { module, name } => Some((module, name)),
+            _ => None,
+        }
+    }
+
+    /// Look up an attribute in the state or slot state. Only works when
+    /// they're dictionaries with string keys, and only if the key is a
+    /// string rather than a `Value::Raw` or a reference (see
+    /// [`ValueView`](crate::ValueView)).
+    pub fn attr(&self, name: &str) -> Option<&Value<'a>> {
+        [&self.slotstate, &self.state]
+            .into_iter()
+            .flatten()
+            .find_map(|st| st.dict_get_str(name))
+    }
+
+    /// Make an object from the result of `REDUCE` (which is expected to be
+    /// a `Value::Global`). The `copyreg` helpers Python uses for `__reduce_ex__`
+    /// with older protocols are unwrapped so you get the same thing you would
+    /// have with `NEWOBJ`. `resolve` gets used to look up memo references.
+    pub(crate) fn from_reduce(
+        val: Value<'a>,
+        resolve: &mut impl FnMut(Value<'a>) -> Result<Value<'a>>,
+    ) -> Result<Self> {
+        let (callable, args) = match val {
+            Value::Global(callable, args) => (*callable, args),
+            _ => bail!("Expected the result of REDUCE"),
+        };
+        let mut args = args.into_iter();
+        let argtuple = match args.next() {
+            Some(Value::Seq(SequenceType::Tuple, argtuple)) => argtuple,
+            Some(val) => vec![val],
+            None => vec![],
+        };
+        let dict_items = match args.next() {
+            Some(Value::Dict(items)) => items,
+            _ => vec![],
+        };
+
+        let is_copyreg =
+            |name| callable.is_class("copyreg", name) || callable.is_class("copy_reg", name);
+        let mut obj = if is_copyreg("__newobj__") && !argtuple.is_empty() {
+            let mut argtuple = argtuple.into_iter();
+            let cls = resolve(argtuple.next().expect("Impossible: Empty args"))?;
+            Self::new(cls, argtuple.collect())
+        } else if is_copyreg("__newobj_ex__") && argtuple.len() == 3 {
+            let mut argtuple = argtuple.into_iter().map(&mut *resolve);
+            let (cls, args, kwargs) = (
+                argtuple.next().expect("Impossible: Missing class")?,
+                argtuple.next().expect("Impossible: Missing args")?,
+                argtuple.next().expect("Impossible: Missing kwargs")?,
+            );
+            Self::new_ex(cls, args, kwargs)?
+        } else if is_copyreg("_reconstructor") && argtuple.len() == 3 {
+            // This is `base.__new__(cls, state)` unless `base` is `object`,
+            // in which case it's just `object.__new__(cls)`.
+            let mut argtuple = argtuple.into_iter();
+            let (cls, base, state) = (
+                resolve(argtuple.next().expect("Impossible: Missing class"))?,
+                resolve(argtuple.next().expect("Impossible: Missing base"))?,
+                argtuple.next().expect("Impossible: Missing state"),
+            );
+            let is_object =
+                base.is_class("builtins", "object") || base.is_class("__builtin__", "object");
+            Self::new(cls, if is_object { vec![] } else { vec![state] })
+        } else {
+            Self::new(callable, argtuple)
+        };
+        obj.dict_items = dict_items;
+        Ok(obj)
+    }
+
+    /// Make an object the way `NEWOBJ_EX` does. `args` should be a tuple and
+    /// `kwargs` a dict.
+    pub(crate) fn new_ex(class: Value<'a>, args: Value<'a>, kwargs: Value<'a>) -> Result<Self> {
+        let mut obj = match args {
+            Value::Seq(SequenceType::Tuple, args) => Self::new(class, args),
+            _ => bail!("Bad args for NEWOBJ_EX"),
+        };
+        obj.kwargs = match kwargs {
+            Value::Dict(kwargs) => kwargs,
+            _ => bail!("Bad kwargs for NEWOBJ_EX"),
+        };
+        Ok(obj)
+    }
+
+    /// Split the state from `BUILD` into the state and slot state. Like Python,
+    /// a 2-tuple where both items are either a dict or `None` gets treated as
+    /// `(state, slotstate)`. `resolve` gets used to look up memo references.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn split_state(
+        state: Value<'a>,
+        resolve: &mut impl FnMut(Value<'a>) -> Result<Value<'a>>,
+    ) -> Result<(Option<Value<'a>>, Option<Value<'a>>)> {
+        let dict_or_none = |v: &Value<'_>| matches!(v, Value::Dict(_) | Value::None);
+        Ok(match state {
+            Value::Seq(SequenceType::Tuple, items) if items.len() == 2 => {
+                let mut items = items.into_iter().map(&mut *resolve);
+                let (st, slotst) = (
+                    fix_value(items.next().expect("Impossible: Missing state")?)?,
+                    fix_value(items.next().expect("Impossible: Missing slotstate")?)?,
+                );
+                if dict_or_none(&st) && dict_or_none(&slotst) {
+                    let some = |v| (v != Value::None).then_some(v);
+                    (some(st), some(slotst))
+                } else {
+                    (
+                        Some(Value::Seq(SequenceType::Tuple, vec![st, slotst])),
+                        None,
+                    )
+                }
+            }
+            state => (Some(state), None),
+        })
+    }
+
+    /// Apply state from `BUILD`. See `split_state`.
+    pub(crate) fn apply_state(&mut self, state: Option<Value<'a>>, slotstate: Option<Value<'a>>) {
+        Self::merge_state(&mut self.state, state);
+        Self::merge_state(&mut self.slotstate, slotstate);
+    }
+
+    /// Dicts get merged (like updating `__dict__`), anything else just
+    /// replaces the existing state.
+    fn merge_state(dest: &mut Option<Value<'a>>, state: Option<Value<'a>>) {
+        match (dest, state) {
+            (Some(Value::Dict(items)), Some(Value::Dict(new_items))) => items.extend(new_items),
+            (dest, Some(state)) => *dest = Some(state),
+            (_, None) => (),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 #![allow(non_camel_case_types)]
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A decoded Pickle operation in its natural state.
 pub enum PickleOp<'a> {
     MARK,
@@ -21,7 +22,7 @@ pub enum PickleOp<'a> {
     STRING(&'a str),
     BINSTRING(&'a [u8]),
     SHORT_BINSTRING(&'a [u8]),
-    UNICODE(&'a str),
+    UNICODE(&'a [u8]),
     BINUNICODE(&'a str),
     APPEND,
     BUILD,
@@ -16,8 +16,14 @@ where
     IResult::Ok((i, s))
 }
 
+fn parse_bytes_nl<'a, E: ne::ParseError<&'a [u8]>>(i: &'a [u8]) -> IResult<&'a [u8], &'a [u8], E> {
+    let (i, b) = take_till(|c| c == b'\n')(i)?;
+    let (i, _) = tag(b"\n")(i)?;
+    IResult::Ok((i, b))
+}
+
 /// Parse 1+ ops into a Vec. It's a nom parser.
-pub fn parse_ops<'a, E>(i: &'a [u8]) -> IResult<&'a [u8], Vec<PickleOp>>
+pub fn parse_ops<'a, E>(i: &'a [u8]) -> IResult<&'a [u8], Vec<PickleOp<'a>>>
 where
     E: ne::ParseError<&'a [u8]> + ne::FromExternalError<&'a [u8], Utf8Error>,
 {
@@ -25,7 +31,7 @@ where
 }
 
 /// Parse a single op. It's nom parser.
-pub fn parse_op<'a, E>(i: &'a [u8]) -> IResult<&'a [u8], PickleOp>
+pub fn parse_op<'a, E>(i: &'a [u8]) -> IResult<&'a [u8], PickleOp<'a>>
 where
     E: ne::ParseError<&'a [u8]> + ne::FromExternalError<&'a [u8], Utf8Error>,
 {
@@ -51,7 +57,7 @@ where
             p_op::STRING => return map(parse_string_nl, PickleOp::STRING)(i),
             p_op::BINSTRING => return map(length_data(le_u32), PickleOp::BINSTRING)(i),
             p_op::SHORT_BINSTRING => return map(length_data(u8), PickleOp::SHORT_BINSTRING)(i),
-            p_op::UNICODE => return map(parse_string_nl, PickleOp::UNICODE)(i),
+            p_op::UNICODE => return map(parse_bytes_nl, PickleOp::UNICODE)(i),
             p_op::BINUNICODE => {
                 return map(
                     map_res(length_data(le_u32), std::str::from_utf8),
@@ -86,7 +92,7 @@ where
             p_op::TUPLE => PickleOp::TUPLE,
             p_op::EMPTY_TUPLE => PickleOp::EMPTY_TUPLE,
             p_op::SETITEMS => PickleOp::SETITEMS,
-            p_op::BINFLOAT => return map(le_f64, PickleOp::BINFLOAT)(i),
+            p_op::BINFLOAT => return map(be_f64, PickleOp::BINFLOAT)(i),
             p_op::PROTO => return map(u8, PickleOp::PROTO)(i),
             p_op::NEWOBJ => PickleOp::NEWOBJ,
             p_op::EXT1 => return map(u8, PickleOp::EXT1)(i),
@@ -0,0 +1,321 @@
+use std::{borrow::Cow, fmt};
+
+use crate::value::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// One step in a `Path`.
+pub enum PathKey<'k> {
+    /// A string dict key or an attribute name. If neither of those exist
+    /// and it looks like a number, it gets treated like `PathKey::Int`.
+    Str(Cow<'k, str>),
+
+    /// An int dict key or an index into a sequence. Negative indexes
+    /// count from the end, like in Python.
+    Int(i64),
+}
+
+impl PathKey<'_> {
+    /// Make a copy that doesn't borrow anything.
+    pub fn into_owned(self) -> PathKey<'static> {
+        match self {
+            Self::Str(s) => PathKey::Str(Cow::Owned(s.into_owned())),
+            Self::Int(i) => PathKey::Int(i),
+        }
+    }
+}
+
+impl<'k> From<&'k str> for PathKey<'k> {
+    fn from(value: &'k str) -> Self {
+        Self::Str(Cow::Borrowed(value))
+    }
+}
+
+impl From<String> for PathKey<'_> {
+    fn from(value: String) -> Self {
+        Self::Str(Cow::Owned(value))
+    }
+}
+
+impl From<i64> for PathKey<'_> {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<i32> for PathKey<'_> {
+    fn from(value: i32) -> Self {
+        Self::Int(value.into())
+    }
+}
+
+impl From<usize> for PathKey<'_> {
+    fn from(value: usize) -> Self {
+        // Nothing has that many items, so looking it up still fails.
+        Self::Int(i64::try_from(value).unwrap_or(i64::MAX))
+    }
+}
+
+impl fmt::Display for PathKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Str(s) => f.write_str(s),
+            Self::Int(i) => i.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// A list of keys to follow to get to a value. You can make one from a string
+/// like `"state_dict/blocks.0.ln1.weight"` (keys separated by `/`) or from a
+/// list of keys if you need something like an int key or a key with a `/` in it:
+/// `[PathKey::from("optimizer"), "state".into(), 3.into()]`.
+///
+/// Paths display as the keys separated by `/`.
+pub struct Path<'k>(pub Vec<PathKey<'k>>);
+
+impl<'k> Path<'k> {
+    /// A path with a key added to the end.
+    pub fn join(&self, key: impl Into<PathKey<'k>>) -> Self {
+        let mut path = self.clone();
+        path.0.push(key.into());
+        path
+    }
+
+    /// Make a copy that doesn't borrow anything.
+    pub fn into_owned(self) -> Path<'static> {
+        Path(self.0.into_iter().map(PathKey::into_owned).collect())
+    }
+}
+
+impl<'k> From<&'k str> for Path<'k> {
+    fn from(value: &'k str) -> Self {
+        Self(
+            value
+                .split('/')
+                .filter(|key| !key.is_empty())
+                .map(PathKey::from)
+                .collect(),
+        )
+    }
+}
+
+impl<'k, K: Into<PathKey<'k>>, const N: usize> From<[K; N]> for Path<'k> {
+    fn from(value: [K; N]) -> Self {
+        Self(value.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<'k> From<Vec<PathKey<'k>>> for Path<'k> {
+    fn from(value: Vec<PathKey<'k>>) -> Self {
+        Self(value)
+    }
+}
+
+impl<'k> From<&[PathKey<'k>]> for Path<'k> {
+    fn from(value: &[PathKey<'k>]) -> Self {
+        Self(value.to_vec())
+    }
+}
+
+impl fmt::Display for Path<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, key) in self.0.iter().enumerate() {
+            if idx > 0 {
+                f.write_str("/")?;
+            }
+            key.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Look up a key in a dictionary. Unlike `dict_get`, the key can be
+    /// borrowed from anywhere and this also looks in things that got built
+    /// from a dict like `collections.OrderedDict`, with or without
+    /// `EvalOptions::object_model`. If there are duplicate keys, the last
+    /// one wins.
+    ///
+    /// None of these lookups follow memo references, see
+    /// [`ValueView`](crate::ValueView).
+    pub fn get(&self, key: &Value<'_>) -> Option<&Value<'a>> {
+        get(self, key, no_refs)
+    }
+
+    /// Get an item from a sequence. This also works for the items that got
+    /// appended to a `PyObject`, and for the arguments of calls like
+    /// `torch._utils._rebuild_tensor_v2(...)`. A persistent ID works like
+    /// a call with one argument.
+    pub fn index(&self, idx: usize) -> Option<&Value<'a>> {
+        index(self, i64::try_from(idx).ok()?, no_refs)
+    }
+
+    /// Get an attribute of an object. That's the state from `BUILD` (or
+    /// `PyObject::attr`) when it's a dict.
+    pub fn attr(&self, name: &str) -> Option<&Value<'a>> {
+        attr(self, name, no_refs)
+    }
+
+    /// Follow a path of dict keys, indexes and attributes. See `Path` and
+    /// `PathKey` for how keys work.
+    pub fn at<'k>(&self, path: impl Into<Path<'k>>) -> Option<&Value<'a>> {
+        at(self, &path.into(), no_refs)
+    }
+}
+
+fn no_refs<'m, 'a>(val: &'m Value<'a>) -> &'m Value<'a> {
+    val
+}
+
+/// Compare dict keys. Unlike `==`, the values don't have to have the same lifetime.
+pub(crate) fn key_eq(a: &Value<'_>, b: &Value<'_>) -> bool {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Bytes(a), Value::Bytes(b)) => a == b,
+        (Value::Int(a), Value::Int(b)) => a == b,
+        (Value::BigInt(a), Value::BigInt(b)) => a == b,
+        (Value::Float(a), Value::Float(b)) => a == b,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::None, Value::None) => true,
+        (Value::Seq(atyp, a), Value::Seq(btyp, b)) => {
+            atyp == btyp && a.len() == b.len() && a.iter().zip(b).all(|(a, b)| key_eq(a, b))
+        }
+        (
+            Value::Class { module, name },
+            Value::Class {
+                module: bmodule,
+                name: bname,
+            },
+        ) => module == bmodule && name == bname,
+        _ => false,
+    }
+}
+
+/// Skip over `BUILD` to get to the thing that got built, and over
+/// `Value::Collection` to get to its items.
+pub(crate) fn container<'m, 'a>(
+    val: &'m Value<'a>,
+    resolve: impl Fn(&'m Value<'a>) -> &'m Value<'a>,
+) -> &'m Value<'a> {
+    let mut val = resolve(val);
+    loop {
+        val = match val {
+            Value::Build(target, _) => resolve(target),
+            Value::Collection(coll) => resolve(&coll.value),
+            val => return val,
+        };
+    }
+}
+
+/// The lookups below take a function that follows references, so `ValueView`
+/// can use them too.
+pub(crate) fn get<'m, 'a>(
+    val: &'m Value<'a>,
+    key: &Value<'_>,
+    resolve: impl Fn(&'m Value<'a>) -> &'m Value<'a>,
+) -> Option<&'m Value<'a>> {
+    dict_items(val, &resolve)?
+        .iter()
+        .rfind(|(k, _)| match resolve(k) {
+            k @ Value::Raw(_) => fix_value(k.clone()).is_ok_and(|k| key_eq(&k, key)),
+            k => key_eq(k, key),
+        })
+        .map(|(_, v)| resolve(v))
+}
+
+/// The key/value pairs `get` looks in.
+pub(crate) fn dict_items<'m, 'a>(
+    val: &'m Value<'a>,
+    resolve: impl Fn(&'m Value<'a>) -> &'m Value<'a>,
+) -> Option<&'m [(Value<'a>, Value<'a>)]> {
+    match container(val, &resolve) {
+        Value::PyObject(obj) => Some(&obj.dict_items),
+        val => val.dict_items(),
+    }
+}
+
+/// The items `index` looks in.
+pub(crate) fn seq_items<'m, 'a>(
+    val: &'m Value<'a>,
+    resolve: impl Fn(&'m Value<'a>) -> &'m Value<'a>,
+) -> Option<&'m [Value<'a>]> {
+    Some(match container(val, &resolve) {
+        Value::Seq(_, items) => items,
+        Value::PersId(pid) => std::slice::from_ref(pid.as_ref()),
+        Value::PyObject(obj) if obj.list_items.is_empty() => &obj.args,
+        Value::PyObject(obj) => &obj.list_items,
+        Value::Global(_, args) | Value::Object(_, args) | Value::App(_, args) => {
+            match args.first().map(&resolve) {
+                Some(Value::Seq(SequenceType::Tuple, targs)) => targs,
+                _ => args,
+            }
+        }
+        _ => return None,
+    })
+}
+
+pub(crate) fn index<'m, 'a>(
+    val: &'m Value<'a>,
+    idx: i64,
+    resolve: impl Fn(&'m Value<'a>) -> &'m Value<'a>,
+) -> Option<&'m Value<'a>> {
+    let items = seq_items(val, &resolve)?;
+    let idx = if idx < 0 {
+        items.len().checked_sub(idx.unsigned_abs() as usize)?
+    } else {
+        idx as usize
+    };
+    items.get(idx).map(resolve)
+}
+
+pub(crate) fn attr<'m, 'a>(
+    val: &'m Value<'a>,
+    name: &str,
+    resolve: impl Fn(&'m Value<'a>) -> &'m Value<'a>,
+) -> Option<&'m Value<'a>> {
+    let key = Value::String(Cow::Borrowed(name));
+    match resolve(val) {
+        Value::PyObject(obj) => [&obj.slotstate, &obj.state]
+            .into_iter()
+            .flatten()
+            .find_map(|st| get(st, &key, &resolve)),
+        Value::Build(_, state) => match resolve(state) {
+            Value::Seq(SequenceType::Tuple, items) if items.len() == 2 => {
+                items.iter().rev().find_map(|st| get(st, &key, &resolve))
+            }
+            state => get(state, &key, &resolve),
+        },
+        _ => None,
+    }
+}
+
+pub(crate) fn step<'m, 'a>(
+    val: &'m Value<'a>,
+    key: &PathKey<'_>,
+    resolve: impl Fn(&'m Value<'a>) -> &'m Value<'a>,
+) -> Option<&'m Value<'a>> {
+    match key {
+        PathKey::Str(s) => get(val, &Value::String(Cow::Borrowed(s)), &resolve)
+            .or_else(|| attr(val, s, &resolve))
+            .or_else(|| step_int(val, s.parse().ok()?, &resolve)),
+        PathKey::Int(i) => step_int(val, *i, &resolve),
+    }
+}
+
+fn step_int<'m, 'a>(
+    val: &'m Value<'a>,
+    i: i64,
+    resolve: impl Fn(&'m Value<'a>) -> &'m Value<'a>,
+) -> Option<&'m Value<'a>> {
+    get(val, &Value::Int(i), &resolve).or_else(|| index(val, i, &resolve))
+}
+
+pub(crate) fn at<'m, 'a>(
+    val: &'m Value<'a>,
+    path: &Path<'_>,
+    resolve: impl Fn(&'m Value<'a>) -> &'m Value<'a>,
+) -> Option<&'m Value<'a>> {
+    path.0
+        .iter()
+        .try_fold(resolve(val), |val, key| step(val, key, &resolve))
+}
@@ -0,0 +1,616 @@
+//! Queries look like a small subset of [jq](https://jqlang.github.io/jq/).
+//! A query is a list of filters. Each filter takes the values that came out
+//! of the previous one and turns every one of them into zero or more values.
+//! You can separate filters with `|` if it makes things easier to read, but
+//! you don't have to.
+//!
+//! | Filter | Result |
+//! |--------|--------|
+//! | `.` | The value itself. |
+//! | `.name`, `."any key"`, `["any key"]` | A dict key or attribute, like `Value::at`. |
+//! | `[3]`, `[-1]` | An item from a sequence, the arguments of a call or an int dict key. |
+//! | `.*`, `[*]`, `[]` | Every child: Items of sequences, dict values, attributes, etc. |
+//! | `..` | The value and everything in it, recursively. |
+//! | `select(condition)` | The value, if the condition is true. |
+//! | `{name, key: filters, ...}` | A dict built from the value. `name` is short for `name: .name`. |
+//!
+//! A condition compares a property of the value with a literal (a string in
+//! double quotes, a number, `true`, `false` or `null`). Conditions can be
+//! combined with `and`, `or`, `not` and parentheses. The properties are:
+//!
+//! * `type`: What `Value::type_name` returns, like `"dict"` or `"string"`.
+//! * `class`: The `module.name` of the class for calls, objects and classes.
+//! * `key`: The last key in the path to the value.
+//! * `value`: The value itself if it's a string, number, bool or `None`.
+//! * `len`: The length of a sequence, dict, string, bytes or array. This
+//!   includes `collections` types like `deque`.
+//!
+//! Properties a value doesn't have are `null`. You can compare with `==`,
+//! `!=`, `<`, `<=`, `>` and `>=`, or with `=~` which matches a pattern where
+//! `*` is anything and `?` is any single character.
+//!
+//! In a dict built with `{...}`, a key that got no results is `None` and one
+//! that got more than one is a list.
+//!
+//! Some examples:
+//!
+//! ```plaintext
+//! ..|select(class == "torch._utils._rebuild_tensor_v2")
+//! .state_dict.*|select(key =~ "*.weight")|{shape: .[2]}
+//! .optimizer.state[3]
+//! ```
+
+use std::borrow::Cow;
+
+use anyhow::{anyhow, Result};
+use nom::{
+    branch::alt,
+    bytes::complete::{escaped_transform, is_not, tag},
+    character::complete::{alpha1, alphanumeric1, char, multispace0},
+    combinator::{all_consuming, map, map_res, opt, recognize, value},
+    multi::{many0, many0_count, separated_list0, separated_list1},
+    number::complete::recognize_float,
+    sequence::{delimited, pair, preceded, separated_pair, tuple},
+    IResult,
+};
+
+use crate::{
+    path::{self, Path, PathKey},
+    value::*,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+/// A parsed query. See the module docs for the syntax.
+pub struct Query(Vec<Filter>);
+
+#[derive(Debug, Clone, PartialEq)]
+/// A value that a query found, and where it found it.
+pub struct QueryMatch<'m, 'a> {
+    /// The path to the value. If it's something a `{...}` filter built,
+    /// this is the path to the value it was built from.
+    pub path: Path<'m>,
+
+    /// The value. This is only owned if it was built by the query.
+    pub value: Cow<'m, Value<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Identity,
+    Key(PathKey<'static>),
+    Children,
+    Recurse,
+    Select(Cond),
+    Project(Vec<(String, Vec<Filter>)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Cond {
+    Compare(Property, CmpOp, Literal),
+    Not(Box<Cond>),
+    And(Vec<Cond>),
+    Or(Vec<Cond>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Property {
+    Type,
+    Class,
+    Key,
+    Value,
+    Len,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Glob,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Null,
+}
+
+impl Query {
+    /// Parse a query.
+    pub fn new(query: &str) -> Result<Self> {
+        match all_consuming(ws(pipeline))(query) {
+            Ok((_, filters)) => Ok(Self(filters)),
+            Err(nom::Err::Error(e) | nom::Err::Failure(e)) => Err(anyhow!(
+                "Query parse error at offset {}: {:?}",
+                query.len() - e.input.len(),
+                e.code
+            )),
+            Err(nom::Err::Incomplete(_)) => Err(anyhow!("Incomplete query")),
+        }
+    }
+
+    /// Run the query. References don't get looked up, see
+    /// [`ValueView`](crate::ValueView).
+    pub fn run<'m, 'a>(&self, value: &'m Value<'a>) -> Vec<QueryMatch<'m, 'a>> {
+        let start = QueryMatch {
+            path: Path::default(),
+            value: Cow::Borrowed(value),
+        };
+        run_filters(&self.0, vec![start])
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Parse and run a query. See the `query` module for the syntax.
+    pub fn query(&self, query: &str) -> Result<Vec<QueryMatch<'_, 'a>>> {
+        Ok(Query::new(query)?.run(self))
+    }
+}
+
+fn run_filters<'m, 'a>(
+    filters: &[Filter],
+    mut items: Vec<QueryMatch<'m, 'a>>,
+) -> Vec<QueryMatch<'m, 'a>> {
+    for filter in filters {
+        items = items
+            .into_iter()
+            .flat_map(|item| run_filter(filter, item))
+            .collect();
+    }
+    items
+}
+
+fn run_filter<'m, 'a>(filter: &Filter, item: QueryMatch<'m, 'a>) -> Vec<QueryMatch<'m, 'a>> {
+    match filter {
+        Filter::Identity => vec![item],
+        Filter::Key(key) => {
+            let found = match &item.value {
+                Cow::Borrowed(val) => path::step(val, key, no_refs).map(Cow::Borrowed),
+                Cow::Owned(val) => path::step(val, key, no_refs).map(|v| Cow::Owned(v.clone())),
+            };
+            found
+                .map(|value| QueryMatch {
+                    path: item.path.join(key.clone()),
+                    value,
+                })
+                .into_iter()
+                .collect()
+        }
+        Filter::Children => children(&item),
+        Filter::Recurse => {
+            let mut found = vec![];
+            let mut todo = vec![item];
+            while let Some(item) = todo.pop() {
+                todo.extend(children(&item).into_iter().rev());
+                found.push(item);
+            }
+            found
+        }
+        Filter::Select(cond) => {
+            if cond.eval(&item) {
+                vec![item]
+            } else {
+                vec![]
+            }
+        }
+        Filter::Project(fields) => {
+            let items = fields
+                .iter()
+                .map(|(name, filters)| {
+                    let mut found = run_filters(filters, vec![item.clone()])
+                        .into_iter()
+                        .map(|m| m.value.into_owned())
+                        .collect::<Vec<_>>();
+                    let val = match found.len() {
+                        0 => Value::None,
+                        1 => found.pop().expect("Impossible: Empty results"),
+                        _ => Value::Seq(SequenceType::List, found),
+                    };
+                    (Value::String(Cow::Owned(name.clone())), val)
+                })
+                .collect();
+            vec![QueryMatch {
+                path: item.path,
+                value: Cow::Owned(Value::Dict(items)),
+            }]
+        }
+    }
+}
+
+fn no_refs<'m, 'a>(val: &'m Value<'a>) -> &'m Value<'a> {
+    val
+}
+
+fn children<'m, 'a>(item: &QueryMatch<'m, 'a>) -> Vec<QueryMatch<'m, 'a>> {
+    match &item.value {
+        Cow::Borrowed(val) => child_values(val)
+            .into_iter()
+            .map(|(key, value)| QueryMatch {
+                path: item.path.join(key),
+                value: Cow::Borrowed(value),
+            })
+            .collect(),
+        Cow::Owned(val) => child_values(val)
+            .into_iter()
+            .map(|(key, value)| QueryMatch {
+                path: item.path.join(key.into_owned()),
+                value: Cow::Owned(value.clone()),
+            })
+            .collect(),
+    }
+}
+
+/// The children of a value, with keys that `path::step` can use to find them
+/// again. Dict keys that aren't strings or ints get turned into strings, so
+/// those won't work.
+pub(crate) fn child_values<'m, 'a>(val: &'m Value<'a>) -> Vec<(PathKey<'m>, &'m Value<'a>)> {
+    child_items(val)
+        .into_iter()
+        .map(|(key, _, value)| (key, value))
+        .collect()
+}
+
+/// A child from `child_items`: the path key, the dict key if it came from a
+/// dict and the value.
+pub(crate) type Child<'m, 'a> = (PathKey<'m>, Option<&'m Value<'a>>, &'m Value<'a>);
+
+/// Like `child_values`, but you also get the dict keys themselves.
+pub(crate) fn child_items<'m, 'a>(val: &'m Value<'a>) -> Vec<Child<'m, 'a>> {
+    fn indexed<'m, 'a>(items: &'m [Value<'a>]) -> Vec<Child<'m, 'a>> {
+        items
+            .iter()
+            .enumerate()
+            .map(|(idx, v)| (idx.into(), None, v))
+            .collect()
+    }
+    fn keyed<'m, 'a>(items: &'m [(Value<'a>, Value<'a>)]) -> Vec<Child<'m, 'a>> {
+        items
+            .iter()
+            .map(|(k, v)| {
+                let key = match k {
+                    Value::String(s) => PathKey::Str(Cow::Borrowed(s.as_ref())),
+                    Value::Int(i) => PathKey::Int(*i),
+                    k => PathKey::Str(Cow::Owned(k.to_string())),
+                };
+                (key, Some(k), v)
+            })
+            .collect()
+    }
+    let state_items = |state: &'m Value<'a>| match state {
+        Value::Seq(SequenceType::Tuple, items) if items.len() == 2 => items
+            .iter()
+            .filter_map(Value::dict_items)
+            .flat_map(keyed)
+            .collect(),
+        state => state.dict_items().map(keyed).unwrap_or_default(),
+    };
+    if let Some(items) = val.dict_items() {
+        return keyed(items);
+    }
+    match val {
+        Value::Seq(_, items) => indexed(items),
+        Value::Build(target, state) => {
+            let mut found = child_items(target);
+            found.extend(state_items(state));
+            found
+        }
+        Value::PyObject(obj) => {
+            let mut found = keyed(&obj.dict_items);
+            found.extend(indexed(if obj.list_items.is_empty() {
+                &obj.args
+            } else {
+                &obj.list_items
+            }));
+            found.extend(obj.slotstate.iter().chain(&obj.state).flat_map(state_items));
+            found
+        }
+        Value::Collection(coll) => child_items(&coll.value),
+        Value::PersId(pid) => indexed(std::slice::from_ref(pid.as_ref())),
+        Value::Global(_, args) | Value::Object(_, args) | Value::App(_, args) => {
+            match args.first() {
+                Some(Value::Seq(SequenceType::Tuple, targs)) => indexed(targs),
+                _ => indexed(args),
+            }
+        }
+        _ => vec![],
+    }
+}
+
+/// The class of a value for the `class` property.
+pub(crate) fn class_name(val: &Value<'_>) -> Option<String> {
+    match val {
+        Value::Class { module, name } => Some(format!("{module}.{name}")),
+        Value::Global(target, _)
+        | Value::Object(target, _)
+        | Value::App(target, _)
+        | Value::Build(target, _) => class_name(target),
+        Value::PyObject(obj) => class_name(&obj.class),
+        Value::Collection(coll) => class_name(&coll.class),
+        Value::Enum { class, .. } => class_name(class),
+        _ => None,
+    }
+}
+
+impl Cond {
+    fn eval(&self, item: &QueryMatch<'_, '_>) -> bool {
+        match self {
+            Self::Compare(prop, op, lit) => compare(&property(*prop, item), *op, lit),
+            Self::Not(cond) => !cond.eval(item),
+            Self::And(conds) => conds.iter().all(|c| c.eval(item)),
+            Self::Or(conds) => conds.iter().any(|c| c.eval(item)),
+        }
+    }
+}
+
+/// The `len` property, which works like Python's `len`.
+fn len(val: &Value<'_>) -> Literal {
+    match val {
+        Value::Seq(_, items) => Literal::Int(items.len() as i64),
+        Value::String(s) => Literal::Int(s.chars().count() as i64),
+        Value::Bytes(b) | Value::ByteArray(b) => Literal::Int(b.len() as i64),
+        Value::Array(array) => Literal::Int(array.len() as i64),
+        val => val
+            .dict_items()
+            .map_or(Literal::Null, |items| Literal::Int(items.len() as i64)),
+    }
+}
+
+fn property(prop: Property, item: &QueryMatch<'_, '_>) -> Literal {
+    let val = item.value.as_ref();
+    match prop {
+        Property::Type => Literal::Str(val.type_name().to_string()),
+        Property::Class => class_name(val).map_or(Literal::Null, Literal::Str),
+        Property::Key => match item.path.0.last() {
+            Some(PathKey::Str(s)) => Literal::Str(s.to_string()),
+            Some(PathKey::Int(i)) => Literal::Int(*i),
+            None => Literal::Null,
+        },
+        Property::Value => match val {
+            Value::String(s) => Literal::Str(s.to_string()),
+            Value::Int(i) => Literal::Int(*i),
+            Value::Float(f) => Literal::Float(*f),
+            Value::Bool(b) => Literal::Bool(*b),
+            _ => Literal::Null,
+        },
+        Property::Len => match val {
+            Value::Collection(coll) => len(&coll.value),
+            val => len(val),
+        },
+    }
+}
+
+fn compare(a: &Literal, op: CmpOp, b: &Literal) -> bool {
+    use std::cmp::Ordering;
+
+    let ord = match (a, b) {
+        (Literal::Str(a), Literal::Str(b)) => Some(a.cmp(b)),
+        (Literal::Int(a), Literal::Int(b)) => Some(a.cmp(b)),
+        (Literal::Int(_) | Literal::Float(_), Literal::Int(_) | Literal::Float(_)) => {
+            let num = |l: &Literal| match l {
+                Literal::Int(i) => *i as f64,
+                Literal::Float(f) => *f,
+                _ => unreachable!(),
+            };
+            num(a).partial_cmp(&num(b))
+        }
+        (Literal::Bool(a), Literal::Bool(b)) => Some(a.cmp(b)),
+        (Literal::Null, Literal::Null) => Some(Ordering::Equal),
+        _ => None,
+    };
+    match op {
+        CmpOp::Eq => ord == Some(Ordering::Equal),
+        CmpOp::Ne => ord != Some(Ordering::Equal),
+        CmpOp::Lt => ord == Some(Ordering::Less),
+        CmpOp::Le => matches!(ord, Some(Ordering::Less | Ordering::Equal)),
+        CmpOp::Gt => ord == Some(Ordering::Greater),
+        CmpOp::Ge => matches!(ord, Some(Ordering::Greater | Ordering::Equal)),
+        CmpOp::Glob => match (a, b) {
+            (Literal::Str(s), Literal::Str(pat)) => glob(pat, s),
+            (Literal::Int(i), Literal::Str(pat)) => glob(pat, &i.to_string()),
+            _ => false,
+        },
+    }
+}
+
+/// Match a pattern where `*` is any number of characters and `?` is one.
+fn glob(pat: &str, s: &str) -> bool {
+    let (pat, s) = (
+        pat.chars().collect::<Vec<_>>(),
+        s.chars().collect::<Vec<_>>(),
+    );
+    let (mut pi, mut si) = (0, 0);
+    // Where to go back to if the rest doesn't match: The last `*` and the
+    // position in the string it's matched up to.
+    let mut retry = None;
+    while si < s.len() {
+        match pat.get(pi) {
+            Some('*') => {
+                retry = Some((pi, si));
+                pi += 1;
+            }
+            Some(&c) if c == '?' || c == s[si] => {
+                pi += 1;
+                si += 1;
+            }
+            _ => match retry {
+                Some((rpi, rsi)) => {
+                    retry = Some((rpi, rsi + 1));
+                    (pi, si) = (rpi + 1, rsi + 1);
+                }
+                None => return false,
+            },
+        }
+    }
+    pat[pi..].iter().all(|&c| c == '*')
+}
+
+type PResult<'q, T> = IResult<&'q str, T>;
+
+fn ws<'q, T>(p: impl FnMut(&'q str) -> PResult<'q, T>) -> impl FnMut(&'q str) -> PResult<'q, T> {
+    delimited(multispace0, p, multispace0)
+}
+
+fn pipeline(i: &str) -> PResult<'_, Vec<Filter>> {
+    map(
+        separated_list1(ws(char('|')), many0(ws(filter))),
+        |chains| chains.into_iter().flatten().collect(),
+    )(i)
+}
+
+fn filter(i: &str) -> PResult<'_, Filter> {
+    alt((
+        value(Filter::Recurse, tag("..")),
+        preceded(opt(char('.')), bracket),
+        preceded(
+            char('.'),
+            alt((
+                value(Filter::Children, char('*')),
+                map(alt((map(ident, str::to_string), string_lit)), |s| {
+                    Filter::Key(PathKey::from(s))
+                }),
+            )),
+        ),
+        value(Filter::Identity, char('.')),
+        map(
+            preceded(tag("select"), delimited(ws(char('(')), cond, char(')'))),
+            Filter::Select,
+        ),
+        map(
+            delimited(
+                char('{'),
+                separated_list0(char(','), ws(project_field)),
+                char('}'),
+            ),
+            Filter::Project,
+        ),
+    ))(i)
+}
+
+fn bracket(i: &str) -> PResult<'_, Filter> {
+    delimited(
+        char('['),
+        ws(alt((
+            value(Filter::Children, char('*')),
+            map(int_lit, |i| Filter::Key(PathKey::Int(i))),
+            map(string_lit, |s| Filter::Key(PathKey::from(s))),
+            value(Filter::Children, multispace0),
+        ))),
+        char(']'),
+    )(i)
+}
+
+fn project_field(i: &str) -> PResult<'_, (String, Vec<Filter>)> {
+    alt((
+        separated_pair(
+            alt((map(ident, str::to_string), string_lit)),
+            ws(char(':')),
+            pipeline,
+        ),
+        map(ident, |name| {
+            let key = Filter::Key(PathKey::from(name.to_string()));
+            (name.to_string(), vec![key])
+        }),
+    ))(i)
+}
+
+fn cond(i: &str) -> PResult<'_, Cond> {
+    map(separated_list1(ws(tag("or")), cond_and), |mut conds| {
+        if conds.len() == 1 {
+            conds.pop().expect("Impossible: Empty conditions")
+        } else {
+            Cond::Or(conds)
+        }
+    })(i)
+}
+
+fn cond_and(i: &str) -> PResult<'_, Cond> {
+    map(separated_list1(ws(tag("and")), cond_atom), |mut conds| {
+        if conds.len() == 1 {
+            conds.pop().expect("Impossible: Empty conditions")
+        } else {
+            Cond::And(conds)
+        }
+    })(i)
+}
+
+fn cond_atom(i: &str) -> PResult<'_, Cond> {
+    ws(alt((
+        map(preceded(tag("not"), cond_atom), |c| Cond::Not(Box::new(c))),
+        delimited(char('('), cond, char(')')),
+        map(
+            tuple((property_name, ws(cmp_op), literal)),
+            |(p, op, lit)| Cond::Compare(p, op, lit),
+        ),
+    )))(i)
+}
+
+fn property_name(i: &str) -> PResult<'_, Property> {
+    alt((
+        value(Property::Type, tag("type")),
+        value(Property::Class, tag("class")),
+        value(Property::Key, tag("key")),
+        value(Property::Value, tag("value")),
+        value(Property::Len, tag("len")),
+    ))(i)
+}
+
+fn cmp_op(i: &str) -> PResult<'_, CmpOp> {
+    alt((
+        value(CmpOp::Eq, tag("==")),
+        value(CmpOp::Ne, tag("!=")),
+        value(CmpOp::Glob, tag("=~")),
+        value(CmpOp::Le, tag("<=")),
+        value(CmpOp::Ge, tag(">=")),
+        value(CmpOp::Lt, tag("<")),
+        value(CmpOp::Gt, tag(">")),
+    ))(i)
+}
+
+fn literal(i: &str) -> PResult<'_, Literal> {
+    alt((
+        map(string_lit, Literal::Str),
+        value(Literal::Null, tag("null")),
+        value(Literal::Bool(true), tag("true")),
+        value(Literal::Bool(false), tag("false")),
+        map(int_lit, Literal::Int),
+        map_res(recognize_float, |s: &str| s.parse().map(Literal::Float)),
+    ))(i)
+}
+
+fn ident(i: &str) -> PResult<'_, &str> {
+    recognize(pair(
+        alt((alpha1, tag("_"))),
+        many0_count(alt((alphanumeric1, tag("_")))),
+    ))(i)
+}
+
+/// An int that isn't the start of a float.
+fn int_lit(i: &str) -> PResult<'_, i64> {
+    map_res(recognize_float, str::parse)(i)
+}
+
+fn string_lit(i: &str) -> PResult<'_, String> {
+    map(
+        delimited(
+            char('"'),
+            opt(escaped_transform(
+                is_not("\\\""),
+                '\\',
+                alt((
+                    value("\\", char('\\')),
+                    value("\"", char('"')),
+                    value("\n", char('n')),
+                    value("\t", char('t')),
+                )),
+            )),
+            char('"'),
+        ),
+        Option::unwrap_or_default,
+    )(i)
+}
@@ -0,0 +1,662 @@
+use std::fmt::{self, Write};
+
+use crate::{
+    array::{ArrayData, PyArray},
+    collection::{Collection, CollectionKind},
+    object::PyObject,
+    ops::PickleOp,
+    stdlib::{Date, StdValue, Time, TimeDelta},
+    value::*,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Options for showing values in Python syntax. See `Value::repr`.
+pub struct ReprOptions {
+    /// Try to keep lines at most this long by splitting lists, dicts
+    /// and calls over multiple lines. `None` puts everything on one line.
+    pub width: Option<usize>,
+
+    /// Spaces to indent by for each level when things get split up.
+    pub indent: usize,
+
+    /// Show at most this many items of a list, dict or call. The rest get
+    /// replaced with `...`.
+    pub max_items: Option<usize>,
+
+    /// Show at most this many characters of a string or bytes of a bytes
+    /// value. Anything cut off gets a `...` after the closing quote.
+    pub max_len: Option<usize>,
+}
+
+impl Default for ReprOptions {
+    fn default() -> Self {
+        Self {
+            width: Some(100),
+            indent: 4,
+            max_items: Some(32),
+            max_len: Some(128),
+        }
+    }
+}
+
+impl ReprOptions {
+    /// Everything on one line and nothing left out. This is what you get
+    /// with `{}`, while `{:#}` uses the defaults.
+    pub fn exact() -> Self {
+        Self {
+            width: None,
+            indent: 0,
+            max_items: None,
+            max_len: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A value that displays in Python syntax. You get these from `Value::repr`.
+pub struct Repr<'v, 'a> {
+    value: &'v Value<'a>,
+    options: ReprOptions,
+}
+
+impl<'a> Value<'a> {
+    /// Display this value like Python's `repr` would, so `{'a': (1, 2.0)}`
+    /// instead of a pile of `Seq(Tuple, ...)`. Things that aren't literals
+    /// show up as calls like `torch._utils._rebuild_tensor_v2(...)`, the
+    /// state from `BUILD` as `.__setstate__(...)` and persistent IDs as
+    /// `persistent_load(...)`.
+    ///
+    /// Memo references show up as `memo[id]`, see [`ValueView`](crate::ValueView).
+    pub fn repr(&self, options: ReprOptions) -> Repr<'_, 'a> {
+        Repr {
+            value: self,
+            options,
+        }
+    }
+}
+
+impl fmt::Display for Repr<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let doc = DocBuilder(&self.options).value(self.value);
+        Layout {
+            out: f,
+            width: self.options.width.unwrap_or(usize::MAX),
+            indent: self.options.indent,
+            col: 0,
+        }
+        .write(&doc, 0, self.options.width.is_none())
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    /// Python syntax, see `Value::repr`. The alternate form (`{:#}`) uses
+    /// the default `ReprOptions`, otherwise it's `ReprOptions::exact`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let options = if f.alternate() {
+            ReprOptions::default()
+        } else {
+            ReprOptions::exact()
+        };
+        self.repr(options).fmt(f)
+    }
+}
+
+/// Something that can be laid out either on one line or split up.
+enum Doc {
+    Text(String),
+
+    /// Docs next to each other.
+    Cat(Vec<Doc>),
+
+    /// Comma separated items in brackets. `width` is the width when it's
+    /// all on one line.
+    Group {
+        open: &'static str,
+        items: Vec<Doc>,
+        close: &'static str,
+        tuple: bool,
+        width: usize,
+    },
+}
+
+impl Doc {
+    fn text(s: impl Into<String>) -> Self {
+        Self::Text(s.into())
+    }
+
+    fn group(open: &'static str, items: Vec<Doc>, close: &'static str, tuple: bool) -> Self {
+        let width = open.len()
+            + close.len()
+            + items.iter().map(Doc::width).sum::<usize>()
+            + items.len().saturating_sub(1) * 2
+            + usize::from(tuple && items.len() == 1);
+        Self::Group {
+            open,
+            items,
+            close,
+            tuple,
+            width,
+        }
+    }
+
+    /// Width when it's all on one line.
+    fn width(&self) -> usize {
+        match self {
+            Self::Text(s) => s.chars().count(),
+            Self::Cat(docs) => docs.iter().map(Doc::width).sum(),
+            Self::Group { width, .. } => *width,
+        }
+    }
+
+    fn take_children(&mut self) -> Vec<Doc> {
+        match self {
+            Self::Text(_) => vec![],
+            Self::Cat(docs) | Self::Group { items: docs, .. } => std::mem::take(docs),
+        }
+    }
+}
+
+// Docs are as deep as the value, which is too deep to drop recursively.
+impl Drop for Doc {
+    fn drop(&mut self) {
+        let mut todo = self.take_children();
+        while let Some(mut doc) = todo.pop() {
+            todo.extend(doc.take_children());
+        }
+    }
+}
+
+struct DocBuilder<'o>(&'o ReprOptions);
+
+impl DocBuilder<'_> {
+    fn value(&self, val: &Value<'_>) -> Doc {
+        deeper(|| self.value_doc(val))
+    }
+
+    fn value_doc(&self, val: &Value<'_>) -> Doc {
+        if let Some(std) = val.as_std() {
+            return self.std(&std);
+        }
+        match val {
+            Value::None => Doc::text("None"),
+            Value::Bool(true) => Doc::text("True"),
+            Value::Bool(false) => Doc::text("False"),
+            Value::Int(i) => Doc::text(i.to_string()),
+            Value::BigInt(i) => Doc::text(i.to_string()),
+            Value::Float(f) => Doc::text(float_repr(*f)),
+            Value::Complex(re, im) => Doc::Text(complex_repr(*re, *im)),
+            Value::String(s) => Doc::Text(self.str_repr(s)),
+            Value::Bytes(b) => Doc::Text(self.bytes_repr(b)),
+            Value::ByteArray(b) => Doc::Text(format!("bytearray({})", self.bytes_repr(b))),
+            Value::Seq(SequenceType::List, items) => self.items("[", items, "]", false),
+            Value::Seq(SequenceType::Tuple, items) => self.items("(", items, ")", true),
+            Value::Seq(SequenceType::Set, items) if items.is_empty() => Doc::text("set()"),
+            Value::Seq(SequenceType::Set, items) => self.items("{", items, "}", false),
+            Value::Seq(SequenceType::FrozenSet, items) if items.is_empty() => {
+                Doc::text("frozenset()")
+            }
+            Value::Seq(SequenceType::FrozenSet, items) => {
+                self.items("frozenset({", items, "})", false)
+            }
+            Value::Dict(items) => self.dict(items),
+            Value::Class { module, name } => Doc::Text(format!("{module}.{name}")),
+            Value::Global(target, args)
+            | Value::Object(target, args)
+            | Value::App(target, args) => {
+                let args = match args.split_first() {
+                    Some((Value::Seq(SequenceType::Tuple, targs), rest)) => {
+                        self.truncated(targs.iter().chain(rest), targs.len() + rest.len())
+                    }
+                    _ => self.truncated(args.iter(), args.len()),
+                };
+                Doc::Cat(vec![self.value(target), Doc::group("(", args, ")", false)])
+            }
+            Value::Build(target, state) => {
+                let target = self.value(target);
+                match state.as_ref() {
+                    Value::None => target,
+                    Value::Dict(items) if items.is_empty() => target,
+                    state => method(target, "__setstate__", vec![self.value(state)]),
+                }
+            }
+            Value::PyObject(obj) => self.object(obj),
+            Value::Collection(coll) => self.collection(coll),
+            Value::Enum {
+                class,
+                name: Some(name),
+                ..
+            } => Doc::Cat(vec![self.value(class), Doc::Text(format!(".{name}"))]),
+            Value::Enum { class, value, .. } => Doc::Cat(vec![
+                self.value(class),
+                Doc::group("(", vec![self.value(value)], ")", false),
+            ]),
+            Value::Array(array) => self.array(array),
+            Value::PersId(pid) => call("persistent_load", vec![self.value(pid)]),
+            Value::Ref(mid) => Doc::Text(format!("memo[{mid}]")),
+            Value::Slice(start, stop, step) => call("slice", self.values([start, stop, step])),
+            Value::Range(start, stop, step) => call("range", self.values([start, stop, step])),
+            Value::Raw(op) => Doc::Text(raw_repr(op)),
+            Value::RawNum(op) => Doc::Text(raw_repr(op)),
+        }
+    }
+
+    /// Standard library types look like they would in Python.
+    fn std(&self, std: &StdValue<'_, '_>) -> Doc {
+        let int = |i: i64| Doc::Text(i.to_string());
+        let time = |time: &Time| {
+            let mut args = vec![int(time.hour.into()), int(time.minute.into())];
+            if time.second != 0 || time.microsecond != 0 {
+                args.push(int(time.second.into()));
+            }
+            if time.microsecond != 0 {
+                args.push(int(time.microsecond.into()));
+            }
+            args
+        };
+        let extras = |args: &mut Vec<Doc>, time: &Time, tzinfo: Option<&Value<'_>>| {
+            if time.fold {
+                args.push(Doc::text("fold=1"));
+            }
+            if let Some(tz) = tzinfo {
+                args.push(Doc::Cat(vec![Doc::text("tzinfo="), self.value(tz)]));
+            }
+        };
+        let timedelta = |td: &TimeDelta| {
+            let args = [
+                ("days", td.days),
+                ("seconds", td.seconds),
+                ("microseconds", td.microseconds),
+            ]
+            .into_iter()
+            .filter(|(_, n)| *n != 0)
+            .map(|(name, n)| Doc::Text(format!("{name}={n}")))
+            .collect::<Vec<_>>();
+            match args.is_empty() {
+                true => Doc::text("datetime.timedelta(0)"),
+                false => call("datetime.timedelta", args),
+            }
+        };
+        let date = |date: &Date| {
+            vec![
+                int(date.year.into()),
+                int(date.month.into()),
+                int(date.day.into()),
+            ]
+        };
+        match std {
+            StdValue::DateTime {
+                date: d,
+                time: t,
+                tzinfo,
+            } => {
+                let mut args = date(d);
+                args.extend(time(t));
+                extras(&mut args, t, *tzinfo);
+                call("datetime.datetime", args)
+            }
+            StdValue::Date(d) => call("datetime.date", date(d)),
+            StdValue::Time { time: t, tzinfo } => {
+                let mut args = time(t);
+                extras(&mut args, t, *tzinfo);
+                call("datetime.time", args)
+            }
+            StdValue::TimeDelta(td) => timedelta(td),
+            StdValue::TimeZone { offset, name: None } if *offset == TimeDelta::default() => {
+                Doc::text("datetime.timezone.utc")
+            }
+            StdValue::TimeZone { offset, name } => {
+                let mut args = vec![timedelta(offset)];
+                args.extend(name.map(|name| Doc::Text(self.str_repr(name))));
+                call("datetime.timezone", args)
+            }
+            StdValue::Decimal(text) => {
+                call("decimal.Decimal", vec![Doc::Text(self.str_repr(text))])
+            }
+            StdValue::Uuid(uuid) => {
+                let hex = format!("{uuid:032x}");
+                let text = [
+                    &hex[..8],
+                    &hex[8..12],
+                    &hex[12..16],
+                    &hex[16..20],
+                    &hex[20..],
+                ]
+                .join("-");
+                call("uuid.UUID", vec![Doc::Text(self.str_repr(&text))])
+            }
+            StdValue::Fraction(num, den) => call(
+                "fractions.Fraction",
+                vec![Doc::Text(num.to_string()), Doc::Text(den.to_string())],
+            ),
+            StdValue::Path { class, path } => call(
+                &format!("pathlib.{class}"),
+                vec![Doc::Text(self.str_repr(path))],
+            ),
+        }
+    }
+
+    /// Collections look like they would in Python, except that namedtuples
+    /// don't know the names of their fields.
+    fn collection(&self, coll: &Collection<'_>) -> Doc {
+        let name = match coll.kind {
+            CollectionKind::OrderedDict => "collections.OrderedDict",
+            CollectionKind::DefaultDict => "collections.defaultdict",
+            CollectionKind::Counter => "collections.Counter",
+            CollectionKind::Deque => "collections.deque",
+            CollectionKind::NamedTuple => {
+                let items = match &coll.value {
+                    Value::Seq(SequenceType::Tuple, items) => {
+                        self.truncated(items.iter(), items.len())
+                    }
+                    value => vec![self.value(value)],
+                };
+                return Doc::Cat(vec![
+                    self.value(&coll.class),
+                    Doc::group("(", items, ")", false),
+                ]);
+            }
+        };
+        let mut args = vec![];
+        if coll.kind == CollectionKind::DefaultDict {
+            args.push(self.value(&coll.extra));
+        }
+        // Like Python, empty dicts get left out when they can be.
+        let empty = matches!(&coll.value, Value::Dict(items) if items.is_empty());
+        if !empty || coll.kind == CollectionKind::DefaultDict {
+            args.push(self.value(&coll.value));
+        }
+        if let (CollectionKind::Deque, Value::Int(maxlen)) = (coll.kind, &coll.extra) {
+            args.push(Doc::Text(format!("maxlen={maxlen}")));
+        }
+        call(name, args)
+    }
+
+    fn array(&self, array: &PyArray) -> Doc {
+        let mut args = vec![Doc::Text(self.str_repr(&array.typecode.to_string()))];
+        // Like Python, empty arrays just get the typecode.
+        match &array.data {
+            _ if array.is_empty() => (),
+            ArrayData::Unicode(s) => args.push(Doc::Text(self.str_repr(s))),
+            _ => args.push(self.items("[", &array.to_values(), "]", false)),
+        }
+        call("array.array", args)
+    }
+
+    fn values(&self, vals: [&Value<'_>; 3]) -> Vec<Doc> {
+        vals.into_iter().map(|v| self.value(v)).collect()
+    }
+
+    fn items(
+        &self,
+        open: &'static str,
+        items: &[Value<'_>],
+        close: &'static str,
+        tuple: bool,
+    ) -> Doc {
+        Doc::group(
+            open,
+            self.truncated(items.iter(), items.len()),
+            close,
+            tuple,
+        )
+    }
+
+    /// Docs for the items, up to `max_items` of them.
+    fn truncated<'v, 'a: 'v>(
+        &self,
+        items: impl Iterator<Item = &'v Value<'a>>,
+        len: usize,
+    ) -> Vec<Doc> {
+        let max = self.0.max_items.unwrap_or(usize::MAX);
+        let mut docs = items.take(max).map(|v| self.value(v)).collect::<Vec<_>>();
+        if len > max {
+            docs.push(Doc::text("..."));
+        }
+        docs
+    }
+
+    fn dict(&self, items: &[(Value<'_>, Value<'_>)]) -> Doc {
+        let max = self.0.max_items.unwrap_or(usize::MAX);
+        let mut docs = items
+            .iter()
+            .take(max)
+            .map(|(k, v)| Doc::Cat(vec![self.value(k), Doc::text(": "), self.value(v)]))
+            .collect::<Vec<_>>();
+        if items.len() > max {
+            docs.push(Doc::text("..."));
+        }
+        Doc::group("{", docs, "}", false)
+    }
+
+    /// `cls(*args, **kwargs)`, then whatever got done to it afterwards.
+    fn object(&self, obj: &PyObject<'_>) -> Doc {
+        let mut args = self.truncated(obj.args.iter(), obj.args.len());
+        args.extend(obj.kwargs.iter().map(|(k, v)| match k {
+            Value::String(k) => Doc::Cat(vec![Doc::Text(format!("{k}=")), self.value(v)]),
+            k => Doc::Cat(vec![
+                Doc::text("**{"),
+                self.value(k),
+                Doc::text(": "),
+                self.value(v),
+                Doc::text("}"),
+            ]),
+        }));
+        let mut doc = Doc::Cat(vec![
+            self.value(&obj.class),
+            Doc::group("(", args, ")", false),
+        ]);
+        match (&obj.state, &obj.slotstate) {
+            (None, None) => (),
+            (Some(state), None) => doc = method(doc, "__setstate__", vec![self.value(state)]),
+            (state, Some(slotstate)) => {
+                let state = state
+                    .as_ref()
+                    .map_or_else(|| Doc::text("None"), |st| self.value(st));
+                let both = Doc::group("(", vec![state, self.value(slotstate)], ")", true);
+                doc = method(doc, "__setstate__", vec![both]);
+            }
+        }
+        if !obj.list_items.is_empty() {
+            let items = self.items("[", &obj.list_items, "]", false);
+            doc = method(doc, "extend", vec![items]);
+        }
+        if !obj.dict_items.is_empty() {
+            doc = method(doc, "update", vec![self.dict(&obj.dict_items)]);
+        }
+        doc
+    }
+
+    fn str_repr(&self, s: &str) -> String {
+        let max = self.0.max_len.unwrap_or(usize::MAX);
+        let quote = if s.contains('\'') && !s.contains('"') {
+            '"'
+        } else {
+            '\''
+        };
+        let mut out = String::with_capacity(s.len().min(max) + 2);
+        out.push(quote);
+        for c in s.chars().take(max) {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if c == quote => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                c if c.is_control() && u32::from(c) <= 0xff => {
+                    let _ = write!(out, "\\x{:02x}", u32::from(c));
+                }
+                c if c.is_control() => {
+                    let _ = write!(out, "\\u{:04x}", u32::from(c));
+                }
+                c => out.push(c),
+            }
+        }
+        out.push(quote);
+        if s.chars().nth(max).is_some() {
+            out.push_str("...");
+        }
+        out
+    }
+
+    fn bytes_repr(&self, b: &[u8]) -> String {
+        let max = self.0.max_len.unwrap_or(usize::MAX);
+        let quote = if b.contains(&b'\'') && !b.contains(&b'"') {
+            b'"'
+        } else {
+            b'\''
+        };
+        let mut out = String::with_capacity(b.len().min(max) + 3);
+        out.push('b');
+        out.push(char::from(quote));
+        for &c in b.iter().take(max) {
+            match c {
+                b'\\' => out.push_str("\\\\"),
+                b'\n' => out.push_str("\\n"),
+                b'\r' => out.push_str("\\r"),
+                b'\t' => out.push_str("\\t"),
+                c if c == quote => {
+                    out.push('\\');
+                    out.push(char::from(c));
+                }
+                b' '..=b'~' => out.push(char::from(c)),
+                c => {
+                    let _ = write!(out, "\\x{c:02x}");
+                }
+            }
+        }
+        out.push(char::from(quote));
+        if b.len() > max {
+            out.push_str("...");
+        }
+        out
+    }
+}
+
+fn call(name: &str, args: Vec<Doc>) -> Doc {
+    Doc::Cat(vec![Doc::text(name), Doc::group("(", args, ")", false)])
+}
+
+fn method(target: Doc, name: &str, args: Vec<Doc>) -> Doc {
+    Doc::Cat(vec![
+        target,
+        Doc::Text(format!(".{name}")),
+        Doc::group("(", args, ")", false),
+    ])
+}
+
+/// Rust and Python switch to scientific notation at the same points, but
+/// Python always has a sign and at least two digits in the exponent.
+fn float_repr(f: f64) -> String {
+    if f.is_nan() {
+        return "nan".to_string();
+    } else if f.is_infinite() {
+        return if f > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    let s = format!("{f:?}");
+    match s.split_once('e') {
+        Some((mantissa, exp)) => {
+            let (sign, digits) = exp.strip_prefix('-').map_or(("+", exp), |d| ("-", d));
+            format!("{mantissa}e{sign}{digits:0>2}")
+        }
+        None => s,
+    }
+}
+
+/// Python leaves off the `.0` for the parts of complex numbers and doesn't
+/// show a real part that's positive zero.
+fn complex_repr(re: f64, im: f64) -> String {
+    let part = |f: f64| {
+        let s = float_repr(f);
+        s.strip_suffix(".0")
+            .map_or_else(|| s.clone(), str::to_string)
+    };
+    if re == 0.0 && re.is_sign_positive() {
+        format!("{}j", part(im))
+    } else if im.is_sign_negative() && !im.is_nan() {
+        format!("({}{}j)", part(re), part(im))
+    } else {
+        format!("({}+{}j)", part(re), part(im))
+    }
+}
+
+/// `STRING` and text numbers are already Python syntax, anything else
+/// is just the operation in angle brackets.
+fn raw_repr(op: &PickleOp<'_>) -> String {
+    match op {
+        PickleOp::STRING(s) | PickleOp::INT(s) | PickleOp::LONG(s) | PickleOp::FLOAT(s) => {
+            s.to_string()
+        }
+        op => format!("<{op:?}>"),
+    }
+}
+
+struct Layout<'w, W> {
+    out: &'w mut W,
+    width: usize,
+    indent: usize,
+    col: usize,
+}
+
+impl<W: Write> Layout<'_, W> {
+    fn text(&mut self, s: &str) -> fmt::Result {
+        self.col += s.chars().count();
+        self.out.write_str(s)
+    }
+
+    fn newline(&mut self, level: usize) -> fmt::Result {
+        self.col = level * self.indent;
+        write!(self.out, "\n{:1$}", "", self.col)
+    }
+
+    /// Groups get split up if they don't fit in the rest of the line (along
+    /// with a trailing comma), unless `flat` is set.
+    fn write(&mut self, doc: &Doc, level: usize, flat: bool) -> fmt::Result {
+        deeper(|| self.write_doc(doc, level, flat))
+    }
+
+    fn write_doc(&mut self, doc: &Doc, level: usize, flat: bool) -> fmt::Result {
+        match doc {
+            Doc::Text(s) => self.text(s),
+            Doc::Cat(docs) => docs.iter().try_for_each(|d| self.write(d, level, flat)),
+            Doc::Group {
+                open,
+                items,
+                close,
+                tuple,
+                width,
+            } => {
+                let flat = flat || items.is_empty() || self.col + width < self.width;
+                // Lists of numbers and such get packed in as many per line
+                // as will fit.
+                let fill = !flat && items.iter().all(|item| matches!(item, Doc::Text(_)));
+                self.text(open)?;
+                for (idx, item) in items.iter().enumerate() {
+                    if flat {
+                        if idx > 0 {
+                            self.text(", ")?;
+                        }
+                        self.write(item, level, true)?;
+                    } else {
+                        if fill && idx > 0 && self.col + item.width() + 2 <= self.width {
+                            self.text(" ")?;
+                        } else {
+                            self.newline(level + 1)?;
+                        }
+                        self.write(item, level + 1, false)?;
+                        self.text(",")?;
+                    }
+                }
+                if flat && *tuple && items.len() == 1 {
+                    self.text(",")?;
+                }
+                if !flat {
+                    self.newline(level)?;
+                }
+                self.text(close)
+            }
+        }
+    }
+}
@@ -0,0 +1,630 @@
+//! The mapping is meant to be readable by things that only know JSON (so you
+//! can look at a pickle with `jq` or in a web page) while still containing
+//! enough information to get the exact same `Value` back. Deserializing a
+//! `Value` from the output of serializing one is lossless, apart from raw
+//! operations that stand for a simple value (see below).
+//!
+//! `None`, `bool`, `int`, `float`, `str` and lists map to the obvious JSON
+//! types. Dicts become JSON objects as long as every key is a string, no
+//! key is repeated and no key starts with `$`. Everything else is an object
+//! with a single key that starts with `$` and tells you what it is:
+//!
+//! | Value | JSON |
+//! |-------|------|
+//! | `Float` (NaN or infinite) | `{"$float": "nan"}`, `"inf"` or `"-inf"` |
+//! | `Bytes` | `{"$bytes": "<base64>"}` |
+//! | `ByteArray` | `{"$bytearray": "<base64>"}` |
+//! | `BigInt` | `{"$bigint": "<decimal digits>"}` |
+//! | `Complex` | `{"$complex": [real, imag]}` |
+//! | `Seq(Tuple, ...)` | `{"$tuple": [...]}` |
+//! | `Seq(Set, ...)` | `{"$set": [...]}` |
+//! | `Seq(FrozenSet, ...)` | `{"$frozenset": [...]}` |
+//! | `Dict` (other keys) | `{"$dict": [[key, value], ...]}` |
+//! | `Slice` | `{"$slice": [start, stop, step]}` |
+//! | `Range` | `{"$range": [start, stop, step]}` |
+//! | `Class` | `{"$class": [module, name]}` |
+//! | `Global` | `{"$global": [target, [args...]]}` |
+//! | `App` | `{"$app": [target, [args...]]}` |
+//! | `Object` | `{"$object": [target, [args...]]}` |
+//! | `Build` | `{"$build": [target, state]}` |
+//! | `PersId` | `{"$persid": value}` |
+//! | `Ref` | `{"$ref": memo_id}` |
+//! | `PyObject` | `{"$pyobject": {"class": ..., ...}}` |
+//! | `Collection` | `{"$collection": [kind, class, value, extra]}` |
+//! | `Enum` | `{"$enum": [class, value, name]}` (`name` can be `null`) |
+//! | `Array` | `{"$array": [typecode, type, items]}` |
+//! | `Raw` | `{"$raw": op}` |
+//! | `RawNum` | `{"$rawnum": op}` |
+//!
+//! A `PyObject` is an object with the same fields as the struct. Only
+//! `class` is always there, the others are left out if they're empty.
+//! `kwargs` and `dict_items` are lists of `[key, value]` pairs. The kind of
+//! a `Collection` is a name like `"OrderedDict"` or `"deque"`, see
+//! `CollectionKind::name`. The type of an `Array` is a name like `"f32"`,
+//! see `ArrayData::name`, and the items of a `"unicode"` one are a string.
+//!
+//! A `Raw` op that stands for a simple value, like `BININT1` or `UNICODE`,
+//! is written as the value `fix_value` gives you for it, so you get that
+//! value back instead of the op. Other pickle operations use Serde's
+//! default representation for enums, so `MARK` is `"MARK"` and
+//! `FRAME(9)` is `{"FRAME": 9}`. Bytes are written the same way as a
+//! `Bytes` value. Operations hold borrowed data, so the strings in ones
+//! like `GLOBAL` or `PERSID` can only be read back if the deserializer
+//! can lend them out. For JSON, this means strings without escapes work.
+//! Evaluating doesn't leave ops like those in its output.
+
+use std::{borrow::Cow, collections::HashSet, fmt, marker::PhantomData, str::FromStr};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{
+    de::{
+        self, Deserialize, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess,
+        VariantAccess, Visitor,
+    },
+    ser::{Serialize, SerializeMap, Serializer},
+};
+
+use crate::{
+    array::{ArrayData, PyArray},
+    collection::{Collection, CollectionKind},
+    compat::decode_unicode,
+    object::PyObject,
+    ops::PickleOp,
+    value::*,
+};
+
+impl Serialize for Value<'_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        deeper(|| match self {
+            Value::None => s.serialize_unit(),
+            Value::Bool(b) => s.serialize_bool(*b),
+            Value::Int(i) => s.serialize_i64(*i),
+            Value::Float(f) if f.is_finite() => s.serialize_f64(*f),
+            Value::Float(f) => tagged(s, "$float", nonfinite_name(*f)),
+            Value::String(st) => s.serialize_str(st),
+            Value::Seq(SequenceType::List, items) => s.collect_seq(items),
+            Value::Seq(SequenceType::Tuple, items) => tagged(s, "$tuple", items),
+            Value::Seq(SequenceType::Set, items) => tagged(s, "$set", items),
+            Value::Seq(SequenceType::FrozenSet, items) => tagged(s, "$frozenset", items),
+            Value::Dict(items) if has_plain_keys(items) => {
+                s.collect_map(items.iter().map(|(k, v)| (k, v)))
+            }
+            Value::Dict(items) => tagged(s, "$dict", items),
+            Value::Bytes(b) => tagged(s, "$bytes", &BASE64.encode(b)),
+            Value::ByteArray(b) => tagged(s, "$bytearray", &BASE64.encode(b)),
+            Value::BigInt(i) => tagged(s, "$bigint", &i.to_string()),
+            Value::Complex(re, im) => {
+                tagged(s, "$complex", &(Value::Float(*re), Value::Float(*im)))
+            }
+            Value::Slice(start, stop, step) => tagged(s, "$slice", &(start, stop, step)),
+            Value::Range(start, stop, step) => tagged(s, "$range", &(start, stop, step)),
+            Value::Class { module, name } => tagged(s, "$class", &(module, name)),
+            Value::Global(target, args) => tagged(s, "$global", &(target, args)),
+            Value::App(target, args) => tagged(s, "$app", &(target, args)),
+            Value::Object(target, args) => tagged(s, "$object", &(target, args)),
+            Value::Build(target, state) => tagged(s, "$build", &(target, state)),
+            Value::PersId(pid) => tagged(s, "$persid", pid),
+            Value::Ref(mid) => tagged(s, "$ref", mid),
+            Value::PyObject(obj) => tagged(s, "$pyobject", obj),
+            Value::Collection(coll) => tagged(
+                s,
+                "$collection",
+                &(coll.kind.name(), &coll.class, &coll.value, &coll.extra),
+            ),
+            Value::Enum { class, value, name } => tagged(s, "$enum", &(class, value, name)),
+            Value::Array(array) => match &array.data {
+                ArrayData::Unicode(st) => tagged(s, "$array", &(array.typecode, "unicode", st)),
+                data => tagged(
+                    s,
+                    "$array",
+                    &(array.typecode, data.name(), array.to_values()),
+                ),
+            },
+            // Ops that stand for a simple value are written as that value.
+            Value::Raw(op) => match fix_value(Value::Raw(Cow::Borrowed(op.as_ref()))) {
+                Ok(Value::Raw(_)) | Err(_) => tagged(s, "$raw", &RawOp(op)),
+                Ok(val) => val.serialize(s),
+            },
+            Value::RawNum(op) => tagged(s, "$rawnum", op),
+        })
+    }
+}
+
+impl Serialize for PyObject<'_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut map = s.serialize_map(None)?;
+        map.serialize_entry("class", &self.class)?;
+        if !self.args.is_empty() {
+            map.serialize_entry("args", &self.args)?;
+        }
+        if !self.kwargs.is_empty() {
+            map.serialize_entry("kwargs", &self.kwargs)?;
+        }
+        if let Some(state) = &self.state {
+            map.serialize_entry("state", state)?;
+        }
+        if let Some(slotstate) = &self.slotstate {
+            map.serialize_entry("slotstate", slotstate)?;
+        }
+        if !self.list_items.is_empty() {
+            map.serialize_entry("list_items", &self.list_items)?;
+        }
+        if !self.dict_items.is_empty() {
+            map.serialize_entry("dict_items", &self.dict_items)?;
+        }
+        map.end()
+    }
+}
+
+/// A `$raw` op, with bytes and floats JSON can't hold tagged like they
+/// would be in a value.
+struct RawOp<'o, 'a>(&'o PickleOp<'a>);
+
+impl Serialize for RawOp<'_, '_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match (self.0, op_bytes(self.0)) {
+            (_, Some((name, b))) => tagged(s, name, &Value::Bytes(Cow::Borrowed(b))),
+            (PickleOp::BINFLOAT(f), _) if !f.is_finite() => {
+                tagged(s, "BINFLOAT", &Value::Float(*f))
+            }
+            (op, _) => op.serialize(s),
+        }
+    }
+}
+
+/// The name and bytes of an op that holds bytes.
+fn op_bytes<'o>(op: &'o PickleOp<'_>) -> Option<(&'static str, &'o [u8])> {
+    Some(match op {
+        PickleOp::BINSTRING(b) => ("BINSTRING", b),
+        PickleOp::SHORT_BINSTRING(b) => ("SHORT_BINSTRING", b),
+        PickleOp::UNICODE(b) => ("UNICODE", b),
+        PickleOp::LONG1(b) => ("LONG1", b),
+        PickleOp::LONG4(b) => ("LONG4", b),
+        PickleOp::BINBYTES(b) => ("BINBYTES", b),
+        PickleOp::SHORT_BINBYTES(b) => ("SHORT_BINBYTES", b),
+        PickleOp::BINBYTES8(b) => ("BINBYTES8", b),
+        PickleOp::BYTEARRAY8(b) => ("BYTEARRAY8", b),
+        _ => return None,
+    })
+}
+
+/// A single entry map like `{"$tag": val}`.
+fn tagged<S: Serializer, T: Serialize + ?Sized>(
+    s: S,
+    tag: &'static str,
+    val: &T,
+) -> Result<S::Ok, S::Error> {
+    let mut map = s.serialize_map(Some(1))?;
+    map.serialize_entry(tag, val)?;
+    map.end()
+}
+
+fn nonfinite_name(f: f64) -> &'static str {
+    if f.is_nan() {
+        "nan"
+    } else if f > 0.0 {
+        "inf"
+    } else {
+        "-inf"
+    }
+}
+
+/// Whether a dict can be written as a plain JSON object.
+fn has_plain_keys(items: &[(Value<'_>, Value<'_>)]) -> bool {
+    let mut seen = HashSet::with_capacity(items.len());
+    items.iter().all(
+        |(k, _)| matches!(k, Value::String(s) if !s.starts_with('$') && seen.insert(s.as_ref())),
+    )
+}
+
+/// A string that gets borrowed from the input when possible.
+struct Str<'a>(Cow<'a, str>);
+
+impl<'de: 'a, 'a> Deserialize<'de> for Str<'a> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct StrVisitor<'a>(PhantomData<&'a ()>);
+
+        impl<'de: 'a, 'a> Visitor<'de> for StrVisitor<'a> {
+            type Value = Str<'a>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+                Ok(Str(Cow::Borrowed(v)))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(Str(Cow::Owned(v.to_owned())))
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+                Ok(Str(Cow::Owned(v)))
+            }
+        }
+
+        d.deserialize_str(StrVisitor(PhantomData))
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Value<'a> {
+    /// Read a `Value` back from the format described in the module docs.
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_any(ValueVisitor(PhantomData))
+    }
+}
+
+struct ValueVisitor<'a>(PhantomData<Value<'a>>);
+
+impl<'de: 'a, 'a> Visitor<'de> for ValueVisitor<'a> {
+    type Value = Value<'a>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a serialized pickle value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Value::None)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(Value::None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        Value::deserialize(d)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Value::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(i64::try_from(v).map_or_else(|_| Value::BigInt(v.into()), Value::Int))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<Self::Value, E> {
+        Ok(i64::try_from(v).map_or_else(|_| Value::BigInt(v.into()), Value::Int))
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<Self::Value, E> {
+        Ok(i64::try_from(v).map_or_else(|_| Value::BigInt(v.into()), Value::Int))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        Ok(Value::String(Cow::Borrowed(v)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Value::String(Cow::Owned(v.to_owned())))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Value::String(Cow::Owned(v)))
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+        Ok(Value::Bytes(Cow::Borrowed(v)))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(Value::Bytes(Cow::Owned(v.to_owned())))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(Value::Bytes(Cow::Owned(v)))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::Seq(SequenceType::List, items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let Some(Str(key)) = map.next_key::<Str<'a>>()? else {
+            return Ok(Value::Dict(vec![]));
+        };
+        if key.starts_with('$') {
+            let val = from_tagged(&key, &mut map)?;
+            if map.next_key::<de::IgnoredAny>()?.is_some() {
+                return Err(de::Error::custom(format!("Unexpected key after {key}")));
+            }
+            return Ok(val);
+        }
+        let mut items = Vec::with_capacity(map.size_hint().unwrap_or(0) + 1);
+        items.push((Value::String(key), map.next_value()?));
+        while let Some((Str(key), val)) = map.next_entry::<Str<'a>, Value<'a>>()? {
+            items.push((Value::String(key), val));
+        }
+        Ok(Value::Dict(items))
+    }
+}
+
+/// Read the value for a `$tag` key.
+fn from_tagged<'de: 'a, 'a, A: MapAccess<'de>>(
+    tag: &str,
+    map: &mut A,
+) -> Result<Value<'a>, A::Error> {
+    use de::Error;
+    Ok(match tag {
+        "$float" => Value::Float(parse_str(tag, map.next_value()?)?),
+        "$bytes" | "$bytearray" => {
+            let Str(b64) = map.next_value()?;
+            let b = Cow::Owned(BASE64.decode(b64.as_ref()).map_err(A::Error::custom)?);
+            match tag {
+                "$bytes" => Value::Bytes(b),
+                _ => Value::ByteArray(b),
+            }
+        }
+        "$bigint" => Value::BigInt(parse_str(tag, map.next_value()?)?),
+        "$complex" => match map.next_value()? {
+            (Value::Float(re), Value::Float(im)) => Value::Complex(re, im),
+            _ => return Err(A::Error::custom("Bad value for $complex")),
+        },
+        "$tuple" => Value::Seq(SequenceType::Tuple, map.next_value()?),
+        "$set" => Value::Seq(SequenceType::Set, map.next_value()?),
+        "$frozenset" => Value::Seq(SequenceType::FrozenSet, map.next_value()?),
+        "$dict" => Value::Dict(map.next_value()?),
+        "$slice" => {
+            let (start, stop, step) = map.next_value()?;
+            Value::Slice(Box::new(start), Box::new(stop), Box::new(step))
+        }
+        "$range" => {
+            let (start, stop, step) = map.next_value()?;
+            Value::Range(Box::new(start), Box::new(stop), Box::new(step))
+        }
+        "$class" => {
+            let (Str(module), Str(name)) = map.next_value()?;
+            Value::Class { module, name }
+        }
+        "$global" => {
+            let (target, args) = map.next_value()?;
+            Value::Global(Box::new(target), args)
+        }
+        "$app" => {
+            let (target, args) = map.next_value()?;
+            Value::App(Box::new(target), args)
+        }
+        "$object" => {
+            let (target, args) = map.next_value()?;
+            Value::Object(Box::new(target), args)
+        }
+        "$build" => {
+            let (target, state) = map.next_value()?;
+            Value::Build(Box::new(target), Box::new(state))
+        }
+        "$persid" => Value::PersId(Box::new(map.next_value()?)),
+        "$ref" => Value::Ref(map.next_value()?),
+        "$pyobject" => Value::PyObject(Box::new(map.next_value()?)),
+        "$collection" => {
+            let (Str(kind), class, value, extra) = map.next_value()?;
+            let kind = match kind.as_ref() {
+                "OrderedDict" => CollectionKind::OrderedDict,
+                "defaultdict" => CollectionKind::DefaultDict,
+                "Counter" => CollectionKind::Counter,
+                "deque" => CollectionKind::Deque,
+                "namedtuple" => CollectionKind::NamedTuple,
+                _ => {
+                    return Err(A::Error::custom(format!(
+                        "Bad kind for $collection: {kind}"
+                    )))
+                }
+            };
+            Value::Collection(Box::new(Collection {
+                kind,
+                class,
+                value,
+                extra,
+            }))
+        }
+        "$enum" => {
+            let (class, value, name) = map.next_value::<(_, _, Option<Str<'_>>)>()?;
+            Value::Enum {
+                class: Box::new(class),
+                value: Box::new(value),
+                name: name.map(|Str(name)| name),
+            }
+        }
+        "$array" => {
+            let (typecode, Str(name), items) = map.next_value::<(char, _, Value<'_>)>()?;
+            let data = match &items {
+                Value::Seq(SequenceType::List, items) => ArrayData::from_values(&name, items),
+                items => ArrayData::from_values(&name, std::slice::from_ref(items)),
+            };
+            let data = data.ok_or_else(|| A::Error::custom(format!("Bad {name} $array")))?;
+            Value::Array(Box::new(PyArray { typecode, data }))
+        }
+        "$raw" => map.next_value::<FromRaw<'a>>()?.0,
+        "$rawnum" => Value::RawNum(map.next_value()?),
+        _ => return Err(A::Error::custom(format!("Unknown tag {tag}"))),
+    })
+}
+
+/// The value for a `$raw` op. Ops can only borrow their bytes, so ones
+/// with bytes come back as the value they stand for, like `fix_value`
+/// would give you.
+struct FromRaw<'a>(Value<'a>);
+
+impl<'de: 'a, 'a> Deserialize<'de> for FromRaw<'a> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_any(FromRawVisitor(PhantomData))
+    }
+}
+
+struct FromRawVisitor<'a>(PhantomData<Value<'a>>);
+
+impl<'de: 'a, 'a> Visitor<'de> for FromRawVisitor<'a> {
+    type Value = FromRaw<'a>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a pickle op")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        let op = PickleOp::deserialize(de::value::StrDeserializer::<E>::new(v))?;
+        Ok(FromRaw(Value::Raw(Cow::Owned(op))))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        use de::Error;
+        let Some(Str(name)) = map.next_key::<Str<'a>>()? else {
+            return Err(A::Error::custom("Missing op for $raw"));
+        };
+        let val = map.next_value_seed(RawArg(&name, PhantomData))?;
+        if map.next_key::<de::IgnoredAny>()?.is_some() {
+            return Err(A::Error::custom(format!("Unexpected key after {name}")));
+        }
+        Ok(FromRaw(val))
+    }
+}
+
+/// Reads the argument for the `$raw` op with the specified name.
+struct RawArg<'n, 'a>(&'n str, PhantomData<Value<'a>>);
+
+impl<'de: 'a, 'a> DeserializeSeed<'de> for RawArg<'_, 'a> {
+    type Value = Value<'a>;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        use de::Error;
+        let name = self.0;
+        let bytes = |d: D| match Value::deserialize(d)? {
+            Value::Bytes(b) => Ok(b.into_owned()),
+            _ => Err(D::Error::custom(format!("Bad value for {name}"))),
+        };
+        Ok(match name {
+            "BINFLOAT" => match Value::deserialize(d)? {
+                Value::Float(f) => Value::Raw(Cow::Owned(PickleOp::BINFLOAT(f))),
+                _ => return Err(D::Error::custom("Bad value for BINFLOAT")),
+            },
+            "BINSTRING" | "SHORT_BINSTRING" => String::from_utf8(bytes(d)?).map_or_else(
+                |e| Value::Bytes(Cow::Owned(e.into_bytes())),
+                |s| Value::String(Cow::Owned(s)),
+            ),
+            "UNICODE" => {
+                let b = bytes(d)?;
+                match decode_unicode(&b) {
+                    Ok(s) => Value::String(Cow::Owned(s.into_owned())),
+                    Err(_) => Value::Bytes(Cow::Owned(b)),
+                }
+            }
+            "LONG1" | "LONG4" => long_value(&bytes(d)?),
+            "BINBYTES" | "SHORT_BINBYTES" | "BINBYTES8" => Value::Bytes(Cow::Owned(bytes(d)?)),
+            "BYTEARRAY8" => Value::ByteArray(Cow::Owned(bytes(d)?)),
+            _ => Value::Raw(Cow::Owned(PickleOp::deserialize(OpVariant(name, d))?)),
+        })
+    }
+}
+
+/// An op we already know the name of, so the derived `Deserialize` for
+/// `PickleOp` can read the argument.
+struct OpVariant<'n, D>(&'n str, D);
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for OpVariant<'_, D> {
+    type Error = D::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        visitor.visit_enum(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de, D: Deserializer<'de>> EnumAccess<'de> for OpVariant<'_, D> {
+    type Error = D::Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), D::Error> {
+        let name = de::value::StrDeserializer::new(self.0);
+        Ok((seed.deserialize(name)?, self))
+    }
+}
+
+impl<'de, D: Deserializer<'de>> VariantAccess<'de> for OpVariant<'_, D> {
+    type Error = D::Error;
+
+    fn unit_variant(self) -> Result<(), D::Error> {
+        de::IgnoredAny::deserialize(self.1).map(|_| ())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, D::Error> {
+        seed.deserialize(self.1)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, D::Error> {
+        self.1.deserialize_tuple(len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, D::Error> {
+        self.1.deserialize_struct("", fields, visitor)
+    }
+}
+
+fn parse_str<T: FromStr, E: de::Error>(tag: &str, Str(s): Str<'_>) -> Result<T, E> {
+    s.parse()
+        .map_err(|_| E::custom(format!("Bad value for {tag}: {s}")))
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for PyObject<'a> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_map(PyObjectVisitor(PhantomData))
+    }
+}
+
+struct PyObjectVisitor<'a>(PhantomData<PyObject<'a>>);
+
+impl<'de: 'a, 'a> Visitor<'de> for PyObjectVisitor<'a> {
+    type Value = PyObject<'a>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a serialized Python object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut class = None;
+        let mut obj = PyObject::new(Value::None, vec![]);
+        while let Some(Str(key)) = map.next_key::<Str<'_>>()? {
+            match key.as_ref() {
+                "class" => class = Some(map.next_value()?),
+                "args" => obj.args = map.next_value()?,
+                "kwargs" => obj.kwargs = map.next_value()?,
+                "state" => obj.state = Some(map.next_value()?),
+                "slotstate" => obj.slotstate = Some(map.next_value()?),
+                "list_items" => obj.list_items = map.next_value()?,
+                "dict_items" => obj.dict_items = map.next_value()?,
+                _ => return Err(de::Error::unknown_field(&key, PYOBJECT_FIELDS)),
+            }
+        }
+        obj.class = class.ok_or_else(|| de::Error::missing_field("class"))?;
+        Ok(obj)
+    }
+}
+
+const PYOBJECT_FIELDS: &[&str] = &[
+    "class",
+    "args",
+    "kwargs",
+    "state",
+    "slotstate",
+    "list_items",
+    "dict_items",
+];
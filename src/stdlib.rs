@@ -0,0 +1,387 @@
+use std::{borrow::Cow, fmt};
+
+use num_bigint::BigInt;
+
+use crate::{collection::CollectionKind, value::*};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A `datetime.date`.
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The time part of a `datetime.datetime` or `datetime.time`.
+pub struct Time {
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub microsecond: u32,
+
+    /// Whether this is the second time this time happened, when clocks
+    /// go back.
+    pub fold: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A `datetime.timedelta`. Like Python, only `days` can be negative.
+pub struct TimeDelta {
+    pub days: i32,
+    pub seconds: i32,
+    pub microseconds: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A standard library type we recognized, from `Value::as_std`.
+pub enum StdValue<'v, 'a> {
+    /// A `datetime.datetime`. `tzinfo` is whatever the time zone got
+    /// pickled as, which is a `StdValue::TimeZone` for `datetime.timezone`.
+    DateTime {
+        date: Date,
+        time: Time,
+        tzinfo: Option<&'v Value<'a>>,
+    },
+
+    /// A `datetime.date`.
+    Date(Date),
+
+    /// A `datetime.time`.
+    Time {
+        time: Time,
+        tzinfo: Option<&'v Value<'a>>,
+    },
+
+    /// A `datetime.timedelta`.
+    TimeDelta(TimeDelta),
+
+    /// A `datetime.timezone`, which is a fixed offset from UTC.
+    TimeZone {
+        offset: TimeDelta,
+        name: Option<&'v str>,
+    },
+
+    /// A `decimal.Decimal`. You get the text, like `"1.50"` or `"-Infinity"`.
+    Decimal(&'v str),
+
+    /// A `uuid.UUID`.
+    Uuid(u128),
+
+    /// A `fractions.Fraction` as the numerator and denominator.
+    Fraction(BigInt, BigInt),
+
+    /// A `pathlib` path like `PosixPath`. `class` is the name of the class.
+    Path { class: &'v str, path: String },
+}
+
+impl<'a> Value<'a> {
+    /// Recognize standard library types, like `datetime.datetime` or
+    /// `decimal.Decimal`. This works with or without
+    /// `EvalOptions::object_model`, but references need to be resolved.
+    /// You get `None` if it's something else, or it's not pickled the
+    /// way we expected.
+    pub fn as_std(&self) -> Option<StdValue<'_, 'a>> {
+        let Parts { class, args, state } = parts(self)?;
+        Some(match (class, args) {
+            (("datetime", "datetime"), [state, rest @ ..]) if rest.len() <= 1 => {
+                let b = packed(state, 10)?;
+                let (date, time) = (
+                    Date::new(u16::from_be_bytes([b[0], b[1]]), b[2] & 0x7f, b[3])?,
+                    Time::new(b[4], b[5], b[6], be_u24(&b[7..]), b[2] & 0x80 != 0)?,
+                );
+                StdValue::DateTime {
+                    date,
+                    time,
+                    tzinfo: tzinfo(rest),
+                }
+            }
+            (("datetime", "datetime"), [year, month, day, rest @ ..]) if rest.len() <= 5 => {
+                let date = Date::new(int(year)?, int(month)?, int(day)?)?;
+                let (time, tzinfo) = time_args(rest)?;
+                StdValue::DateTime { date, time, tzinfo }
+            }
+            (("datetime", "date"), [state]) => {
+                let b = packed(state, 4)?;
+                StdValue::Date(Date::new(u16::from_be_bytes([b[0], b[1]]), b[2], b[3])?)
+            }
+            (("datetime", "date"), [year, month, day]) => {
+                StdValue::Date(Date::new(int(year)?, int(month)?, int(day)?)?)
+            }
+            (("datetime", "time"), [state, rest @ ..]) if rest.len() <= 1 => match packed(state, 6)
+            {
+                Some(b) => StdValue::Time {
+                    time: Time::new(b[0] & 0x7f, b[1], b[2], be_u24(&b[3..]), b[0] & 0x80 != 0)?,
+                    tzinfo: tzinfo(rest),
+                },
+                None => {
+                    let (time, tzinfo) = time_args(args)?;
+                    StdValue::Time { time, tzinfo }
+                }
+            },
+            (("datetime", "time"), args) => {
+                let (time, tzinfo) = time_args(args)?;
+                StdValue::Time { time, tzinfo }
+            }
+            (("datetime", "timedelta"), args) if args.len() <= 3 => {
+                StdValue::TimeDelta(timedelta(args)?)
+            }
+            (("datetime", "timezone"), [offset, rest @ ..]) if rest.len() <= 1 => {
+                let offset = match offset.as_std()? {
+                    StdValue::TimeDelta(td) => td,
+                    _ => return None,
+                };
+                let name = match rest {
+                    [name] => Some(name.as_str()?),
+                    _ => None,
+                };
+                StdValue::TimeZone { offset, name }
+            }
+            (("decimal" | "_pydecimal", "Decimal"), [text]) => StdValue::Decimal(text.as_str()?),
+            (("uuid", "UUID"), []) => {
+                let int = state?.get(&Value::String(Cow::Borrowed("int")))?;
+                StdValue::Uuid(u128::try_from(&bigint(int)?).ok()?)
+            }
+            (("fractions", "Fraction"), [text]) => {
+                let text = text.as_str()?.trim();
+                let (num, den) = text.split_once('/').unwrap_or((text, "1"));
+                let (num, den) = (num.trim().parse().ok()?, den.trim().parse().ok()?);
+                StdValue::Fraction(num, den)
+            }
+            (("fractions", "Fraction"), [num, den]) => {
+                StdValue::Fraction(bigint(num)?, bigint(den)?)
+            }
+            (("pathlib", class), segments) if class.ends_with("Path") => StdValue::Path {
+                class,
+                path: join_path(segments, class.contains("Windows"))?,
+            },
+            _ => return None,
+        })
+    }
+}
+
+impl Date {
+    fn new(
+        year: impl TryInto<u16>,
+        month: impl TryInto<u8>,
+        day: impl TryInto<u8>,
+    ) -> Option<Self> {
+        let date = Self {
+            year: year.try_into().ok()?,
+            month: month.try_into().ok()?,
+            day: day.try_into().ok()?,
+        };
+        ((1..=9999).contains(&date.year)
+            && (1..=12).contains(&date.month)
+            && (1..=31).contains(&date.day))
+        .then_some(date)
+    }
+}
+
+impl Time {
+    fn new(hour: u8, minute: u8, second: u8, microsecond: u32, fold: bool) -> Option<Self> {
+        (hour < 24 && minute < 60 && second < 60 && microsecond < 1_000_000).then_some(Self {
+            hour,
+            minute,
+            second,
+            microsecond,
+            fold,
+        })
+    }
+}
+
+impl fmt::Display for Date {
+    /// Like Python's `str`, so `2024-01-31`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl fmt::Display for Time {
+    /// Like Python's `str`, so `12:34:56` with `.123456` on the end if there
+    /// are microseconds.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}", self.hour, self.minute, self.second)?;
+        if self.microsecond != 0 {
+            write!(f, ".{:06}", self.microsecond)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TimeDelta {
+    /// Like Python's `str`, so `-1 day, 23:59:59.500000`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.days != 0 {
+            let plural = if self.days.abs() == 1 { "" } else { "s" };
+            write!(f, "{} day{plural}, ", self.days)?;
+        }
+        let (hours, rest) = (self.seconds / 3600, self.seconds % 3600);
+        write!(f, "{hours}:{:02}:{:02}", rest / 60, rest % 60)?;
+        if self.microseconds != 0 {
+            write!(f, ".{:06}", self.microseconds)?;
+        }
+        Ok(())
+    }
+}
+
+/// The class, arguments and state of an object.
+pub(crate) struct Parts<'v, 'a> {
+    pub class: (&'v str, &'v str),
+    pub args: &'v [Value<'a>],
+    pub state: Option<&'v Value<'a>>,
+}
+
+/// Take an object apart. The `copyreg` helpers that `EvalOptions::object_model`
+/// would have unwrapped get unwrapped here too.
+pub(crate) fn parts<'v, 'a>(val: &'v Value<'a>) -> Option<Parts<'v, 'a>> {
+    let (class, args) = match val {
+        Value::Build(target, state) => {
+            let parts = parts(target)?;
+            return Some(Parts {
+                state: Some(state),
+                ..parts
+            });
+        }
+        Value::PyObject(obj) => {
+            return Some(Parts {
+                class: obj.class.as_global()?,
+                args: &obj.args,
+                state: obj.state.as_ref(),
+            })
+        }
+        Value::Collection(coll) => match (coll.kind, &coll.value) {
+            (CollectionKind::NamedTuple, Value::Seq(_, args)) => {
+                (coll.class.as_global()?, args.as_slice())
+            }
+            _ => return None,
+        },
+        Value::Global(target, args) | Value::Object(target, args) | Value::App(target, args) => {
+            let args = match args.first() {
+                Some(Value::Seq(SequenceType::Tuple, targs)) => targs,
+                _ => args,
+            };
+            match (target.as_global()?, args.as_slice()) {
+                (("copyreg" | "copy_reg", "_reconstructor"), [cls, ..]) => {
+                    (cls.as_global()?, &[][..])
+                }
+                (("copyreg" | "copy_reg", "__newobj__"), [cls, args @ ..]) => {
+                    (cls.as_global()?, args)
+                }
+                (class, args) => (class, args),
+            }
+        }
+        _ => return None,
+    };
+    Some(Parts {
+        class,
+        args,
+        state: None,
+    })
+}
+
+/// The packed state for `datetime` types, which is `bytes`. Python 2
+/// pickles it as a `str`, which we might have decoded.
+fn packed<'v>(val: &'v Value<'_>, len: usize) -> Option<Cow<'v, [u8]>> {
+    let bytes = match val {
+        Value::Bytes(b) => Cow::Borrowed(b.as_ref()),
+        Value::String(s) if s.len() == len => Cow::Borrowed(s.as_bytes()),
+        Value::String(s) => Cow::Owned(
+            s.chars()
+                .map(|c| u8::try_from(c).ok())
+                .collect::<Option<Vec<_>>>()?,
+        ),
+        _ => return None,
+    };
+    (bytes.len() == len).then_some(bytes)
+}
+
+fn be_u24(b: &[u8]) -> u32 {
+    u32::from_be_bytes([0, b[0], b[1], b[2]])
+}
+
+fn tzinfo<'v, 'a>(args: &'v [Value<'a>]) -> Option<&'v Value<'a>> {
+    args.first().filter(|tz| !matches!(tz, Value::None))
+}
+
+/// `hour, minute, second, microsecond, tzinfo`, all optional.
+fn time_args<'v, 'a>(args: &'v [Value<'a>]) -> Option<(Time, Option<&'v Value<'a>>)> {
+    let mut ints = [0; 4];
+    let mut tz = None;
+    for (idx, arg) in args.iter().enumerate() {
+        match idx {
+            0..=3 => ints[idx] = int(arg)?,
+            4 => tz = tzinfo(&args[4..]),
+            _ => return None,
+        }
+    }
+    let [hour, minute, second, microsecond] = ints;
+    let time = Time::new(
+        hour.try_into().ok()?,
+        minute.try_into().ok()?,
+        second.try_into().ok()?,
+        microsecond.try_into().ok()?,
+        false,
+    )?;
+    Some((time, tz))
+}
+
+fn timedelta(args: &[Value<'_>]) -> Option<TimeDelta> {
+    let mut ints = [0; 3];
+    for (idx, arg) in args.iter().enumerate() {
+        ints[idx] = int(arg)?.try_into().ok()?;
+    }
+    let [days, seconds, microseconds] = ints;
+    Some(TimeDelta {
+        days,
+        seconds,
+        microseconds,
+    })
+}
+
+fn int(val: &Value<'_>) -> Option<i64> {
+    match val {
+        Value::Int(i) => Some(*i),
+        Value::Raw(_) => match fix_value(val.clone()).ok()? {
+            Value::Int(i) => Some(i),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn bigint(val: &Value<'_>) -> Option<BigInt> {
+    match val {
+        Value::BigInt(i) => Some(i.clone()),
+        Value::Raw(_) => match fix_value(val.clone()).ok()? {
+            Value::BigInt(i) => Some(i),
+            Value::Int(i) => Some(i.into()),
+            _ => None,
+        },
+        val => int(val).map(BigInt::from),
+    }
+}
+
+/// Join the parts of a path, like `("/", "usr", "lib")`. A part that starts
+/// at the root replaces what came before, like Python does.
+fn join_path(parts: &[Value<'_>], windows: bool) -> Option<String> {
+    let sep = if windows { '\\' } else { '/' };
+    let mut path = String::new();
+    for part in parts {
+        let part = part.as_str()?;
+        let absolute = part.starts_with(sep) || (windows && part.get(1..2) == Some(":"));
+        if absolute || path.is_empty() {
+            path = part.to_string();
+        } else if !part.is_empty() {
+            if !path.ends_with(sep) {
+                path.push(sep);
+            }
+            path.push_str(part);
+        }
+    }
+    Some(if path.is_empty() {
+        ".".to_string()
+    } else {
+        path
+    })
+}
@@ -42,11 +42,11 @@
 //! read it the ld fashioned way using `storage` as the ZIP
 //! member filename.
 
-use std::{borrow::Cow, fs::File, io::Read, path::Path, str::FromStr};
+use std::{fs::File, io::Read, path::Path, str::FromStr};
 
 use anyhow::{anyhow, bail, ensure, Ok, Result};
 
-use crate::{ops::PickleOp, *};
+use crate::*;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TensorType {
@@ -167,95 +167,61 @@ impl RepugnantTorchTensors {
         let mut buf = Vec::with_capacity(zf.size() as usize);
         let _ = zf.read_to_end(&mut buf)?;
         drop(zf);
-        let (_remain, ops) = parse_ops::<nom::error::VerboseError<&[u8]>>(&buf)
-            .map_err(|e| anyhow!("Parse error: {:?}", e))?;
-
-        // Why _wouldn't_ there be random garbage left after parsing the pickle?
-        // ensure!(!remain.is_empty(), "Unexpected remaining data in pickle");
-
-        let (vals, _memo) = evaluate(&ops, true)?;
-        let val = match vals.as_slice() {
-            [Value::Build(a, _), ..] => a.as_ref(),
-            _ => bail!("Unexpected toplevel type"),
-        };
-        // Presumably this is usually going to be an OrderedDict, but maybe
-        // it can also be a plain old Dict.
-        let val = match val {
-            Value::Global(g, seq) => match g.as_ref() {
-                // Dereffing both the Box and Cow here.
-                Value::Raw(rv) if **rv == PickleOp::GLOBAL("collections", "OrderedDict") => {
-                    match seq.as_slice() {
-                        [_, Value::Seq(SequenceType::Tuple, seq2), ..] => seq2,
-                        _ => bail!("Unexpected value in collections.OrderedDict"),
-                    }
-                }
-                _ => bail!("Unexpected type in toplevel Global"),
-            },
-            Value::Seq(SequenceType::Dict, seq) => seq,
-            _ => bail!("Unexpected type in Build"),
-        };
+        // Evaluation stops at STOP, so if there's random garbage left after
+        // the pickle it just gets ignored.
+        let (vals, _memo) = evaluate_bytes(&buf, true)?;
+        // The state dict usually gets built with its metadata, but
+        // it's not required. Presumably this is usually going to be an
+        // OrderedDict, but maybe it can also be a plain old Dict.
+        let val = vals
+            .first()
+            .ok_or_else(|| anyhow!("Unexpected toplevel type"))?
+            .as_dict_pairs()
+            .ok_or_else(|| anyhow!("Unexpected toplevel type, expected a dict"))?;
         let mut tensors = Vec::with_capacity(16);
-        for di in val.iter() {
-            let (k, v) = match di {
-                Value::Seq(SequenceType::Tuple, seq) if seq.len() == 2 => (&seq[0], &seq[1]),
-                _ => bail!("Could not get key/value for dictionary item"),
+        for (k, v) in val.iter() {
+            let k = k
+                .as_str()
+                .ok_or_else(|| anyhow!("Dictionary key is not a string"))?;
+            // It's possible to jam random values into the Dict, so
+            // if it's not a tensor we just ignore it here.
+            let Some(args) = v.as_call("torch._utils", "_rebuild_tensor_v2") else {
+                continue;
             };
-            let k = if let Value::String(s) = k {
-                *s
-            } else {
-                bail!("Dictionary key is not a string");
-            };
-            let v = match v {
-                Value::Global(g, seq)
-                    if g.as_ref()
-                        == &Value::Raw(Cow::Owned(PickleOp::GLOBAL(
-                            "torch._utils",
-                            "_rebuild_tensor_v2",
-                        ))) =>
-                {
-                    seq
-                }
-                // It's possible to jam random values into the Dict, so
-                // since it's not a tensor we just ignore it here.
-                _ => continue,
-            };
-            // println!("\nKey: {k:?}\n{v:?}");
+            // println!("\nKey: {k:?}\n{args:?}");
 
-            let (pidval, offs, shape, stride, grad) = match v.as_slice() {
-                [Value::Seq(SequenceType::Tuple, seq)] => match seq.as_slice() {
-                    [Value::PersId(pidval), Value::Int(offs), Value::Seq(SequenceType::Tuple, shape), Value::Seq(SequenceType::Tuple, stride), Value::Bool(grad), ..] => {
-                        (pidval.as_ref(), *offs as u64, shape, stride, *grad)
-                    }
-                    _ => bail!("Unexpected value in call to torch._utils._rebuild_tensor_v2"),
-                },
-                _ => bail!("Unexpected type in call to torch._utils._rebuild_tensor_v2"),
+            let (pidval, offs, shape, stride, grad) = match args {
+                [Value::PersId(pidval), Value::Int(offs), shape @ Value::Seq(SequenceType::Tuple, _), stride @ Value::Seq(SequenceType::Tuple, _), Value::Bool(grad), ..] => {
+                    (pidval.as_ref(), *offs as u64, shape, stride, *grad)
+                }
+                _ => bail!("Unexpected value in call to torch._utils._rebuild_tensor_v2"),
             };
             // println!("PID: {pidval:?}");
-            let fixdim = |v: &[Value]| {
-                v.iter()
-                    .map(|x| match x {
-                        Value::Int(n) => Ok(*n as usize),
-                        _ => bail!("Bad value for shape/stride item"),
-                    })
-                    .collect::<Result<Vec<_>>>()
+            let fixdim = |v: &Value| {
+                v.extract::<Vec<usize>>()
+                    .map_err(|e| anyhow!("Bad value for shape/stride: {e}"))
             };
             let shape = fixdim(shape)?;
             let stride = fixdim(stride)?;
             // println!("Tensor: shape={shape:?}, stride={stride:?}, offs={offs}, grad={grad:?}");
-            let (stype, sfile, sdev, slen) = match pidval {
-                Value::Seq(SequenceType::Tuple, seq) => match seq.as_slice() {
-                    [Value::String("storage"), Value::Raw(op), Value::String(sfile), Value::String(sdev), Value::Int(slen)] => {
-                        match &**op {
-                            PickleOp::GLOBAL("torch", styp) if styp.ends_with("Storage") => {
-                                (&styp[..styp.len() - 7], *sfile, *sdev, *slen as u64)
-                            }
-                            _ => bail!("Unexpected storage type part of persistant ID"),
-                        }
-                    }
-                    _ => bail!("Unexpected sequence in persistant ID"),
-                },
-                _ => bail!("Unexpected value for persistant ID"),
+            let [tag, styp, sfile, sdev, Value::Int(slen)] = pidval
+                .as_tuple_n()
+                .ok_or_else(|| anyhow!("Unexpected value for persistant ID"))?
+            else {
+                bail!("Unexpected sequence in persistant ID");
+            };
+            let (Some("storage"), Some(sfile), Some(sdev)) =
+                (tag.as_str(), sfile.as_str(), sdev.as_str())
+            else {
+                bail!("Unexpected sequence in persistant ID");
+            };
+            let Some(("torch", stype)) = styp.as_global() else {
+                bail!("Unexpected storage type part of persistant ID");
             };
+            let stype = stype
+                .strip_suffix("Storage")
+                .ok_or_else(|| anyhow!("Unexpected storage type part of persistant ID"))?;
+            let slen = *slen as u64;
             let stype: TensorType = stype
                 .parse()
                 .expect("Impossible: Parsing tensor type failed");
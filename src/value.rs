@@ -3,13 +3,19 @@ use std::borrow::Cow;
 use anyhow::Result;
 use num_bigint::BigInt;
 
-use crate::ops::PickleOp;
+use crate::{
+    array::PyArray,
+    collection::{Collection, CollectionKind},
+    compat::{decode_string, decode_unicode},
+    eval::StringEncoding,
+    object::PyObject,
+    ops::PickleOp,
+};
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 /// The types of sequences that exist.
 pub enum SequenceType {
     List,
-    Dict,
     Tuple,
     Set,
     FrozenSet,
@@ -39,6 +45,34 @@ pub enum Value<'a> {
     /// thing, the second one is the arguments it got applied to.
     Build(Box<Value<'a>>, Box<Value<'a>>),
 
+    /// A Python object put together from `REDUCE`, `NEWOBJ` and friends.
+    /// You'll only see these when evaluating with `EvalOptions::object_model`
+    /// set, otherwise you get `Value::Global`, `Value::Object` and
+    /// `Value::Build`.
+    PyObject(Box<PyObject<'a>>),
+
+    /// One of the `collections` types like `OrderedDict` or a namedtuple.
+    /// You'll only see these when evaluating with `EvalOptions::collections`
+    /// set (and `EvalOptions::guess_namedtuples` for namedtuples), otherwise
+    /// they're just calls or objects. See `Collection`.
+    Collection(Box<Collection<'a>>),
+
+    /// A member of an enum like `enum.Enum` or `enum.IntEnum`. You'll only
+    /// see these for classes in `EvalOptions::enum_table`, or with
+    /// `EvalOptions::guess_enums` set. Otherwise they're just calls to the
+    /// class with the value.
+    Enum {
+        class: Box<Value<'a>>,
+        value: Box<Value<'a>>,
+
+        /// The name of the member, if `EvalOptions::enum_table` knows it.
+        name: Option<Cow<'a, str>>,
+    },
+
+    /// An `array.array`. You'll only see these when evaluating with
+    /// `EvalOptions::arrays` set, otherwise they're just calls. See `PyArray`.
+    Array(Box<PyArray>),
+
     /// References to persistant storage. They basically could be anything.
     /// You kind of have to know what the thing you're trying to
     /// interface wants to use as keys for persistant storage.
@@ -49,36 +83,67 @@ pub enum Value<'a> {
     /// the thing, the second one is the arguments it got applied to.
     Global(Box<Value<'a>>, Vec<Value<'a>>),
 
+    /// A reference to a class (or function, or whatever else lives in
+    /// a module). It doesn't matter whether it came from `GLOBAL`,
+    /// `STACK_GLOBAL` or `INST`, you get the same thing.
+    Class {
+        module: Cow<'a, str>,
+        name: Cow<'a, str>,
+    },
+
     /// A sequence. We don't really distinguish between them
-    /// much. The one exception is when the SequenceType is
-    /// Dict we try to split the flat list of `[k, v, k, v, k, v]`
-    /// into a list of tuples with the key and value.
+    /// much.
     Seq(SequenceType, Vec<Value<'a>>),
 
-    /// A string, but not the crazy strings that have to be
-    /// unescaped as if they were Python strings. If you
-    /// need one of those, look for it inside a `Value::Raw`.
-    String(&'a str),
+    /// A dictionary as a list of key/value pairs in insertion order.
+    /// `DICT`, `SETITEM` and `SETITEMS` all end up here. When items
+    /// get set on something that isn't a dict (like the result of
+    /// reducing `collections.OrderedDict`) they're collected into
+    /// a `Value::Dict` at the end of its arguments.
+    ///
+    /// Duplicate keys aren't removed. Like Python, the last one wins
+    /// when you look something up.
+    Dict(Vec<(Value<'a>, Value<'a>)>),
+
+    /// A string. Protocol 0 strings get unescaped, and Python 2
+    /// strings are decoded according to `EvalOptions::string_encoding`.
+    /// These are only owned if they had to be decoded.
+    String(Cow<'a, str>),
+
+    /// Some bytes. It might be a binary string that couldn't
+    /// get UTF8 decoded. We do the best we can. These are only
+    /// owned if they had to be encoded, for example when `bytes`
+    /// get pickled as `_codecs.encode(...)`.
+    Bytes(Cow<'a, [u8]>),
 
-    /// Some bytes. It might be a byte array or a binary
-    /// string that couldn't get UTF8 decoded. We do the best
-    /// we can.
-    Bytes(&'a [u8]),
+    /// A `bytearray`, the mutable version of `bytes`. Like
+    /// `Value::Bytes`, these are only owned if they had to be encoded.
+    ByteArray(Cow<'a, [u8]>),
 
-    /// An integer, but not the crazy kind that comes as a string
-    /// that has to be parsed. You can look in `Value::RawNum` for
-    /// those.
+    /// An integer. Ones that come as text get parsed, if that
+    /// fails you'll get a `Value::RawNum` instead.
     Int(i64),
 
     /// An integer that can't fit in i64.
     BigInt(BigInt),
 
-    /// An float, but not the crazy kind that comes as a string
-    /// that has to be parsed. You can look in `Value::RawNum` for
-    /// those.
+    /// A float. Ones that come as text get parsed, if that
+    /// fails you'll get a `Value::RawNum` instead.
     Float(f64),
 
-    /// Some kind of weird number we can't handle.
+    /// A complex number as the real and imaginary parts.
+    Complex(f64, f64),
+
+    /// A `slice` with the start, stop and step. They're always a
+    /// `Value::Int`, `Value::BigInt` or `Value::None`.
+    Slice(Box<Value<'a>>, Box<Value<'a>>, Box<Value<'a>>),
+
+    /// A `range` with the start, stop and step. They're always
+    /// a `Value::Int` or `Value::BigInt`.
+    Range(Box<Value<'a>>, Box<Value<'a>>, Box<Value<'a>>),
+
+    /// Some kind of weird number we can't handle, like a protocol 0
+    /// number that couldn't be parsed.
     RawNum(PickleOp<'a>),
 
     /// A boolean value.
@@ -88,59 +153,314 @@ pub enum Value<'a> {
     None,
 }
 
-/// Attempt to fix up a value from `Value::Raw(...)` into something
-/// more reasonable.
-pub fn fix_value(val: Value<'_>) -> Result<Value<'_>> {
+impl<'a> Value<'a> {
+    /// Make a `Value::Class` from borrowed module and class names.
+    pub fn class(module: &'a str, name: &'a str) -> Self {
+        Self::Class {
+            module: Cow::Borrowed(module),
+            name: Cow::Borrowed(name),
+        }
+    }
+
+    /// Check if this is a `Value::Class` with the specified module and name.
+    pub fn is_class(&self, module: &str, name: &str) -> bool {
+        matches!(self, Self::Class { module: m, name: n } if m == module && n == name)
+    }
+
+    /// A short name for what kind of value this is, like `"dict"` or `"int"`.
+    /// Handy for error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Raw(_) => "raw op",
+            Self::Ref(_) => "reference",
+            Self::App(..) => "application",
+            Self::Object(..) => "object",
+            Self::Build(..) => "build",
+            Self::PyObject(_) => "Python object",
+            Self::Collection(coll) => coll.kind.name(),
+            Self::Enum { .. } => "enum",
+            Self::Array(_) => "array",
+            Self::PersId(_) => "persistent ID",
+            Self::Global(..) => "global",
+            Self::Class { .. } => "class",
+            Self::Seq(SequenceType::List, _) => "list",
+            Self::Seq(SequenceType::Tuple, _) => "tuple",
+            Self::Seq(SequenceType::Set, _) => "set",
+            Self::Seq(SequenceType::FrozenSet, _) => "frozenset",
+            Self::Dict(_) => "dict",
+            Self::String(_) => "string",
+            Self::Bytes(_) => "bytes",
+            Self::ByteArray(_) => "bytearray",
+            Self::Int(_) => "int",
+            Self::BigInt(_) => "big int",
+            Self::Float(_) => "float",
+            Self::Complex(..) => "complex",
+            Self::Slice(..) => "slice",
+            Self::Range(..) => "range",
+            Self::RawNum(_) => "raw number",
+            Self::Bool(_) => "bool",
+            Self::None => "None",
+        }
+    }
+
+    /// Count the values in this tree, including this one. References
+    /// count as one value, they aren't followed.
+    pub fn node_count(&self) -> usize {
+        let mut count = 0;
+        self.each_node(|_| count += 1);
+        count
+    }
+
+    /// Call `f` with every value in this tree, including this one.
+    /// References aren't followed.
+    pub(crate) fn each_node<'s>(&'s self, mut f: impl FnMut(&'s Value<'a>)) {
+        let mut todo = vec![self];
+        while let Some(val) = todo.pop() {
+            f(val);
+            match val {
+                Self::App(a, args) | Self::Object(a, args) | Self::Global(a, args) => {
+                    todo.push(a);
+                    todo.extend(args);
+                }
+                Self::Build(a, b) => todo.extend([a.as_ref(), b.as_ref()]),
+                Self::PersId(a) => todo.push(a),
+                Self::Seq(_, items) => todo.extend(items),
+                Self::Dict(items) => items.iter().for_each(|(k, v)| todo.extend([k, v])),
+                Self::PyObject(obj) => {
+                    todo.push(&obj.class);
+                    todo.extend(&obj.args);
+                    todo.extend(obj.state.iter().chain(&obj.slotstate));
+                    todo.extend(&obj.list_items);
+                    obj.kwargs
+                        .iter()
+                        .chain(&obj.dict_items)
+                        .for_each(|(k, v)| todo.extend([k, v]));
+                }
+                Self::Collection(coll) => todo.extend([&coll.class, &coll.value, &coll.extra]),
+                Self::Enum { class, value, .. } => todo.extend([class.as_ref(), value.as_ref()]),
+                _ => (),
+            }
+        }
+    }
+
+    /// Get the key/value pairs if this is a dictionary. Also works for things
+    /// like `collections.OrderedDict` that had their items set after being
+    /// reduced, whether or not it's a `Value::Collection`. Those items come
+    /// right after the argument tuple.
+    pub fn dict_items(&self) -> Option<&[(Value<'a>, Value<'a>)]> {
+        match self {
+            Self::Dict(items) => Some(items),
+            Self::Collection(coll) => coll.value.dict_items(),
+            Self::Global(_, args) | Self::Object(_, args) => match args.as_slice() {
+                [Self::Seq(SequenceType::Tuple, _), Self::Dict(items)] => Some(items),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The module and name if this is a global like a class or function.
+    /// It doesn't matter whether it's a `Value::Class` or a `GLOBAL` that
+    /// got left as `Value::Raw`.
+    pub fn as_global(&self) -> Option<(&str, &str)> {
+        match self {
+            Self::Class { module, name } => Some((module, name)),
+            Self::Raw(op) => match op.as_ref() {
+                PickleOp::GLOBAL(module, name) => Some((module, name)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Get the arguments if this is a call to the specified global, like
+    /// `torch._utils._rebuild_tensor_v2(...)`. This works for `REDUCE`,
+    /// `NEWOBJ`, `INST` and `OBJ`, and for `Value::PyObject`. If the
+    /// arguments came as a tuple you get the items of the tuple.
+    pub fn as_call(&self, module: &str, name: &str) -> Option<&[Value<'a>]> {
+        let (target, args) = match self {
+            Self::Global(target, args) | Self::Object(target, args) | Self::App(target, args) => {
+                let args = match args.first() {
+                    Some(Self::Seq(SequenceType::Tuple, items)) => items,
+                    _ => args,
+                };
+                (target.as_ref(), args)
+            }
+            Self::PyObject(obj) => (&obj.class, &obj.args),
+            Self::Collection(coll) => match (coll.kind, &coll.value) {
+                (CollectionKind::NamedTuple, Self::Seq(_, items)) => (&coll.class, items),
+                _ => return None,
+            },
+            Self::Enum { class, value, .. } => {
+                let args = std::slice::from_ref(value.as_ref());
+                return (class.as_global()? == (module, name)).then_some(args);
+            }
+            _ => return None,
+        };
+        (target.as_global()? == (module, name)).then_some(args.as_slice())
+    }
+
+    /// Get the key/value pairs if this is some kind of dictionary. Unlike
+    /// `dict_items`, this also looks through `BUILD` and works for
+    /// `Value::PyObject`. An object without any items only counts if its
+    /// class is `dict` or one of the dicts from `collections`, since any
+    /// other object would look like an empty dict.
+    pub fn as_dict_pairs(&self) -> Option<&[(Value<'a>, Value<'a>)]> {
+        match self {
+            Self::Build(target, _) => target.as_dict_pairs(),
+            Self::PyObject(obj) => {
+                let dict_class = matches!(
+                    obj.class.as_global(),
+                    Some(
+                        ("builtins" | "__builtin__", "dict")
+                            | ("collections", "OrderedDict" | "defaultdict" | "Counter")
+                    )
+                );
+                (dict_class || !obj.dict_items.is_empty()).then_some(obj.dict_items.as_slice())
+            }
+            val => val.dict_items(),
+        }
+    }
+
+    /// Get the items if this is a tuple with exactly `N` items. Handy for
+    /// destructuring: `let [a, b] = val.as_tuple_n()?;`
+    pub fn as_tuple_n<const N: usize>(&self) -> Option<&[Value<'a>; N]> {
+        match self {
+            Self::Seq(SequenceType::Tuple, items) => items.as_slice().try_into().ok(),
+            _ => None,
+        }
+    }
+
+    /// Get the string if this is a string, including string ops that got
+    /// left as `Value::Raw`. Protocol 0 strings with escapes in them have to
+    /// be decoded with `fix_value` first.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            Self::Raw(op) => match op.as_ref() {
+                PickleOp::BINUNICODE(s)
+                | PickleOp::BINUNICODE8(s)
+                | PickleOp::SHORT_BINUNICODE(s) => Some(s),
+                PickleOp::UNICODE(b) => match decode_unicode(b) {
+                    Ok(Cow::Borrowed(s)) => Some(s),
+                    _ => None,
+                },
+                op @ (PickleOp::STRING(_)
+                | PickleOp::BINSTRING(_)
+                | PickleOp::SHORT_BINSTRING(_)) => {
+                    match decode_string(op, StringEncoding::Utf8OrBytes) {
+                        Ok(Some(Self::String(Cow::Borrowed(s)))) => Some(s),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Look up a key in a dictionary. See `dict_items`.
+    pub fn dict_get(&self, key: &Value<'a>) -> Option<&Value<'a>> {
+        self.dict_items()?
+            .iter()
+            .rfind(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Look up a string key in a dictionary. See `dict_items`.
+    pub fn dict_get_str(&self, key: &str) -> Option<&Value<'a>> {
+        self.dict_items()?
+            .iter()
+            .rfind(|(k, _)| matches!(k, Value::String(s) if s == key))
+            .map(|(_, v)| v)
+    }
+}
+
+/// The value of a `LONG1` or `LONG4`, which is a little endian two's
+/// complement integer.
+pub(crate) fn long_value<'a>(b: &[u8]) -> Value<'a> {
     use once_cell::sync::Lazy;
     static BI64MIN: Lazy<BigInt> = Lazy::new(|| BigInt::from(i64::MIN));
     static BI64MAX: Lazy<BigInt> = Lazy::new(|| BigInt::from(i64::MAX));
+    // A zero length long is just zero.
+    let Some(last) = b.last() else {
+        return Value::Int(0);
+    };
+    let is_neg = last & 0x80 != 0;
+    let mut bint = BigInt::from_bytes_le(num_bigint::Sign::Plus, b);
+    if is_neg {
+        bint -= BigInt::from(1) << (b.len() * 8);
+    }
+    if &bint >= &BI64MIN && &bint <= &BI64MAX {
+        (&bint)
+            .try_into()
+            .map_or_else(|_| Value::BigInt(bint), Value::Int)
+    } else {
+        Value::BigInt(bint)
+    }
+}
+
+/// Attempt to fix up a value from `Value::Raw(...)` into something
+/// more reasonable.
+pub fn fix_value(val: Value<'_>) -> Result<Value<'_>> {
     match val {
         Value::Raw(ref rv) => Ok(match rv.as_ref() {
             PickleOp::BININT(val) => Value::Int(*val as i64),
             PickleOp::BININT1(val) => Value::Int(*val as i64),
             PickleOp::BININT2(val) => Value::Int(*val as i64),
-            PickleOp::LONG1(b) | PickleOp::LONG4(b) if !b.is_empty() => {
-                let blen = b.len();
-                let is_neg = b[blen - 1] & 80 != 0;
-                let mut bint = BigInt::from_bytes_le(num_bigint::Sign::Plus, b);
-                if is_neg {
-                    bint -= BigInt::from(1) << (blen * 8);
-                }
-                if &bint >= &BI64MIN && &bint <= &BI64MAX {
-                    (&bint)
-                        .try_into()
-                        .map_or_else(|_| Value::BigInt(bint), Value::Int)
-                } else {
-                    Value::BigInt(bint)
-                }
-            }
+            PickleOp::LONG1(b) | PickleOp::LONG4(b) => long_value(b),
             PickleOp::BINFLOAT(val) => Value::Float(*val),
             PickleOp::BINUNICODE(s) | PickleOp::BINUNICODE8(s) | PickleOp::SHORT_BINUNICODE(s) => {
-                Value::String(s)
+                Value::String(Cow::Borrowed(s))
             }
-            PickleOp::BINBYTES(b)
-            | PickleOp::BINBYTES8(b)
-            | PickleOp::SHORT_BINBYTES(b)
-            | PickleOp::BYTEARRAY8(b) => Value::Bytes(b),
+            PickleOp::UNICODE(b) => match decode_unicode(b) {
+                Ok(s) => Value::String(s),
+                Err(_) => val,
+            },
+            PickleOp::BINBYTES(b) | PickleOp::BINBYTES8(b) | PickleOp::SHORT_BINBYTES(b) => {
+                Value::Bytes(Cow::Borrowed(b))
+            }
+            PickleOp::BYTEARRAY8(b) => Value::ByteArray(Cow::Borrowed(b)),
             // This isn't how Pickle actually works but we just try to UTF8 decode the
             // string and if it fails, we make it a bytes value instead. If anyone
             // actually cares they can just fix values themselves or recover the raw bytes
             // from the UTF8 string (it's guaranteed to be reversible, as far as I know).
             PickleOp::BINSTRING(b) | PickleOp::SHORT_BINSTRING(b) => std::str::from_utf8(b)
-                .map(Value::String)
-                .unwrap_or_else(|_| Value::Bytes(b)),
+                .map(|s| Value::String(Cow::Borrowed(s)))
+                .unwrap_or_else(|_| Value::Bytes(Cow::Borrowed(b))),
+            // Same thing for protocol 0, once the string is unescaped.
+            PickleOp::STRING(_) => match decode_string(rv, StringEncoding::Utf8OrBytes) {
+                Ok(Some(s)) => s,
+                _ => val,
+            },
             PickleOp::NEWTRUE => Value::Bool(true),
             PickleOp::NEWFALSE => Value::Bool(false),
             PickleOp::NONE => Value::None,
             PickleOp::INT("01") => Value::Bool(true),
             PickleOp::INT("00") => Value::Bool(false),
-            PickleOp::INT(_)
-            | PickleOp::FLOAT(_)
-            | PickleOp::LONG(_)
-            | PickleOp::LONG1(_)
-            | PickleOp::LONG4(_) => Value::RawNum(rv.clone().into_owned()),
+            // Protocol 0 numbers are text. Python 2 also uses these for
+            // protocol 1, and `LONG` gets an `L` on the end.
+            PickleOp::INT(s) | PickleOp::LONG(s) => {
+                let s = s.strip_suffix('L').unwrap_or(s);
+                match (s.parse::<i64>(), s.parse::<BigInt>()) {
+                    (Ok(i), _) => Value::Int(i),
+                    (_, Ok(bi)) => Value::BigInt(bi),
+                    _ => Value::RawNum(rv.clone().into_owned()),
+                }
+            }
+            PickleOp::FLOAT(s) => s
+                .parse()
+                .map_or_else(|_| Value::RawNum(rv.clone().into_owned()), Value::Float),
             _ => val,
         }),
         val => Ok(val),
     }
 }
+
+/// Run one level of something recursive, growing the stack first if it's
+/// about to run out. Values can be nested as deep as
+/// `EvalLimits::max_nesting_depth`, which is more than a normal thread's
+/// stack can take.
+pub(crate) fn deeper<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(64 * 1024, 1024 * 1024, f)
+}
@@ -0,0 +1,172 @@
+use std::borrow::Cow;
+
+use anyhow::Result;
+
+use crate::{
+    eval::PickleMemo,
+    path::{self, Path},
+    value::*,
+};
+
+#[derive(Debug, Clone, Copy)]
+/// A value along with the memo it came from. This is useful if you evaluated
+/// without resolving references: Navigating into the children of a view follows
+/// references as needed, so you can poke around in a big pickle without having to
+/// copy the whole thing.
+///
+/// Scalar values that are still in their raw form (like `Raw(BININT1(1))`) get
+/// fixed up when you look at them with `fixed` or compare dictionary keys.
+///
+/// Nothing else that works on a plain `Value` follows references: Paths,
+/// queries, diffs, keys, conversions and `repr` all see a `Value::Ref` as it
+/// is. Those are meant for resolved values, which is what `evaluate` gives
+/// you by default. Use a view if you didn't resolve them.
+pub struct ValueView<'m, 'a> {
+    value: &'m Value<'a>,
+    memo: &'m PickleMemo<'a>,
+}
+
+impl<'m, 'a> ValueView<'m, 'a> {
+    /// Make a view for a value. If it's a reference, it will be followed.
+    /// References that don't exist in the memo are left alone.
+    pub fn new(value: &'m Value<'a>, memo: &'m PickleMemo<'a>) -> Self {
+        Self {
+            value: memo.resolve_ref(value).unwrap_or(value),
+            memo,
+        }
+    }
+
+    /// The value we're looking at. This is only a `Value::Ref` if the
+    /// reference couldn't be found in the memo.
+    pub fn value(&self) -> &'m Value<'a> {
+        self.value
+    }
+
+    /// The memo used to look up references.
+    pub fn memo(&self) -> &'m PickleMemo<'a> {
+        self.memo
+    }
+
+    /// The value with `fix_value` applied. Only raw values actually get
+    /// copied.
+    pub fn fixed(&self) -> Cow<'m, Value<'a>> {
+        match self.value {
+            Value::Raw(_) => {
+                Cow::Owned(fix_value(self.value.clone()).unwrap_or_else(|_| self.value.clone()))
+            }
+            val => Cow::Borrowed(val),
+        }
+    }
+
+    /// Make a copy of the value with all references resolved and values
+    /// fixed up. This is the same thing `evaluate` does when `resolve_refs`
+    /// is set.
+    pub fn to_resolved(&self) -> Result<Value<'a>> {
+        self.memo.resolve_all_refs(0, self.value.clone(), true)
+    }
+
+    fn view(&self, value: &'m Value<'a>) -> Self {
+        Self::new(value, self.memo)
+    }
+
+    fn resolver(&self) -> impl Fn(&'m Value<'a>) -> &'m Value<'a> + 'm {
+        let memo = self.memo;
+        move |v| memo.resolve_ref(v).unwrap_or(v)
+    }
+
+    /// The items that `index` looks in. That's the items of a sequence
+    /// (including ones like `collections.deque`), the list items of an
+    /// object, or the arguments of a call.
+    pub fn items(&self) -> Option<impl Iterator<Item = ValueView<'m, 'a>> + '_> {
+        let items = path::seq_items(self.value, self.resolver())?;
+        Some(items.iter().map(|v| self.view(v)))
+    }
+
+    /// The number of items if this is a sequence, array or dictionary.
+    /// Objects count their list items if they have any, otherwise their
+    /// dict items.
+    pub fn len(&self) -> Option<usize> {
+        match path::container(self.value, self.resolver()) {
+            Value::Seq(_, items) => Some(items.len()),
+            Value::Array(array) => Some(array.len()),
+            Value::PyObject(obj) if !obj.list_items.is_empty() => Some(obj.list_items.len()),
+            val => path::dict_items(val, self.resolver()).map(<[_]>::len),
+        }
+    }
+
+    /// Check if this is an empty sequence, array or dictionary. You get
+    /// `None` if it's not one of those at all.
+    pub fn is_empty(&self) -> Option<bool> {
+        self.len().map(|len| len == 0)
+    }
+
+    /// Get an item from a sequence. See `Value::index`.
+    pub fn index(&self, idx: usize) -> Option<ValueView<'m, 'a>> {
+        path::index(self.value, i64::try_from(idx).ok()?, self.resolver()).map(|v| self.view(v))
+    }
+
+    /// The key/value pairs that `get` looks in. That's the items of a
+    /// dictionary (including ones like `collections.OrderedDict`) or the
+    /// dict items of an object.
+    pub fn dict_items(
+        &self,
+    ) -> Option<impl Iterator<Item = (ValueView<'m, 'a>, ValueView<'m, 'a>)> + '_> {
+        let items = path::dict_items(self.value, self.resolver())?;
+        Some(items.iter().map(|(k, v)| (self.view(k), self.view(v))))
+    }
+
+    /// Look up a key in a dictionary. Keys are fixed up before being compared.
+    /// If there are duplicate keys, the last one wins. See `Value::get`.
+    pub fn get(&self, key: &Value<'_>) -> Option<ValueView<'m, 'a>> {
+        path::get(self.value, key, self.resolver()).map(|v| self.view(v))
+    }
+
+    /// Look up a string key in a dictionary.
+    pub fn get_str(&self, key: &str) -> Option<ValueView<'m, 'a>> {
+        self.get(&Value::String(Cow::Borrowed(key)))
+    }
+
+    /// Get an attribute of an object. See `Value::attr`.
+    pub fn attr(&self, name: &str) -> Option<ValueView<'m, 'a>> {
+        path::attr(self.value, name, self.resolver()).map(|v| self.view(v))
+    }
+
+    /// Follow a path of dict keys, indexes and attributes. See `Value::at`.
+    pub fn at<'k>(&self, path: impl Into<Path<'k>>) -> Option<ValueView<'m, 'a>> {
+        path::at(self.value, &path.into(), self.resolver()).map(|v| self.view(v))
+    }
+
+    /// The thing being applied and its arguments if this is a `Value::Global`,
+    /// `Value::Object` or `Value::App`.
+    pub fn application(
+        &self,
+    ) -> Option<(
+        ValueView<'m, 'a>,
+        impl Iterator<Item = ValueView<'m, 'a>> + '_,
+    )> {
+        match self.value {
+            Value::Global(target, args)
+            | Value::Object(target, args)
+            | Value::App(target, args) => {
+                Some((self.view(target), args.iter().map(|v| self.view(v))))
+            }
+            _ => None,
+        }
+    }
+
+    /// The target and state if this is a `Value::Build`.
+    pub fn build(&self) -> Option<(ValueView<'m, 'a>, ValueView<'m, 'a>)> {
+        match self.value {
+            Value::Build(target, state) => Some((self.view(target), self.view(state))),
+            _ => None,
+        }
+    }
+
+    /// The persistent ID if this is a `Value::PersId`.
+    pub fn persid(&self) -> Option<ValueView<'m, 'a>> {
+        match self.value {
+            Value::PersId(pid) => Some(self.view(pid)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use std::borrow::Cow;
+
+use anyhow::Result;
+
+use crate::{
+    collection::Collection,
+    object::PyObject,
+    path::{Path, PathKey},
+    value::*,
+};
+
+/// Walks through a value without changing it. Every method has a default that
+/// visits the children using the `walk_*` function with the same name, so you
+/// only need to implement the ones you care about. If you override one and
+/// still want to see what's inside, call the `walk_*` function yourself.
+///
+/// Paths work like `Value::at`: Sequence items and dict values get a key, but
+/// the things `at` looks through (like the argument tuple of a call or the
+/// target and state of `BUILD`) are visited with the same path as their parent.
+/// Dict keys aren't visited.
+pub trait ValueVisitor<'v, 'a: 'v> {
+    /// Called for every value. The default calls one of the other methods
+    /// depending on what kind of value it is.
+    fn visit_value(&mut self, path: &Path<'v>, val: &'v Value<'a>) {
+        walk_value(self, path, val)
+    }
+
+    /// Lists, tuples, sets and frozen sets.
+    fn visit_seq(&mut self, path: &Path<'v>, _typ: &'v SequenceType, items: &'v [Value<'a>]) {
+        walk_seq(self, path, items)
+    }
+
+    fn visit_dict(&mut self, path: &Path<'v>, items: &'v [(Value<'a>, Value<'a>)]) {
+        walk_dict(self, path, items)
+    }
+
+    /// `Value::Global`, `Value::Object` and `Value::App`. The whole value
+    /// gets passed along too in case you need to tell them apart.
+    fn visit_call(
+        &mut self,
+        path: &Path<'v>,
+        _call: &'v Value<'a>,
+        target: &'v Value<'a>,
+        args: &'v [Value<'a>],
+    ) {
+        walk_call(self, path, target, args)
+    }
+
+    fn visit_build(&mut self, path: &Path<'v>, target: &'v Value<'a>, state: &'v Value<'a>) {
+        walk_build(self, path, target, state)
+    }
+
+    fn visit_persid(&mut self, path: &Path<'v>, pid: &'v Value<'a>) {
+        walk_persid(self, path, pid)
+    }
+
+    fn visit_pyobject(&mut self, path: &Path<'v>, obj: &'v PyObject<'a>) {
+        walk_pyobject(self, path, obj)
+    }
+
+    fn visit_collection(&mut self, path: &Path<'v>, coll: &'v Collection<'a>) {
+        walk_collection(self, path, coll)
+    }
+
+    /// Everything without children, like strings, numbers, classes and
+    /// raw values.
+    fn visit_leaf(&mut self, _path: &Path<'v>, _val: &'v Value<'a>) {}
+}
+
+/// The default for `ValueVisitor::visit_value`.
+pub fn walk_value<'v, 'a, V>(visitor: &mut V, path: &Path<'v>, val: &'v Value<'a>)
+where
+    V: ValueVisitor<'v, 'a> + ?Sized,
+{
+    deeper(|| match val {
+        Value::Seq(typ, items) => visitor.visit_seq(path, typ, items),
+        Value::Dict(items) => visitor.visit_dict(path, items),
+        Value::Global(target, args) | Value::Object(target, args) | Value::App(target, args) => {
+            visitor.visit_call(path, val, target, args)
+        }
+        Value::Build(target, state) => visitor.visit_build(path, target, state),
+        Value::PersId(pid) => visitor.visit_persid(path, pid),
+        Value::PyObject(obj) => visitor.visit_pyobject(path, obj),
+        Value::Collection(coll) => visitor.visit_collection(path, coll),
+        Value::Enum { class, value, .. } => {
+            visitor.visit_value(path, class);
+            visitor.visit_value(path, value);
+        }
+        Value::Slice(start, stop, step) | Value::Range(start, stop, step) => {
+            for (idx, item) in [start, stop, step].into_iter().enumerate() {
+                visitor.visit_value(&path.join(idx), item);
+            }
+        }
+        val => visitor.visit_leaf(path, val),
+    })
+}
+
+/// The default for `ValueVisitor::visit_seq`.
+pub fn walk_seq<'v, 'a, V>(visitor: &mut V, path: &Path<'v>, items: &'v [Value<'a>])
+where
+    V: ValueVisitor<'v, 'a> + ?Sized,
+{
+    for (idx, item) in items.iter().enumerate() {
+        visitor.visit_value(&path.join(idx), item);
+    }
+}
+
+/// The default for `ValueVisitor::visit_dict`.
+pub fn walk_dict<'v, 'a, V>(visitor: &mut V, path: &Path<'v>, items: &'v [(Value<'a>, Value<'a>)])
+where
+    V: ValueVisitor<'v, 'a> + ?Sized,
+{
+    for (key, val) in items {
+        visitor.visit_value(&path.join(path_key(key)), val);
+    }
+}
+
+/// The default for `ValueVisitor::visit_call`. The arguments of a call are
+/// usually a tuple, which gets visited with the same path as the call.
+pub fn walk_call<'v, 'a, V>(
+    visitor: &mut V,
+    path: &Path<'v>,
+    target: &'v Value<'a>,
+    args: &'v [Value<'a>],
+) where
+    V: ValueVisitor<'v, 'a> + ?Sized,
+{
+    visitor.visit_value(path, target);
+    match args.first() {
+        Some(Value::Seq(SequenceType::Tuple, _)) => {
+            args.iter().for_each(|arg| visitor.visit_value(path, arg))
+        }
+        _ => walk_seq(visitor, path, args),
+    }
+}
+
+/// The default for `ValueVisitor::visit_build`.
+pub fn walk_build<'v, 'a, V>(
+    visitor: &mut V,
+    path: &Path<'v>,
+    target: &'v Value<'a>,
+    state: &'v Value<'a>,
+) where
+    V: ValueVisitor<'v, 'a> + ?Sized,
+{
+    visitor.visit_value(path, target);
+    visitor.visit_value(path, state);
+}
+
+/// The default for `ValueVisitor::visit_persid`. The persistent ID gets
+/// visited like the only argument of a call.
+pub fn walk_persid<'v, 'a, V>(visitor: &mut V, path: &Path<'v>, pid: &'v Value<'a>)
+where
+    V: ValueVisitor<'v, 'a> + ?Sized,
+{
+    visitor.visit_value(&path.join(0), pid);
+}
+
+/// The default for `ValueVisitor::visit_pyobject`. The class, keyword
+/// arguments, state and slot state get visited with the same path as the
+/// object. Like with `Value::index`, the arguments are only indexed by
+/// position if there are no list items.
+pub fn walk_pyobject<'v, 'a, V>(visitor: &mut V, path: &Path<'v>, obj: &'v PyObject<'a>)
+where
+    V: ValueVisitor<'v, 'a> + ?Sized,
+{
+    visitor.visit_value(path, &obj.class);
+    if obj.list_items.is_empty() {
+        walk_seq(visitor, path, &obj.args);
+    } else {
+        for arg in &obj.args {
+            visitor.visit_value(path, arg);
+        }
+    }
+    let kwargs = obj.kwargs.iter().map(|(_, v)| v);
+    for val in kwargs.chain(&obj.state).chain(&obj.slotstate) {
+        visitor.visit_value(path, val);
+    }
+    walk_seq(visitor, path, &obj.list_items);
+    walk_dict(visitor, path, &obj.dict_items);
+}
+
+/// The default for `ValueVisitor::visit_collection`. The class, items and
+/// extra value all get visited with the same path as the collection.
+pub fn walk_collection<'v, 'a, V>(visitor: &mut V, path: &Path<'v>, coll: &'v Collection<'a>)
+where
+    V: ValueVisitor<'v, 'a> + ?Sized,
+{
+    visitor.visit_value(path, &coll.class);
+    visitor.visit_value(path, &coll.extra);
+    visitor.visit_value(path, &coll.value);
+}
+
+/// Dict keys that aren't strings or ints get turned into strings, so `Value::at`
+/// can't follow them.
+fn path_key<'v>(key: &'v Value<'_>) -> PathKey<'v> {
+    match key {
+        Value::String(s) => PathKey::Str(Cow::Borrowed(s)),
+        Value::Int(i) => PathKey::Int(*i),
+        key => PathKey::Str(Cow::Owned(key.to_string())),
+    }
+}
+
+/// Rewrites a value by taking it apart and putting it back together. Like
+/// `ValueVisitor`, every method has a default that folds the children using
+/// the `fold_*` function with the same name, so you only need to implement
+/// the ones you care about. The methods can return a different kind of value
+/// than they got.
+///
+/// Unlike `ValueVisitor`, dict keys get folded too.
+pub trait ValueFolder<'a> {
+    /// Called for every value. The default calls one of the other methods
+    /// depending on what kind of value it is.
+    fn fold_value(&mut self, val: Value<'a>) -> Result<Value<'a>> {
+        fold_value(self, val)
+    }
+
+    /// Lists, tuples, sets and frozen sets.
+    fn fold_seq(&mut self, typ: SequenceType, items: Vec<Value<'a>>) -> Result<Value<'a>> {
+        fold_seq(self, typ, items)
+    }
+
+    fn fold_dict(&mut self, items: Vec<(Value<'a>, Value<'a>)>) -> Result<Value<'a>> {
+        fold_dict(self, items)
+    }
+
+    fn fold_global(&mut self, target: Value<'a>, args: Vec<Value<'a>>) -> Result<Value<'a>> {
+        let (target, args) = fold_call(self, target, args)?;
+        Ok(Value::Global(Box::new(target), args))
+    }
+
+    fn fold_object(&mut self, target: Value<'a>, args: Vec<Value<'a>>) -> Result<Value<'a>> {
+        let (target, args) = fold_call(self, target, args)?;
+        Ok(Value::Object(Box::new(target), args))
+    }
+
+    fn fold_app(&mut self, target: Value<'a>, args: Vec<Value<'a>>) -> Result<Value<'a>> {
+        let (target, args) = fold_call(self, target, args)?;
+        Ok(Value::App(Box::new(target), args))
+    }
+
+    fn fold_build(&mut self, target: Value<'a>, state: Value<'a>) -> Result<Value<'a>> {
+        fold_build(self, target, state)
+    }
+
+    fn fold_persid(&mut self, pid: Value<'a>) -> Result<Value<'a>> {
+        Ok(Value::PersId(Box::new(self.fold_value(pid)?)))
+    }
+
+    fn fold_pyobject(&mut self, obj: PyObject<'a>) -> Result<Value<'a>> {
+        fold_pyobject(self, obj)
+    }
+
+    fn fold_collection(&mut self, coll: Collection<'a>) -> Result<Value<'a>> {
+        fold_collection(self, coll)
+    }
+
+    /// Everything without children, like strings, numbers, classes and
+    /// raw values. The default leaves them alone.
+    fn fold_leaf(&mut self, val: Value<'a>) -> Result<Value<'a>> {
+        Ok(val)
+    }
+}
+
+/// The default for `ValueFolder::fold_value`.
+pub fn fold_value<'a, F: ValueFolder<'a> + ?Sized>(
+    folder: &mut F,
+    val: Value<'a>,
+) -> Result<Value<'a>> {
+    deeper(|| match val {
+        Value::Seq(typ, items) => folder.fold_seq(typ, items),
+        Value::Dict(items) => folder.fold_dict(items),
+        Value::Global(target, args) => folder.fold_global(*target, args),
+        Value::Object(target, args) => folder.fold_object(*target, args),
+        Value::App(target, args) => folder.fold_app(*target, args),
+        Value::Build(target, state) => folder.fold_build(*target, *state),
+        Value::PersId(pid) => folder.fold_persid(*pid),
+        Value::PyObject(obj) => folder.fold_pyobject(*obj),
+        Value::Collection(coll) => folder.fold_collection(*coll),
+        Value::Enum { class, value, name } => Ok(Value::Enum {
+            class: Box::new(folder.fold_value(*class)?),
+            value: Box::new(folder.fold_value(*value)?),
+            name,
+        }),
+        Value::Slice(start, stop, step) => Ok(Value::Slice(
+            Box::new(folder.fold_value(*start)?),
+            Box::new(folder.fold_value(*stop)?),
+            Box::new(folder.fold_value(*step)?),
+        )),
+        Value::Range(start, stop, step) => Ok(Value::Range(
+            Box::new(folder.fold_value(*start)?),
+            Box::new(folder.fold_value(*stop)?),
+            Box::new(folder.fold_value(*step)?),
+        )),
+        val => folder.fold_leaf(val),
+    })
+}
+
+/// The default for `ValueFolder::fold_seq`.
+pub fn fold_seq<'a, F: ValueFolder<'a> + ?Sized>(
+    folder: &mut F,
+    typ: SequenceType,
+    items: Vec<Value<'a>>,
+) -> Result<Value<'a>> {
+    Ok(Value::Seq(typ, fold_items(folder, items)?))
+}
+
+/// The default for `ValueFolder::fold_dict`.
+pub fn fold_dict<'a, F: ValueFolder<'a> + ?Sized>(
+    folder: &mut F,
+    items: Vec<(Value<'a>, Value<'a>)>,
+) -> Result<Value<'a>> {
+    Ok(Value::Dict(fold_pairs(folder, items)?))
+}
+
+/// Fold the target and arguments of `Value::Global`, `Value::Object` or
+/// `Value::App`. The defaults for `fold_global` and friends use this.
+pub fn fold_call<'a, F: ValueFolder<'a> + ?Sized>(
+    folder: &mut F,
+    target: Value<'a>,
+    args: Vec<Value<'a>>,
+) -> Result<(Value<'a>, Vec<Value<'a>>)> {
+    Ok((folder.fold_value(target)?, fold_items(folder, args)?))
+}
+
+/// The default for `ValueFolder::fold_build`.
+pub fn fold_build<'a, F: ValueFolder<'a> + ?Sized>(
+    folder: &mut F,
+    target: Value<'a>,
+    state: Value<'a>,
+) -> Result<Value<'a>> {
+    Ok(Value::Build(
+        Box::new(folder.fold_value(target)?),
+        Box::new(folder.fold_value(state)?),
+    ))
+}
+
+/// The default for `ValueFolder::fold_pyobject`.
+pub fn fold_pyobject<'a, F: ValueFolder<'a> + ?Sized>(
+    folder: &mut F,
+    obj: PyObject<'a>,
+) -> Result<Value<'a>> {
+    let mut fold_opt = |val: Option<Value<'a>>| val.map(|v| folder.fold_value(v)).transpose();
+    let (state, slotstate) = (fold_opt(obj.state)?, fold_opt(obj.slotstate)?);
+    Ok(Value::PyObject(Box::new(PyObject {
+        class: folder.fold_value(obj.class)?,
+        args: fold_items(folder, obj.args)?,
+        kwargs: fold_pairs(folder, obj.kwargs)?,
+        state,
+        slotstate,
+        list_items: fold_items(folder, obj.list_items)?,
+        dict_items: fold_pairs(folder, obj.dict_items)?,
+    })))
+}
+
+/// The default for `ValueFolder::fold_collection`.
+pub fn fold_collection<'a, F: ValueFolder<'a> + ?Sized>(
+    folder: &mut F,
+    coll: Collection<'a>,
+) -> Result<Value<'a>> {
+    Ok(Value::Collection(Box::new(Collection {
+        kind: coll.kind,
+        class: folder.fold_value(coll.class)?,
+        value: folder.fold_value(coll.value)?,
+        extra: folder.fold_value(coll.extra)?,
+    })))
+}
+
+fn fold_items<'a, F: ValueFolder<'a> + ?Sized>(
+    folder: &mut F,
+    items: Vec<Value<'a>>,
+) -> Result<Vec<Value<'a>>> {
+    items.into_iter().map(|v| folder.fold_value(v)).collect()
+}
+
+fn fold_pairs<'a, F: ValueFolder<'a> + ?Sized>(
+    folder: &mut F,
+    items: Vec<(Value<'a>, Value<'a>)>,
+) -> Result<Vec<(Value<'a>, Value<'a>)>> {
+    items
+        .into_iter()
+        .map(|(k, v)| Ok((folder.fold_value(k)?, folder.fold_value(v)?)))
+        .collect()
+}
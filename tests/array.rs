@@ -0,0 +1,133 @@
+mod common;
+
+use anyhow::Result;
+
+use repugnant_pickle::{ArrayData, EvalOptions, PyArray, StringEncoding, Value};
+
+use common::{both_models, eval_one, ops};
+
+fn arrays(val: &Value<'_>) -> Vec<PyArray> {
+    let Value::Seq(_, items) = val else {
+        panic!("Expected a list, got {val:?}");
+    };
+    items
+        .iter()
+        .map(|v| match v {
+            Value::Array(array) => (**array).clone(),
+            v => panic!("Expected an array, got {v:?}"),
+        })
+        .collect()
+}
+
+fn array(typecode: char, data: ArrayData) -> PyArray {
+    PyArray { typecode, data }
+}
+
+/// `array("f", [1.5, 2]), array("i", [1, -2]), array("u", "hé"),
+/// array("Q", [2**63]), array("d")`
+fn expected() -> Vec<PyArray> {
+    vec![
+        array('f', ArrayData::F32(vec![1.5, 2.0])),
+        array('i', ArrayData::I32(vec![1, -2])),
+        array('u', ArrayData::Unicode("hé".to_string())),
+        array('Q', ArrayData::U64(vec![1 << 63])),
+        array('d', ArrayData::F64(vec![])),
+    ]
+}
+
+#[test]
+fn array_reconstructor() -> Result<()> {
+    // pickle.dumps([array("f", [1.5, 2]), array("i", [1, -2]), array("u", "hé"),
+    //     array("Q", [2**63]), array("d")], 4)
+    let ops = ops(b"\x80\x04\x95\xae\x00\x00\x00\x00\x00\x00\x00]\x94(\x8c\x05array\x94\x8c\x14_array_reconstructor\x94\x93\x94(\x8c\x05array\x94\x8c\x05array\x94\x93\x94\x8c\x01f\x94K\x0eC\x08\x00\x00\xc0?\x00\x00\x00@\x94t\x94R\x94h\x03(h\x06\x8c\x01i\x94K\x08C\x08\x01\x00\x00\x00\xfe\xff\xff\xff\x94t\x94R\x94h\x03(h\x06\x8c\x01u\x94K\x14C\x08h\x00\x00\x00\xe9\x00\x00\x00\x94t\x94R\x94h\x03(h\x06\x8c\x01Q\x94K\nC\x08\x00\x00\x00\x00\x00\x00\x00\x80\x94t\x94R\x94h\x03(h\x06\x8c\x01d\x94K\x10C\x00\x94t\x94R\x94e.");
+    for options in both_models() {
+        let val = eval_one(&ops, &options)?;
+        assert_eq!(arrays(&val), expected());
+        assert_eq!(
+            val.to_string(),
+            "[array.array('f', [1.5, 2.0]), array.array('i', [1, -2]), array.array('u', 'hé'), array.array('Q', [9223372036854775808]), array.array('d')]"
+        );
+    }
+
+    let options = EvalOptions {
+        arrays: false,
+        ..Default::default()
+    };
+    let val = eval_one(&ops, &options)?;
+    let Value::Seq(_, items) = &val else {
+        panic!("Expected a list, got {val:?}");
+    };
+    assert!(items[0].as_call("array", "_array_reconstructor").is_some());
+    Ok(())
+}
+
+#[test]
+fn array_big_endian() -> Result<()> {
+    // class R:
+    //     def __reduce__(self):
+    //         return (array._array_reconstructor,
+    //             (array.array, "i", 9, b"\x00\x00\x00\x01\xff\xff\xff\xfe"))
+    // pickle.dumps(R(), 4)
+    let ops = ops(b"\x80\x04\x95J\x00\x00\x00\x00\x00\x00\x00\x8c\x05array\x94\x8c\x14_array_reconstructor\x94\x93\x94(\x8c\x05array\x94\x8c\x05array\x94\x93\x94\x8c\x01i\x94K\x09C\x08\x00\x00\x00\x01\xff\xff\xff\xfe\x94t\x94R\x94.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        val,
+        Value::Array(Box::new(array('i', ArrayData::I32(vec![1, -2]))))
+    );
+    Ok(())
+}
+
+#[test]
+fn array_list() -> Result<()> {
+    // The same arrays with pickle.dumps(..., 2)
+    let ops = ops(b"\x80\x02]q\x00(carray\narray\nq\x01X\x01\x00\x00\x00fq\x02]q\x03(G?\xf8\x00\x00\x00\x00\x00\x00G@\x00\x00\x00\x00\x00\x00\x00e\x86q\x04Rq\x05h\x01X\x01\x00\x00\x00iq\x06]q\x07(K\x01J\xfe\xff\xff\xffe\x86q\x08Rq\x09h\x01X\x01\x00\x00\x00uq\n]q\x0b(X\x01\x00\x00\x00hq\x0cX\x02\x00\x00\x00\xc3\xa9q\x0de\x86q\x0eRq\x0fh\x01X\x01\x00\x00\x00Qq\x10]q\x11\x8a\x09\x00\x00\x00\x00\x00\x00\x00\x80\x00a\x86q\x12Rq\x13h\x01X\x01\x00\x00\x00dq\x14]q\x15\x86q\x16Rq\x17e.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(arrays(&val), expected());
+
+    // Python 2: pickle.dumps([array("i", [1, -2]), array("d")], 0)
+    let ops = common::ops(b"(lp0\ncarray\narray\np1\n(S'i'\np2\n(lp3\nI1\naI-2\natp4\nRp5\nag1\n(S'd'\np6\n(lp7\ntp8\nRp9\na.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        arrays(&val),
+        [
+            array('i', ArrayData::I32(vec![1, -2])),
+            array('d', ArrayData::F64(vec![])),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn array_python2_str() -> Result<()> {
+    // Older versions of Python 2 pickle arrays as a str, or just the typecode
+    // if they're empty. This makes the same calls in Python 2.7:
+    // class R(object):
+    //     def __init__(self, args): self.args = args
+    //     def __reduce__(self): return (array.array, self.args)
+    // pickle.dumps([R(("i", "\x01\x00\x00\x00\xfe\xff\xff\xff")), R(("d",)),
+    //     R(("B", "\xc3\xa9"))], 0)
+    let proto0 = b"(lp0\ncarray\narray\np1\n(S'i'\np2\nS'\\x01\\x00\\x00\\x00\\xfe\\xff\\xff\\xff'\np3\ntp4\nRp5\nag1\n(S'd'\np6\ntp7\nRp8\nag1\n(S'B'\np9\nS'\\xc3\\xa9'\np10\ntp11\nRp12\na.";
+    // The same with pickle.dumps(..., 2)
+    let proto2 = b"\x80\x02]q\x00(carray\narray\nq\x01U\x01iq\x02U\x08\x01\x00\x00\x00\xfe\xff\xff\xffq\x03\x86q\x04Rq\x05h\x01U\x01dq\x06\x85q\x07Rq\x08h\x01U\x01Bq\x09U\x02\xc3\xa9q\n\x86q\x0bRq\x0ce.";
+    let expected = [
+        array('i', ArrayData::I32(vec![1, -2])),
+        array('d', ArrayData::F64(vec![])),
+        array('B', ArrayData::U8(vec![0xc3, 0xa9])),
+    ];
+    for data in [&proto0[..], &proto2[..]] {
+        let ops = ops(data);
+        for string_encoding in [
+            StringEncoding::Utf8OrBytes,
+            StringEncoding::Bytes,
+            StringEncoding::Latin1,
+        ] {
+            let options = EvalOptions {
+                string_encoding,
+                ..Default::default()
+            };
+            let val = eval_one(&ops, &options)?;
+            assert_eq!(arrays(&val), expected, "{string_encoding:?}");
+        }
+    }
+    Ok(())
+}
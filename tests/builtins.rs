@@ -0,0 +1,140 @@
+mod common;
+
+use std::borrow::Cow;
+
+use anyhow::Result;
+
+use repugnant_pickle::{EvalOptions, KeyMode, ReprOptions, SequenceType, Value, ValueKey};
+
+use common::{eval_one, ops};
+
+fn bytes<'a>(b: &[u8]) -> Value<'a> {
+    Value::Bytes(Cow::Owned(b.to_vec()))
+}
+
+fn check(data: &[u8], expected: Value<'_>) -> Result<()> {
+    let ops = ops(data);
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        val.repr(ReprOptions::default()).to_string(),
+        expected.repr(ReprOptions::default()).to_string()
+    );
+    assert_eq!(format!("{val:?}"), format!("{expected:?}"));
+    Ok(())
+}
+
+#[test]
+fn builtin_bytes() -> Result<()> {
+    // pickle.dumps(b"ab\xff", 2)
+    check(
+        b"\x80\x02c_codecs\nencode\nq\x00X\x04\x00\x00\x00ab\xc3\xbfq\x01X\x06\x00\x00\x00latin1q\x02\x86q\x03Rq\x04.",
+        bytes(b"ab\xff"),
+    )?;
+    // class B:
+    //     def __reduce__(self): return (bytes, ([1, 2, 3],))
+    // pickle.dumps(B(), 2)
+    check(
+        b"\x80\x02c__builtin__\nbytes\nq\x00]q\x01(K\x01K\x02K\x03e\x85q\x02Rq\x03.",
+        bytes(b"\x01\x02\x03"),
+    )
+}
+
+#[test]
+fn builtin_sets() -> Result<()> {
+    // pickle.dumps({1, 2}, 2)
+    check(
+        b"\x80\x02c__builtin__\nset\nq\x00]q\x01(K\x01K\x02e\x85q\x02Rq\x03.",
+        Value::Seq(SequenceType::Set, vec![Value::Int(1), Value::Int(2)]),
+    )?;
+    // pickle.dumps(frozenset([3]), 2)
+    check(
+        b"\x80\x02c__builtin__\nfrozenset\nq\x00]q\x01K\x03a\x85q\x02Rq\x03.",
+        Value::Seq(SequenceType::FrozenSet, vec![Value::Int(3)]),
+    )
+}
+
+#[test]
+fn builtin_numbers() -> Result<()> {
+    // pickle.dumps(1+2j, 2)
+    check(
+        b"\x80\x02c__builtin__\ncomplex\nq\x00G?\xf0\x00\x00\x00\x00\x00\x00G@\x00\x00\x00\x00\x00\x00\x00\x86q\x01Rq\x02.",
+        Value::Complex(1.0, 2.0),
+    )?;
+    // pickle.dumps(slice(1, None, 2), 2)
+    check(
+        b"\x80\x02c__builtin__\nslice\nq\x00K\x01NK\x02\x87q\x01Rq\x02.",
+        Value::Slice(
+            Box::new(Value::Int(1)),
+            Box::new(Value::None),
+            Box::new(Value::Int(2)),
+        ),
+    )?;
+    // pickle.dumps(range(1, 10, 3), 2)
+    check(
+        b"\x80\x02c__builtin__\nxrange\nq\x00K\x01K\nK\x03\x87q\x01Rq\x02.",
+        Value::Range(
+            Box::new(Value::Int(1)),
+            Box::new(Value::Int(10)),
+            Box::new(Value::Int(3)),
+        ),
+    )
+}
+
+#[test]
+fn builtin_bytearray() -> Result<()> {
+    for data in [
+        // pickle.dumps(bytearray(b"ab"), 2)
+        &b"\x80\x02c__builtin__\nbytearray\nq\x00c_codecs\nencode\nq\x01X\x02\x00\x00\x00abq\x02X\x06\x00\x00\x00latin1q\x03\x86q\x04Rq\x05\x85q\x06Rq\x07."[..],
+        // pickle.dumps(bytearray(b"ab"), 3)
+        b"\x80\x03cbuiltins\nbytearray\nq\x00C\x02abq\x01\x85q\x02Rq\x03.",
+        // pickle.dumps(bytearray(b"ab"), 5)
+        b"\x80\x05\x95\x0d\x00\x00\x00\x00\x00\x00\x00\x96\x02\x00\x00\x00\x00\x00\x00\x00ab\x94.",
+    ] {
+        let ops = ops(data);
+        let val = eval_one(&ops, &EvalOptions::default())?;
+        assert_eq!(val, Value::ByteArray(Cow::Borrowed(b"ab")));
+        assert_eq!(val.type_name(), "bytearray");
+        assert_eq!(val.repr(ReprOptions::default()).to_string(), "bytearray(b'ab')");
+        assert_eq!(val.extract::<Vec<u8>>().unwrap(), b"ab");
+
+        // Python thinks they're equal, but they aren't the same type.
+        let b = bytes(b"ab");
+        assert_eq!(ValueKey::new(&val, KeyMode::Python), ValueKey::new(&b, KeyMode::Python));
+        assert_ne!(ValueKey::new(&val, KeyMode::Strict), ValueKey::new(&b, KeyMode::Strict));
+    }
+    Ok(())
+}
+
+#[test]
+fn builtin_bytearray_from_items() -> Result<()> {
+    // class BA:
+    //     def __reduce__(self): return (bytearray, ("a\xff", "latin-1"))
+    // pickle.dumps(BA(), 2)
+    let ops = ops(b"\x80\x02c__builtin__\nbytearray\nq\x00X\x03\x00\x00\x00a\xc3\xbfq\x01X\x07\x00\x00\x00latin-1q\x02\x86q\x03Rq\x04.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        val.repr(ReprOptions::default()).to_string(),
+        "bytearray(b'a\\xff')"
+    );
+
+    // Same thing but with ([1, 2, 300],). 300 doesn't fit so it stays a call.
+    let ops = common::ops(
+        b"\x80\x02c__builtin__\nbytearray\nq\x00]q\x01(K\x01K\x02M,\x01e\x85q\x02Rq\x03.",
+    );
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert!(val.as_call("builtins", "bytearray").is_some(), "{val:?}");
+    Ok(())
+}
+
+#[test]
+fn builtins_off() -> Result<()> {
+    let options = EvalOptions {
+        builtins: false,
+        ..Default::default()
+    };
+    // pickle.dumps({1, 2}, 2)
+    let ops = ops(b"\x80\x02c__builtin__\nset\nq\x00]q\x01(K\x01K\x02e\x85q\x02Rq\x03.");
+    let val = eval_one(&ops, &options)?;
+    assert!(val.as_call("builtins", "set").is_some(), "{val:?}");
+    Ok(())
+}
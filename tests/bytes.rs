@@ -0,0 +1,70 @@
+mod common;
+
+use anyhow::Result;
+
+use repugnant_pickle::{evaluate_bytes_with_options, evaluate_with_options, EvalMode, EvalOptions};
+
+use common::ops;
+
+// The output of tests/data/state_dict.py, which looks like the data.pkl
+// in a PyTorch checkpoint.
+const STATE_DICT: &[u8] = include_bytes!("data/state_dict.pkl");
+
+// pickle.dumps([[i, "x"] for i in range(1200)], 2)
+const LIST: &[u8] = include_bytes!("data/stream.pkl");
+
+// This with protocols 0 to 5:
+// {"a": [1, 2.5, None, True], "b": (b"xy", "z" * 3), "c": {1, 2},
+//  "d": datetime.date(2020, 1, 2), "e": -2**70}
+const PROTOCOLS: [&[u8]; 6] = [
+    b"(dp0\nVa\np1\n(lp2\nI1\naF2.5\naNaI01\nasVb\np3\n(c_codecs\nencode\np4\n(Vxy\np5\nVlatin1\np6\ntp7\nRp8\nVzzz\np9\ntp10\nsVc\np11\nc__builtin__\nset\np12\n((lp13\nI1\naI2\natp14\nRp15\nsVd\np16\ncdatetime\ndate\np17\n(g4\n(V\x07\xe4\x01\x02\np18\ng6\ntp19\nRp20\ntp21\nRp22\nsVe\np23\nL-1180591620717411303424L\ns.",
+    b"}q\x00(X\x01\x00\x00\x00aq\x01]q\x02(K\x01G@\x04\x00\x00\x00\x00\x00\x00NI01\neX\x01\x00\x00\x00bq\x03(c_codecs\nencode\nq\x04(X\x02\x00\x00\x00xyq\x05X\x06\x00\x00\x00latin1q\x06tq\x07Rq\x08X\x03\x00\x00\x00zzzq\x09tq\nX\x01\x00\x00\x00cq\x0bc__builtin__\nset\nq\x0c(]q\x0d(K\x01K\x02etq\x0eRq\x0fX\x01\x00\x00\x00dq\x10cdatetime\ndate\nq\x11(h\x04(X\x05\x00\x00\x00\x07\xc3\xa4\x01\x02q\x12h\x06tq\x13Rq\x14tq\x15Rq\x16X\x01\x00\x00\x00eq\x17L-1180591620717411303424L\nu.",
+    b"\x80\x02}q\x00(X\x01\x00\x00\x00aq\x01]q\x02(K\x01G@\x04\x00\x00\x00\x00\x00\x00N\x88eX\x01\x00\x00\x00bq\x03c_codecs\nencode\nq\x04X\x02\x00\x00\x00xyq\x05X\x06\x00\x00\x00latin1q\x06\x86q\x07Rq\x08X\x03\x00\x00\x00zzzq\x09\x86q\nX\x01\x00\x00\x00cq\x0bc__builtin__\nset\nq\x0c]q\x0d(K\x01K\x02e\x85q\x0eRq\x0fX\x01\x00\x00\x00dq\x10cdatetime\ndate\nq\x11h\x04X\x05\x00\x00\x00\x07\xc3\xa4\x01\x02q\x12h\x06\x86q\x13Rq\x14\x85q\x15Rq\x16X\x01\x00\x00\x00eq\x17\x8a\x09\x00\x00\x00\x00\x00\x00\x00\x00\xc0u.",
+    b"\x80\x03}q\x00(X\x01\x00\x00\x00aq\x01]q\x02(K\x01G@\x04\x00\x00\x00\x00\x00\x00N\x88eX\x01\x00\x00\x00bq\x03C\x02xyq\x04X\x03\x00\x00\x00zzzq\x05\x86q\x06X\x01\x00\x00\x00cq\x07cbuiltins\nset\nq\x08]q\x09(K\x01K\x02e\x85q\nRq\x0bX\x01\x00\x00\x00dq\x0ccdatetime\ndate\nq\x0dC\x04\x07\xe4\x01\x02q\x0e\x85q\x0fRq\x10X\x01\x00\x00\x00eq\x11\x8a\x09\x00\x00\x00\x00\x00\x00\x00\x00\xc0u.",
+    b"\x80\x04\x95i\x00\x00\x00\x00\x00\x00\x00}\x94(\x8c\x01a\x94]\x94(K\x01G@\x04\x00\x00\x00\x00\x00\x00N\x88e\x8c\x01b\x94C\x02xy\x94\x8c\x03zzz\x94\x86\x94\x8c\x01c\x94\x8f\x94(K\x01K\x02\x90\x8c\x01d\x94\x8c\x08datetime\x94\x8c\x04date\x94\x93\x94C\x04\x07\xe4\x01\x02\x94\x85\x94R\x94\x8c\x01e\x94\x8a\x09\x00\x00\x00\x00\x00\x00\x00\x00\xc0u.",
+    b"\x80\x05\x95i\x00\x00\x00\x00\x00\x00\x00}\x94(\x8c\x01a\x94]\x94(K\x01G@\x04\x00\x00\x00\x00\x00\x00N\x88e\x8c\x01b\x94C\x02xy\x94\x8c\x03zzz\x94\x86\x94\x8c\x01c\x94\x8f\x94(K\x01K\x02\x90\x8c\x01d\x94\x8c\x08datetime\x94\x8c\x04date\x94\x93\x94C\x04\x07\xe4\x01\x02\x94\x85\x94R\x94\x8c\x01e\x94\x8a\x09\x00\x00\x00\x00\x00\x00\x00\x00\xc0u.",
+];
+
+fn check_same(data: &[u8], options: &EvalOptions) {
+    let ops = ops(data);
+    let two_stage = evaluate_with_options(&ops, options);
+    let fused = evaluate_bytes_with_options(data, options);
+    match (fused, two_stage) {
+        (Ok(fused), Ok(two_stage)) => assert_eq!(fused, two_stage),
+        (fused, two_stage) => panic!("Expected both to work: {fused:?} vs {two_stage:?}"),
+    }
+}
+
+#[test]
+fn evaluate_bytes_matches_evaluate() {
+    let all_options = [
+        EvalOptions::default(),
+        EvalOptions {
+            resolve_refs: false,
+            ..Default::default()
+        },
+        EvalOptions {
+            object_model: true,
+            ..Default::default()
+        },
+        EvalOptions {
+            mode: EvalMode::Strict,
+            ..Default::default()
+        },
+    ];
+    for options in &all_options {
+        for data in [STATE_DICT, LIST].iter().chain(&PROTOCOLS) {
+            check_same(data, options);
+        }
+    }
+}
+
+#[test]
+fn evaluate_bytes_stops_at_stop() -> Result<()> {
+    let mut data = PROTOCOLS[2].to_vec();
+    data.extend_from_slice(b"garbage");
+    let (values, _) = evaluate_bytes_with_options(&data, &EvalOptions::default())?;
+    let (expected, _) = evaluate_bytes_with_options(PROTOCOLS[2], &EvalOptions::default())?;
+    assert_eq!(values, expected);
+    Ok(())
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use anyhow::Result;
+
+use repugnant_pickle::{EvalOptions, SequenceType, Value};
+
+use common::{eval_one, ops};
+
+fn ordered_dict<'a>() -> Value<'a> {
+    Value::class("collections", "OrderedDict")
+}
+
+#[test]
+fn class_from_global() -> Result<()> {
+    // pickle.dumps(collections.OrderedDict, 2)
+    let ops = ops(b"\x80\x02ccollections\nOrderedDict\nq\x00.");
+    assert_eq!(eval_one(&ops, &EvalOptions::default())?, ordered_dict());
+    Ok(())
+}
+
+#[test]
+fn class_from_stack_global() -> Result<()> {
+    // pickle.dumps(collections.OrderedDict, 4)
+    let ops = ops(b"\x80\x04\x95\x1f\x00\x00\x00\x00\x00\x00\x00\x8c\x0bcollections\x94\x8c\x0bOrderedDict\x94\x93\x94.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(val, ordered_dict());
+    assert_eq!(val.as_global(), Some(("collections", "OrderedDict")));
+    Ok(())
+}
+
+#[test]
+fn class_from_inst() -> Result<()> {
+    // Python 2: class Foo: pass
+    // f = Foo(); f.a = 1; pickle.dumps(f, 0)
+    let ops = ops(b"(i__main__\nFoo\np0\n(dp1\nS'a'\np2\nI1\nsb.");
+    match eval_one(&ops, &EvalOptions::default())? {
+        Value::Build(target, _) => {
+            assert_eq!(
+                *target,
+                Value::Object(Box::new(Value::class("__main__", "Foo")), vec![])
+            )
+        }
+        val => panic!("Unexpected value {val:?}"),
+    }
+    Ok(())
+}
+
+#[test]
+fn memoize_keeps_later_items() -> Result<()> {
+    // a = [1]; pickle.dumps([a, a], 4)
+    let ops = ops(b"\x80\x04\x95\x0c\x00\x00\x00\x00\x00\x00\x00]\x94(]\x94K\x01ah\x01e.");
+    let inner = Value::Seq(SequenceType::List, vec![Value::Int(1)]);
+    assert_eq!(
+        eval_one(&ops, &EvalOptions::default())?,
+        Value::Seq(SequenceType::List, vec![inner.clone(), inner])
+    );
+    Ok(())
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use anyhow::Result;
+
+use repugnant_pickle::{Collection, CollectionKind, EvalOptions, SequenceType, Value};
+
+use common::{both_models, eval_one, ops, s};
+
+fn guess() -> EvalOptions {
+    EvalOptions {
+        guess_namedtuples: true,
+        ..Default::default()
+    }
+}
+
+fn collection<'v, 'a>(val: &'v Value<'a>) -> &'v Collection<'a> {
+    match val {
+        Value::Collection(coll) => coll,
+        val => panic!("Expected a collection, got {val:?}"),
+    }
+}
+
+fn tuple<'a>(items: Vec<Value<'a>>) -> Value<'a> {
+    Value::Seq(SequenceType::Tuple, items)
+}
+
+#[test]
+fn collections_types() -> Result<()> {
+    // pickle.dumps([
+    //     collections.OrderedDict([("a", 1)]),
+    //     collections.defaultdict(list, {"a": [1]}),
+    //     collections.Counter({"a": 2}),
+    //     collections.deque([1, 2], maxlen=5),
+    //     collections.deque(),
+    // ], 2)
+    let ops = ops(b"\x80\x02]q\x00(ccollections\nOrderedDict\nq\x01)Rq\x02X\x01\x00\x00\x00aq\x03K\x01sccollections\ndefaultdict\nq\x04c__builtin__\nlist\nq\x05\x85q\x06Rq\x07h\x03]q\x08K\x01asccollections\nCounter\nq\x09}q\nh\x03K\x02s\x85q\x0bRq\x0cccollections\ndeque\nq\x0d)K\x05\x86q\x0eRq\x0f(K\x01K\x02eh\x0d)Rq\x10e.");
+    for options in both_models() {
+        let val = eval_one(&ops, &options)?;
+        let Value::Seq(_, items) = &val else {
+            panic!("Expected a list, got {val:?}");
+        };
+        let [odict, ddict, counter, deque, empty] = items.as_slice() else {
+            panic!("Expected 5 items, got {val:?}");
+        };
+
+        let odict = collection(odict);
+        assert_eq!(odict.kind, CollectionKind::OrderedDict);
+        assert_eq!(odict.value, Value::Dict(vec![(s("a"), Value::Int(1))]));
+        assert_eq!(odict.type_name(), "OrderedDict");
+
+        let ddict = collection(ddict);
+        assert_eq!(ddict.kind, CollectionKind::DefaultDict);
+        assert_eq!(
+            ddict.value,
+            Value::Dict(vec![(
+                s("a"),
+                Value::Seq(SequenceType::List, vec![Value::Int(1)])
+            )])
+        );
+        assert_eq!(
+            ddict.default_factory(),
+            Some(&Value::class("builtins", "list"))
+        );
+
+        let counter = collection(counter);
+        assert_eq!(counter.kind, CollectionKind::Counter);
+        assert_eq!(counter.value, Value::Dict(vec![(s("a"), Value::Int(2))]));
+        assert_eq!(counter.default_factory(), None);
+
+        let deque = collection(deque);
+        assert_eq!(deque.kind, CollectionKind::Deque);
+        assert_eq!(
+            deque.value,
+            Value::Seq(SequenceType::List, vec![Value::Int(1), Value::Int(2)])
+        );
+        assert_eq!(deque.maxlen(), Some(5));
+        assert_eq!(collection(empty).maxlen(), None);
+
+        assert_eq!(
+            val.to_string(),
+            "[collections.OrderedDict({'a': 1}), collections.defaultdict(builtins.list, {'a': [1]}), collections.Counter({'a': 2}), collections.deque([1, 2], maxlen=5), collections.deque([])]"
+        );
+    }
+
+    let options = EvalOptions {
+        collections: false,
+        ..Default::default()
+    };
+    let val = eval_one(&ops, &options)?;
+    let Value::Seq(_, items) = &val else {
+        panic!("Expected a list, got {val:?}");
+    };
+    assert!(items.iter().all(|v| !matches!(v, Value::Collection(_))));
+    Ok(())
+}
+
+#[test]
+fn collections_namedtuple() -> Result<()> {
+    // Point = collections.namedtuple("Point", "x y")
+    // pickle.dumps(Point(1, 2), 2)
+    let ops = ops(b"\x80\x02c__main__\nPoint\nq\x00K\x01K\x02\x86q\x01\x81q\x02.");
+    let items = tuple(vec![Value::Int(1), Value::Int(2)]);
+
+    // Only a guess, so it's just an object by default.
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        val,
+        Value::Object(
+            Box::new(Value::class("__main__", "Point")),
+            vec![items.clone()]
+        )
+    );
+
+    let val = eval_one(&ops, &guess())?;
+    let coll = collection(&val);
+    assert_eq!(coll.kind, CollectionKind::NamedTuple);
+    assert_eq!(coll.type_name(), "Point");
+    assert_eq!(coll.value, items);
+    assert_eq!(
+        val.as_call("__main__", "Point"),
+        Some(&[Value::Int(1), Value::Int(2)][..])
+    );
+
+    // The object model always gets an object.
+    let options = EvalOptions {
+        object_model: true,
+        ..guess()
+    };
+    assert!(matches!(eval_one(&ops, &options)?, Value::PyObject(_)));
+
+    // Protocols before 2 use copyreg._reconstructor.
+    // pickle.dumps(Point(1, 2), 0)
+    let ops = common::ops(b"ccopy_reg\n_reconstructor\np0\n(c__main__\nPoint\np1\nc__builtin__\ntuple\np2\n(I1\nI2\ntp3\ntp4\nRp5\n.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(val.as_global(), None);
+    assert!(val.as_call("copyreg", "_reconstructor").is_some());
+    let val = eval_one(&ops, &guess())?;
+    let coll = collection(&val);
+    assert_eq!(coll.kind, CollectionKind::NamedTuple);
+    assert_eq!(coll.value, items);
+    Ok(())
+}
+
+#[test]
+fn collections_not_namedtuple() -> Result<()> {
+    // A subclass of str looks just like a namedtuple.
+    // class S(str): pass
+    // pickle.dumps(S("hi"), 2)
+    let ops = ops(b"\x80\x02c__main__\nS\nq\x00X\x02\x00\x00\x00hiq\x01\x85q\x02\x81q\x03.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        val,
+        Value::Object(
+            Box::new(Value::class("__main__", "S")),
+            vec![tuple(vec![s("hi")])]
+        )
+    );
+    assert_eq!(val.to_string(), "__main__.S('hi')");
+
+    // Getting state means it wasn't a namedtuple after all.
+    // class T(tuple): pass
+    // t = T((1, 2)); t.x = 1; pickle.dumps(t, 2)
+    let ops = common::ops(b"\x80\x02c__main__\nT\nq\x00K\x01K\x02\x86q\x01\x85q\x02\x81q\x03}q\x04X\x01\x00\x00\x00xq\x05K\x01sb.");
+    for options in [EvalOptions::default(), guess()] {
+        let val = eval_one(&ops, &options)?;
+        assert_eq!(
+            val,
+            Value::Build(
+                Box::new(Value::Object(
+                    Box::new(Value::class("__main__", "T")),
+                    vec![tuple(vec![tuple(vec![Value::Int(1), Value::Int(2)])])]
+                )),
+                Box::new(Value::Dict(vec![(s("x"), Value::Int(1))]))
+            )
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! Helpers shared by the integration tests. The pickles in the tests are
+//! real pickle bytes, mostly from `pickle.dumps` in Python 3.11. The
+//! comments next to them say what produced them.
+
+#![allow(dead_code)]
+
+use anyhow::Result;
+
+use repugnant_pickle::{evaluate_with_options, ops::PickleOp, parse_ops, EvalOptions, Value};
+
+/// Parse the ops in a pickle.
+pub fn ops(data: &[u8]) -> Vec<PickleOp<'_>> {
+    let (_, ops) = parse_ops::<nom::error::Error<&[u8]>>(data).expect("Parse error");
+    ops
+}
+
+/// Evaluate ops with the options and make sure there's exactly one value.
+pub fn eval_one<'a>(ops: &'a [PickleOp<'a>], options: &EvalOptions) -> Result<Value<'a>> {
+    let (mut values, _) = evaluate_with_options(ops, options)?;
+    assert_eq!(values.len(), 1, "Expected one value, got {values:?}");
+    Ok(values.pop().unwrap())
+}
+
+/// Options for putting objects together as `Value::PyObject`.
+pub fn object_model() -> EvalOptions {
+    EvalOptions {
+        object_model: true,
+        ..Default::default()
+    }
+}
+
+/// The default options and `object_model`, for things that should work
+/// either way.
+pub fn both_models() -> [EvalOptions; 2] {
+    [EvalOptions::default(), object_model()]
+}
+
+/// A string value.
+pub fn s<'a>(s: &str) -> Value<'a> {
+    Value::String(s.to_string().into())
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+
+use repugnant_pickle::{ConvertError, EvalOptions, FromValue, Path, Value};
+
+use common::{both_models, eval_one, ops, s};
+
+// pickle.dumps({"lr": 0.001, "steps": [1, -3], "names": {"a": 1, (1, 2): "x"},
+//     "big": 2**70, "shape": (3, 4, 5), "opt": None,
+//     "od": collections.OrderedDict(a=[2]), "data": bytearray(b"ab")}, 4)
+const CONFIG: &[u8] = b"\x80\x04\x95\xc1\x00\x00\x00\x00\x00\x00\x00}\x94(\x8c\x02lr\x94G?PbM\xd2\xf1\xa9\xfc\x8c\x05steps\x94]\x94(K\x01J\xfd\xff\xff\xffe\x8c\x05names\x94}\x94(\x8c\x01a\x94K\x01K\x01K\x02\x86\x94\x8c\x01x\x94u\x8c\x03big\x94\x8a\x09\x00\x00\x00\x00\x00\x00\x00\x00@\x8c\x05shape\x94K\x03K\x04K\x05\x87\x94\x8c\x03opt\x94N\x8c\x02od\x94\x8c\x0bcollections\x94\x8c\x0bOrderedDict\x94\x93\x94)R\x94h\x06]\x94K\x02as\x8c\x04data\x94\x8c\x08builtins\x94\x8c\x09bytearray\x94\x93\x94C\x02ab\x94\x85\x94R\x94u.";
+
+fn field<'v, 'a>(val: &'v Value<'a>, key: &str) -> &'v Value<'a> {
+    val.get(&s(key))
+        .unwrap_or_else(|| panic!("Expected {key} in {val:?}"))
+}
+
+fn err<'v, T: FromValue<'v> + std::fmt::Debug>(val: &'v Value<'_>) -> ConvertError {
+    val.extract::<T>().expect_err("Expected an error")
+}
+
+#[test]
+fn convert_ok() -> Result<()> {
+    let ops = ops(CONFIG);
+    for options in both_models() {
+        let val = eval_one(&ops, &options)?;
+        assert_eq!(field(&val, "lr").extract::<f64>()?, 0.001);
+        assert_eq!(field(&val, "steps").extract::<Vec<i32>>()?, [1, -3]);
+        let shape: (usize, usize, usize) = field(&val, "shape").try_into()?;
+        assert_eq!(shape, (3, 4, 5));
+        assert_eq!(field(&val, "shape").extract::<Vec<u64>>()?, [3, 4, 5]);
+        assert_eq!(field(&val, "opt").extract::<Option<i64>>()?, None);
+        assert_eq!(field(&val, "data").extract::<Vec<u8>>()?, b"ab");
+        assert_eq!(
+            field(&val, "od").extract::<BTreeMap<&str, Vec<i64>>>()?,
+            BTreeMap::from([("a", vec![2])])
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn convert_error_paths() -> Result<()> {
+    let ops = ops(CONFIG);
+    for options in both_models() {
+        let val = eval_one(&ops, &options)?;
+
+        let e = err::<Vec<usize>>(field(&val, "steps"));
+        assert_eq!(e.path, Path::from([1]));
+        assert_eq!(e.to_string(), "-3 doesn't fit in usize at 1");
+
+        // The first value that doesn't convert is the one in the error.
+        let e = err::<HashMap<String, f64>>(&val);
+        assert_eq!(e.path, Path::from(["steps"]));
+        assert_eq!(e.to_string(), "Expected a float, found list at steps");
+
+        // Keys that aren't strings or ints show up as their repr, and keys
+        // that don't convert get the key in the path too.
+        let e = err::<HashMap<String, i64>>(field(&val, "names"));
+        assert_eq!(e.to_string(), "Expected a string, found tuple at (1, 2)");
+        let e = err::<BTreeMap<String, String>>(field(&val, "names"));
+        assert_eq!(e.to_string(), "Expected a string, found int at a");
+
+        // Errors from deeper down get the whole path.
+        let e = err::<HashMap<&str, Vec<String>>>(field(&val, "od"));
+        assert_eq!(e.path, Path::from(["a".into(), 0.into()].to_vec()));
+        assert_eq!(e.to_string(), "Expected a string, found int at a/0");
+    }
+    Ok(())
+}
+
+#[test]
+fn convert_errors() -> Result<()> {
+    let ops = ops(CONFIG);
+    let val = eval_one(&ops, &EvalOptions::default())?;
+
+    let e = err::<i64>(field(&val, "big"));
+    assert_eq!(e.path, Path::default());
+    assert_eq!(e.to_string(), "1180591620717411303424 doesn't fit in i64");
+    assert_eq!(
+        err::<u32>(field(&val, "lr")).to_string(),
+        "Expected an int, found float"
+    );
+    assert_eq!(
+        err::<(usize, usize)>(field(&val, "shape")).to_string(),
+        "Expected 2 items, found 3"
+    );
+    assert_eq!(
+        err::<Vec<i64>>(field(&val, "lr")).to_string(),
+        "Expected a sequence, found float"
+    );
+    assert_eq!(
+        err::<HashMap<String, i64>>(field(&val, "steps")).to_string(),
+        "Expected a dict, found list"
+    );
+    assert_eq!(
+        err::<Option<String>>(field(&val, "steps")).to_string(),
+        "Expected a string, found list"
+    );
+    assert_eq!(
+        err::<Vec<u8>>(field(&val, "lr")).to_string(),
+        "Expected bytes, found float"
+    );
+    assert_eq!(
+        err::<bool>(field(&val, "opt")).to_string(),
+        "Expected a bool, found None"
+    );
+    assert!(<&str>::try_from(field(&val, "data")).is_err());
+    Ok(())
+}
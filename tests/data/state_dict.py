@@ -0,0 +1,37 @@
+# Run with the output filename and protocol:
+#   python3 state_dict.py state_dict.pkl 2
+#   python3 state_dict.py state_dict_p4.pkl 4
+#   python3 state_dict.py state_dict_p0.pkl 0
+# Makes a pickle shaped like the data.pkl in a PyTorch checkpoint, without
+# needing PyTorch: fake torch modules so the globals get the right names.
+import collections, pickle, sys, types
+torch = types.ModuleType('torch'); utils = types.ModuleType('torch._utils')
+sys.modules['torch'] = torch; sys.modules['torch._utils'] = utils
+torch._utils = utils
+class FloatStorage: pass
+FloatStorage.__module__ = 'torch'; torch.FloatStorage = FloatStorage
+class Storage:
+    def __init__(self, key, numel): self.key, self.numel = key, numel
+class Tensor:
+    def __init__(self, storage, offset, size, stride):
+        self.args = (storage, offset, size, stride, False, collections.OrderedDict())
+    def __reduce_ex__(self, proto): return (utils._rebuild_tensor_v2, self.args)
+def _rebuild_tensor_v2(*a): pass
+_rebuild_tensor_v2.__module__ = 'torch._utils'; utils._rebuild_tensor_v2 = _rebuild_tensor_v2
+class P(pickle._Pickler):
+    def persistent_id(self, obj):
+        if isinstance(obj, Storage):
+            return ('storage', FloatStorage, obj.key, 'cpu', obj.numel)
+    def save_pers(self, pid):
+        # Protocol 0 can only write persistent IDs as text, so always write
+        # them the binary way.
+        self.save(pid, save_persistent_id=False)
+        self.write(pickle.BINPERSID)
+sd = collections.OrderedDict()
+sd['emb.weight'] = Tensor(Storage('0', 12), 0, (3, 4), (4, 1))
+sd['emb.bias'] = Tensor(Storage('1', 4), 0, (4,), (1,))
+shared = Storage('2', 8)
+sd['head.weight'] = Tensor(shared, 0, (2, 2), (2, 1))
+sd['head.bias'] = Tensor(shared, 4, (4,), (1,))
+with open(sys.argv[1], 'wb') as f:
+    P(f, int(sys.argv[2])).dump(sd)
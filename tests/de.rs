@@ -0,0 +1,146 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use repugnant_pickle::{from_value, EvalOptions};
+
+use common::{eval_one, ops};
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Thing<'a> {
+    name: &'a str,
+    size: u8,
+    tags: Vec<String>,
+    extra: Option<i64>,
+    data: &'a [u8],
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Point {
+    x: i64,
+    y: f64,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+enum Shape {
+    Red,
+    Circle(f64),
+}
+
+#[test]
+fn de_struct() -> Result<()> {
+    // pickle.dumps({"name": "x", "size": 3, "tags": ["a"], "extra": None, "data": b"\x00\x01"}, 3)
+    let ops = ops(b"\x80\x03}q\x00(X\x04\x00\x00\x00nameq\x01X\x01\x00\x00\x00xq\x02X\x04\x00\x00\x00sizeq\x03K\x03X\x04\x00\x00\x00tagsq\x04]q\x05X\x01\x00\x00\x00aq\x06aX\x05\x00\x00\x00extraq\x07NX\x04\x00\x00\x00dataq\x08C\x02\x00\x01q\x09u.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    let thing: Thing = from_value(&val)?;
+    assert_eq!(
+        thing,
+        Thing {
+            name: "x",
+            size: 3,
+            tags: vec!["a".to_string()],
+            extra: None,
+            data: b"\x00\x01",
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn de_object_state() -> Result<()> {
+    // class P:
+    //     def __init__(self): self.x = 1; self.y = 2.5
+    // pickle.dumps(P(), 2)
+    let ops = ops(b"\x80\x02c__main__\nP\nq\x00)\x81q\x01}q\x02(X\x01\x00\x00\x00xq\x03K\x01X\x01\x00\x00\x00yq\x04G@\x04\x00\x00\x00\x00\x00\x00ub.");
+    for object_model in [false, true] {
+        let options = EvalOptions {
+            object_model,
+            ..Default::default()
+        };
+        let val = eval_one(&ops, &options)?;
+        assert_eq!(from_value::<Point>(&val)?, Point { x: 1, y: 2.5 });
+    }
+    Ok(())
+}
+
+#[test]
+fn de_ordered_dict() -> Result<()> {
+    // pickle.dumps(collections.OrderedDict([("a", 1), ("b", 2)]), 2)
+    let ops = ops(b"\x80\x02ccollections\nOrderedDict\nq\x00)Rq\x01(X\x01\x00\x00\x00aq\x02K\x01X\x01\x00\x00\x00bq\x03K\x02u.");
+    let expected = HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+    for collections in [false, true] {
+        let options = EvalOptions {
+            collections,
+            ..Default::default()
+        };
+        let val = eval_one(&ops, &options)?;
+        assert_eq!(from_value::<HashMap<String, i64>>(&val)?, expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn de_enum_and_class() -> Result<()> {
+    // pickle.dumps(["Red", {"Circle": 1.5}], 2)
+    let ops = ops(b"\x80\x02]q\x00(X\x03\x00\x00\x00Redq\x01}q\x02X\x06\x00\x00\x00Circleq\x03G?\xf8\x00\x00\x00\x00\x00\x00se.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        from_value::<Vec<Shape>>(&val)?,
+        [Shape::Red, Shape::Circle(1.5)]
+    );
+
+    // pickle.dumps(collections.OrderedDict, 2)
+    let ops = common::ops(b"\x80\x02ccollections\nOrderedDict\nq\x00.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(from_value::<String>(&val)?, "collections.OrderedDict");
+    Ok(())
+}
+
+#[test]
+fn de_errors() -> Result<()> {
+    // x = [1]; pickle.dumps([x, x], 2)
+    let ops = ops(b"\x80\x02]q\x00(]q\x01K\x01ah\x01e.");
+    let options = EvalOptions {
+        resolve_refs: false,
+        ..Default::default()
+    };
+    let val = eval_one(&ops, &options)?;
+    let err = from_value::<Vec<Vec<i64>>>(&val).unwrap_err();
+    assert_eq!(err.to_string(), "Can't deserialize an unresolved reference");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(from_value::<Vec<Vec<i64>>>(&val)?, [[1], [1]]);
+
+    // pickle.dumps([2**200], 2)
+    let ops = common::ops(b"\x80\x02]q\x00\x8a\x1a\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01a.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    let err = from_value::<Vec<u128>>(&val).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Integer 1606938044258990275541962092341162602522202993782792835301376 is too big"
+    );
+
+    // pickle.dumps(["Red", {"Circle": 1.5}], 2)
+    let ops = common::ops(b"\x80\x02]q\x00(X\x03\x00\x00\x00Redq\x01}q\x02X\x06\x00\x00\x00Circleq\x03G?\xf8\x00\x00\x00\x00\x00\x00se.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    let err = from_value::<Vec<i64>>(&val).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: string \"Red\", expected i64"
+    );
+    let err = from_value::<HashMap<String, i64>>(&val).unwrap_err();
+    assert_eq!(err.to_string(), "invalid type: sequence, expected a map");
+
+    // class F:
+    //     def __reduce__(self): return (print, (1,))
+    // pickle.dumps(F(), 2)
+    let ops = common::ops(b"\x80\x02c__builtin__\nprint\nq\x00K\x01\x85q\x01Rq\x02.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    let err = from_value::<Point>(&val).unwrap_err();
+    assert_eq!(err.to_string(), "Can't deserialize global");
+    Ok(())
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use anyhow::Result;
+
+use repugnant_pickle::{EvalMode, EvalOptions, Value};
+
+use common::{eval_one, ops, s};
+
+fn int_dict<'a>(items: &[(i64, i64)]) -> Value<'a> {
+    Value::Dict(
+        items
+            .iter()
+            .map(|&(k, v)| (Value::Int(k), Value::Int(v)))
+            .collect(),
+    )
+}
+
+#[test]
+fn dict_from_dict_op() -> Result<()> {
+    // Protocol 0 DICT with the items after the MARK, pickle.loads gives {1: 2}
+    let ops = ops(b"(I1\nI2\nd.");
+    assert_eq!(
+        eval_one(&ops, &EvalOptions::default())?,
+        int_dict(&[(1, 2)])
+    );
+    Ok(())
+}
+
+#[test]
+fn dict_from_setitem() -> Result<()> {
+    // pickle.dumps({1: 2, 3: 4}, 0)
+    let ops = ops(b"(dp0\nI1\nI2\nsI3\nI4\ns.");
+    assert_eq!(
+        eval_one(&ops, &EvalOptions::default())?,
+        int_dict(&[(1, 2), (3, 4)])
+    );
+    Ok(())
+}
+
+#[test]
+fn dict_from_setitems() -> Result<()> {
+    // pickle.dumps({"a": 1, "b": 2}, 1)
+    let ops = ops(b"}q\x00(X\x01\x00\x00\x00aq\x01K\x01X\x01\x00\x00\x00bq\x02K\x02u.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        val,
+        Value::Dict(vec![(s("a"), Value::Int(1)), (s("b"), Value::Int(2))])
+    );
+    assert_eq!(val.dict_get_str("b"), Some(&Value::Int(2)));
+    assert_eq!(val.dict_get(&s("a")), Some(&Value::Int(1)));
+    assert_eq!(val.dict_get_str("c"), None);
+    Ok(())
+}
+
+#[test]
+fn ordereddict_items() -> Result<()> {
+    let options = EvalOptions {
+        collections: false,
+        ..Default::default()
+    };
+    let expected = [(s("a"), Value::Int(1)), (s("b"), Value::Int(2))];
+    // pickle.dumps(collections.OrderedDict(a=1, b=2), 2)
+    let reduce_ops = ops(b"\x80\x02ccollections\nOrderedDict\nq\x00)Rq\x01(X\x01\x00\x00\x00aq\x02K\x01X\x01\x00\x00\x00bq\x03K\x02u.");
+    let val = eval_one(&reduce_ops, &options)?;
+    assert_eq!(val.dict_items(), Some(expected.as_slice()));
+    assert!(val.as_call("collections", "OrderedDict").is_some());
+
+    // The same thing with a list of pairs, like Python 2 pickled it:
+    // collections.OrderedDict([["a", 1], ["b", 2]])
+    let pair_ops = ops(b"\x80\x02ccollections\nOrderedDict\nq\x00]q\x01(]q\x02(X\x01\x00\x00\x00aK\x01e]q\x03(X\x01\x00\x00\x00bK\x02ee\x85Rq\x04.");
+    let val = eval_one(&pair_ops, &options)?;
+    assert_eq!(val.dict_items(), Some(expected.as_slice()));
+    Ok(())
+}
+
+#[test]
+fn reduce_with_dict_argument() -> Result<()> {
+    // class D(dict):
+    //     def __reduce__(self):
+    //         return (D, ({"x": 1},), None, None, iter(self.items()))
+    // pickle.dumps(D(y=2), 2)
+    let ops = ops(b"\x80\x02c__main__\nD\nq\x00}q\x01X\x01\x00\x00\x00xq\x02K\x01s\x85q\x03Rq\x04X\x01\x00\x00\x00yq\x05K\x02s.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(val.dict_get_str("y"), Some(&Value::Int(2)));
+    assert_eq!(val.dict_get_str("x"), None);
+    let [arg] = val.as_call("__main__", "D").unwrap() else {
+        panic!("Expected one argument in {val:?}")
+    };
+    assert_eq!(arg.dict_get_str("x"), Some(&Value::Int(1)));
+    Ok(())
+}
+
+#[test]
+fn obj_with_dict_argument() -> Result<()> {
+    // MARK, GLOBAL __main__ C, {1: 2}, OBJ, then SETITEM 3: 4. The dict is
+    // an argument, so the item goes after it and not in it.
+    let ops = ops(b"(c__main__\nC\n}K\x01K\x02soK\x03K\x04s.");
+    for mode in [EvalMode::Lenient, EvalMode::Strict] {
+        let options = EvalOptions {
+            mode,
+            ..EvalOptions::default()
+        };
+        let val = eval_one(&ops, &options)?;
+        assert_eq!(val.dict_items(), int_dict(&[(3, 4)]).dict_items());
+        let [arg] = val.as_call("__main__", "C").unwrap() else {
+            panic!("Expected one argument in {val:?}")
+        };
+        assert_eq!(*arg, int_dict(&[(1, 2)]));
+    }
+    Ok(())
+}
+
+#[test]
+fn setitems_shape_is_consistent() -> Result<()> {
+    // The same dict from SETITEM, SETITEMS and DICT ends up the same.
+    // pickle.dumps({1: 2, 3: 4}, 1), pickle.dumps({1: 2, 3: 4}, 0) and
+    // a protocol 0 DICT.
+    for data in [
+        &b"}q\x00(K\x01K\x02K\x03K\x04u."[..],
+        b"(dp0\nI1\nI2\nsI3\nI4\ns.",
+        b"(I1\nI2\nI3\nI4\nd.",
+    ] {
+        let ops = ops(data);
+        let val = eval_one(&ops, &EvalOptions::default())?;
+        assert_eq!(val, int_dict(&[(1, 2), (3, 4)]));
+    }
+    Ok(())
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use anyhow::Result;
+
+use repugnant_pickle::{Change, DiffOptions, EvalOptions, SequenceType, Value};
+
+use common::{both_models, eval_one, ops, s};
+
+fn tolerance(float_tolerance: f64) -> DiffOptions {
+    DiffOptions { float_tolerance }
+}
+
+/// The differences as lines.
+fn lines(old: &Value<'_>, new: &Value<'_>, options: &DiffOptions) -> Vec<String> {
+    old.diff(new, options)
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[test]
+fn diff_dicts() -> Result<()> {
+    // pickle.dumps({"lr": 0.001, "steps": [1, 2], "name": "a", "drop": None}, 2)
+    let old = ops(b"\x80\x02}q\x00(X\x02\x00\x00\x00lrq\x01G?PbM\xd2\xf1\xa9\xfcX\x05\x00\x00\x00stepsq\x02]q\x03(K\x01K\x02eX\x04\x00\x00\x00nameq\x04X\x01\x00\x00\x00aq\x05X\x04\x00\x00\x00dropq\x06Nu.");
+    // pickle.dumps({"lr": 0.0005, "steps": [1, 2, 3], "name": 1, "extra": True}, 2)
+    let new = ops(b"\x80\x02}q\x00(X\x02\x00\x00\x00lrq\x01G?@bM\xd2\xf1\xa9\xfcX\x05\x00\x00\x00stepsq\x02]q\x03(K\x01K\x02K\x03eX\x04\x00\x00\x00nameq\x04K\x01X\x05\x00\x00\x00extraq\x05\x88u.");
+    let old = eval_one(&old, &EvalOptions::default())?;
+    let new = eval_one(&new, &EvalOptions::default())?;
+    assert_eq!(
+        lines(&old, &new, &DiffOptions::default()),
+        [
+            "~ /lr: 0.001 -> 0.0005",
+            "+ /steps/2: 3",
+            "! /name: 'a' -> 1",
+            "- /drop: None",
+            "+ /extra: True",
+        ]
+    );
+    assert_eq!(
+        lines(&old, &new, &tolerance(0.001)),
+        [
+            "+ /steps/2: 3",
+            "! /name: 'a' -> 1",
+            "- /drop: None",
+            "+ /extra: True",
+        ]
+    );
+    assert!(old.diff(&old, &DiffOptions::default()).is_empty());
+    Ok(())
+}
+
+#[test]
+fn diff_non_string_keys() -> Result<()> {
+    // A string key that looks like a tuple key is a different key.
+    // pickle.dumps({"(1, 2)": 1, (1, 2): 2}, 2)
+    let old = ops(b"\x80\x02}q\x00(X\x06\x00\x00\x00(1, 2)q\x01K\x01K\x01K\x02\x86q\x02K\x02u.");
+    // pickle.dumps({"(1, 2)": 5, (1, 2): 2}, 2)
+    let new = ops(b"\x80\x02}q\x00(X\x06\x00\x00\x00(1, 2)q\x01K\x05K\x01K\x02\x86q\x02K\x02u.");
+    let old = eval_one(&old, &EvalOptions::default())?;
+    let new = eval_one(&new, &EvalOptions::default())?;
+    let diffs = old.diff(&new, &DiffOptions::default());
+    assert_eq!(diffs.len(), 1, "{diffs:?}");
+    assert_eq!(
+        diffs[0].change,
+        Change::ValueChanged(&Value::Int(1), &Value::Int(5))
+    );
+    assert!(old.diff(&old, &DiffOptions::default()).is_empty());
+    Ok(())
+}
+
+#[test]
+fn diff_opaque_state() -> Result<()> {
+    // The state isn't a dict, so it gets compared as a whole.
+    // class C:
+    //     def __init__(self, s): self.s = s
+    //     def __reduce__(self): return (C, (1,), self.s)
+    // pickle.dumps(C((0.5, "x")), 2)
+    let old = ops(b"\x80\x02c__main__\nC\nq\x00K\x01\x85q\x01Rq\x02G?\xe0\x00\x00\x00\x00\x00\x00X\x01\x00\x00\x00xq\x03\x86q\x04b.");
+    // pickle.dumps(C((0.5000001, "x")), 2)
+    let new = ops(b"\x80\x02c__main__\nC\nq\x00K\x01\x85q\x01Rq\x02G?\xe0\x00\x005\xaf\xe55X\x01\x00\x00\x00xq\x03\x86q\x04b.");
+    for options in both_models() {
+        let old = eval_one(&old, &options)?;
+        let new = eval_one(&new, &options)?;
+        let diffs = old.diff(&new, &DiffOptions::default());
+        assert_eq!(diffs.len(), 1, "{diffs:?}");
+        assert_eq!(diffs[0].path.to_string(), "");
+        assert!(matches!(diffs[0].change, Change::ValueChanged(..)));
+        assert!(old.diff(&new, &tolerance(1e-6)).is_empty());
+        assert_eq!(old.diff(&new, &tolerance(1e-8)).len(), 1);
+    }
+    Ok(())
+}
+
+#[test]
+fn diff_nan() -> Result<()> {
+    // pickle.dumps([float("nan"), 1.0], 2)
+    let old =
+        ops(b"\x80\x02]q\x00(G\x7f\xf8\x00\x00\x00\x00\x00\x00G?\xf0\x00\x00\x00\x00\x00\x00e.");
+    let old = eval_one(&old, &EvalOptions::default())?;
+    assert!(old.diff(&old, &DiffOptions::default()).is_empty());
+    Ok(())
+}
+
+#[test]
+fn diff_sets() {
+    // Members are matched up by value, wherever they are in the set.
+    let set = |typ, items: &[&str]| Value::Seq(typ, items.iter().map(|i| s(i)).collect());
+    let old = set(SequenceType::Set, &["a", "b", "c"]);
+    let new = set(SequenceType::Set, &["c", "a", "d"]);
+    assert_eq!(
+        lines(&old, &new, &DiffOptions::default()),
+        ["- /1: 'b'", "+ /2: 'd'"]
+    );
+
+    let old = set(SequenceType::FrozenSet, &["a", "b"]);
+    let new = set(SequenceType::FrozenSet, &["b", "a"]);
+    assert!(old.diff(&new, &DiffOptions::default()).is_empty());
+}
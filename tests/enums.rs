@@ -0,0 +1,154 @@
+mod common;
+
+use std::borrow::Cow;
+
+use anyhow::Result;
+
+use repugnant_pickle::{EnumTable, EvalOptions, SequenceType, Value};
+
+use common::{eval_one, ops, s};
+
+// class Color(enum.Enum):
+//     RED = 1
+//     GREEN = 2
+// class Mode(enum.IntEnum):
+//     A = 1
+// class Name(str, enum.Enum):
+//     X = "x"
+// pickle.dumps([Color.RED, Mode.A, Name.X, Color.GREEN, decimal.Decimal("1")], 2)
+const MEMBERS: &[u8] = b"\x80\x02]q\x00(c__main__\nColor\nq\x01K\x01\x85q\x02Rq\x03c__main__\nMode\nq\x04K\x01\x85q\x05Rq\x06c__main__\nName\nq\x07X\x01\x00\x00\x00xq\x08\x85q\x09Rq\nh\x01K\x02\x85q\x0bRq\x0ccdecimal\nDecimal\nq\x0dX\x01\x00\x00\x001q\x0e\x85q\x0fRq\x10e.";
+
+fn table() -> EnumTable {
+    let mut table = EnumTable::default();
+    table.add_class(
+        "__main__",
+        "Color",
+        [(Value::Int(1), "RED"), (Value::Int(2), "GREEN")],
+    );
+    table
+}
+
+fn member<'a>(name: &'a str, value: Value<'a>, member: Option<&'a str>) -> Value<'a> {
+    Value::Enum {
+        class: Box::new(Value::class("__main__", name)),
+        value: Box::new(value),
+        name: member.map(Cow::Borrowed),
+    }
+}
+
+fn call<'a>(module: &'a str, name: &'a str, arg: Value<'a>) -> Value<'a> {
+    Value::Global(
+        Box::new(Value::class(module, name)),
+        vec![Value::Seq(SequenceType::Tuple, vec![arg])],
+    )
+}
+
+#[test]
+fn enums_off() -> Result<()> {
+    let ops = ops(MEMBERS);
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        val,
+        Value::Seq(
+            SequenceType::List,
+            vec![
+                call("__main__", "Color", Value::Int(1)),
+                call("__main__", "Mode", Value::Int(1)),
+                call("__main__", "Name", s("x")),
+                call("__main__", "Color", Value::Int(2)),
+                call("decimal", "Decimal", s("1")),
+            ]
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn enums_table() -> Result<()> {
+    let ops = ops(MEMBERS);
+    for object_model in [false, true] {
+        let options = EvalOptions {
+            enum_table: table(),
+            object_model,
+            ..Default::default()
+        };
+        let val = eval_one(&ops, &options)?;
+        let Value::Seq(_, items) = &val else {
+            panic!("Expected a list, got {val:?}");
+        };
+        assert_eq!(items[0], member("Color", Value::Int(1), Some("RED")));
+        assert_eq!(items[3], member("Color", Value::Int(2), Some("GREEN")));
+        // Only the classes in the table.
+        assert!(!matches!(items[1], Value::Enum { .. }));
+        assert!(!matches!(items[2], Value::Enum { .. }));
+        assert_eq!(
+            items[0].as_call("__main__", "Color"),
+            Some(&[Value::Int(1)][..])
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn enums_guess() -> Result<()> {
+    let ops = ops(MEMBERS);
+    let options = EvalOptions {
+        enum_table: table(),
+        guess_enums: true,
+        ..Default::default()
+    };
+    let val = eval_one(&ops, &options)?;
+    assert_eq!(
+        val,
+        Value::Seq(
+            SequenceType::List,
+            vec![
+                member("Color", Value::Int(1), Some("RED")),
+                member("Mode", Value::Int(1), None),
+                member("Name", s("x"), None),
+                member("Color", Value::Int(2), Some("GREEN")),
+                // Things we know about aren't enums.
+                call("decimal", "Decimal", s("1")),
+            ]
+        )
+    );
+    assert_eq!(
+        val.to_string(),
+        "[__main__.Color.RED, __main__.Mode(1), __main__.Name('x'), __main__.Color.GREEN, decimal.Decimal('1')]"
+    );
+    Ok(())
+}
+
+#[test]
+fn enums_with_state() -> Result<()> {
+    // Enum members don't get state, so this is just an object.
+    // class C:
+    //     def __reduce__(self): return (C, (1,), {"a": 1})
+    // pickle.dumps(C(), 2)
+    let ops = ops(
+        b"\x80\x02c__main__\nC\nq\x00K\x01\x85q\x01Rq\x02}q\x03X\x01\x00\x00\x00aq\x04K\x01sb.",
+    );
+    let options = EvalOptions {
+        guess_enums: true,
+        ..Default::default()
+    };
+    let val = eval_one(&ops, &options)?;
+    assert_eq!(
+        val,
+        Value::Build(
+            Box::new(call("__main__", "C", Value::Int(1))),
+            Box::new(Value::Dict(vec![(s("a"), Value::Int(1))]))
+        )
+    );
+
+    let options = EvalOptions {
+        object_model: true,
+        ..options
+    };
+    let Value::PyObject(obj) = eval_one(&ops, &options)? else {
+        panic!("Expected an object");
+    };
+    assert_eq!(obj.args, [Value::Int(1)]);
+    assert_eq!(obj.attr("a"), Some(&Value::Int(1)));
+    Ok(())
+}
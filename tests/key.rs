@@ -0,0 +1,156 @@
+mod common;
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use anyhow::Result;
+
+use repugnant_pickle::{EvalOptions, KeyMode, SequenceType, Value};
+
+use common::{eval_one, ops, s};
+
+fn items<'v, 'a>(val: &'v Value<'a>) -> &'v [Value<'a>] {
+    match val {
+        Value::Seq(_, items) => items,
+        val => panic!("Expected a sequence, got {val:?}"),
+    }
+}
+
+fn same<'a>(a: &Value<'a>, b: &Value<'a>, mode: KeyMode) -> bool {
+    a.key(mode) == b.key(mode)
+}
+
+#[test]
+fn key_modes() -> Result<()> {
+    // pickle.dumps([1, 1.0, True, 2**70, float("nan"), 0.0, -0.0, (1, 2), [1, 2],
+    //     frozenset({1}), {1}, complex(1, 0), "a", b"a"], 4)
+    let ops = ops(b"\x80\x04\x95\x85\x00\x00\x00\x00\x00\x00\x00]\x94(K\x01G?\xf0\x00\x00\x00\x00\x00\x00\x88\x8a\x09\x00\x00\x00\x00\x00\x00\x00\x00@G\x7f\xf8\x00\x00\x00\x00\x00\x00G\x00\x00\x00\x00\x00\x00\x00\x00G\x80\x00\x00\x00\x00\x00\x00\x00K\x01K\x02\x86\x94]\x94(K\x01K\x02e(K\x01\x91\x94\x8f\x94(K\x01\x90\x8c\x08builtins\x94\x8c\x07complex\x94\x93\x94G?\xf0\x00\x00\x00\x00\x00\x00G\x00\x00\x00\x00\x00\x00\x00\x00\x86\x94R\x94\x8c\x01a\x94C\x01a\x94e.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    let [one, fone, t, big, nan, zero, negzero, tuple, list, fset, set, cplx, a, ba] = items(&val)
+    else {
+        panic!("Expected 14 items, got {val:?}");
+    };
+    // NaN isn't equal to itself as a value, but it is as a key.
+    assert_ne!(nan, nan);
+    for mode in [KeyMode::Strict, KeyMode::Python] {
+        assert!(same(nan, nan, mode));
+        assert!(same(zero, negzero, mode));
+        assert!(!same(one, big, mode));
+        assert!(!same(tuple, list, mode));
+        assert!(!same(a, ba, mode));
+        assert!(!same(one, a, mode));
+    }
+
+    assert!(!same(one, fone, KeyMode::Strict));
+    assert!(!same(one, t, KeyMode::Strict));
+    assert!(!same(fset, set, KeyMode::Strict));
+    assert!(!same(fone, cplx, KeyMode::Strict));
+    assert!(same(one, fone, KeyMode::Python));
+    assert!(same(one, t, KeyMode::Python));
+    assert!(same(fset, set, KeyMode::Python));
+    assert!(same(fone, cplx, KeyMode::Python));
+
+    let strict = items(&val)
+        .iter()
+        .map(|v| v.key(KeyMode::Strict))
+        .collect::<HashSet<_>>();
+    assert_eq!(strict.len(), 13);
+    let python = items(&val)
+        .iter()
+        .map(|v| v.key(KeyMode::Python))
+        .collect::<HashSet<_>>();
+    // 1, 1.0, True and the complex number are all the same, and so are
+    // 0.0 and -0.0 and the two sets.
+    assert_eq!(python.len(), 9);
+    Ok(())
+}
+
+#[test]
+fn key_order() -> Result<()> {
+    // pickle.dumps([3, 1.5, 1, 2**70, -1.0, float(2**70)], 4)
+    let ops = ops(b"\x80\x04\x95/\x00\x00\x00\x00\x00\x00\x00]\x94(K\x03G?\xf8\x00\x00\x00\x00\x00\x00K\x01\x8a\x09\x00\x00\x00\x00\x00\x00\x00\x00@G\xbf\xf0\x00\x00\x00\x00\x00\x00GDP\x00\x00\x00\x00\x00\x00e.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    let sorted = items(&val)
+        .iter()
+        .map(|v| v.key(KeyMode::Python))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|k| k.value.to_string())
+        .collect::<Vec<_>>();
+    // 2**70 and float(2**70) are the same, so there's only one of them.
+    assert_eq!(sorted.len(), 5);
+    assert_eq!(sorted[..4], ["-1.0", "1", "1.5", "3"]);
+    Ok(())
+}
+
+#[test]
+fn key_map_lookup() -> Result<()> {
+    // Keys from one pickle find values from another.
+    // pickle.dumps({(1, 2): "x", 1: "y"}, 2)
+    let ops = ops(
+        b"\x80\x02}q\x00(K\x01K\x02\x86q\x01X\x01\x00\x00\x00xq\x02K\x01X\x01\x00\x00\x00yq\x03u.",
+    );
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    let map = val
+        .dict_items()
+        .expect("Expected a dict")
+        .iter()
+        .map(|(k, v)| (k.key(KeyMode::Python), v))
+        .collect::<HashMap<_, _>>();
+    // pickle.dumps([(1, 2), True], 2)
+    let keys_ops = common::ops(b"\x80\x02]q\x00(K\x01K\x02\x86q\x01\x88e.");
+    let keys = eval_one(&keys_ops, &EvalOptions::default())?;
+    let found = items(&keys)
+        .iter()
+        .map(|k| map.get(&k.key(KeyMode::Python)).copied())
+        .collect::<Vec<_>>();
+    assert_eq!(found, [Some(&s("x")), Some(&s("y"))]);
+    Ok(())
+}
+
+#[test]
+fn dedup_set() -> Result<()> {
+    // Not something Python would make since Python dedups sets itself.
+    // PROTO 4, EMPTY_SET, MARK, BININT1 1, BINFLOAT 1.0, NEWTRUE, BININT1 2,
+    // ADDITEMS, STOP
+    let ops = ops(b"\x80\x04\x8f(K\x01G?\xf0\x00\x00\x00\x00\x00\x00\x88K\x02\x90.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        val.clone().dedup(KeyMode::Strict),
+        Value::Seq(
+            SequenceType::Set,
+            vec![
+                Value::Int(1),
+                Value::Float(1.0),
+                Value::Bool(true),
+                Value::Int(2)
+            ]
+        )
+    );
+    assert_eq!(
+        val.dedup(KeyMode::Python),
+        Value::Seq(SequenceType::Set, vec![Value::Int(1), Value::Int(2)])
+    );
+    Ok(())
+}
+
+#[test]
+fn dedup_dict() -> Result<()> {
+    // The same for dicts, which pickle.loads turns into {1: "d", 2: "c"}.
+    // PROTO 4, EMPTY_DICT, MARK, BININT1 1, "a", BINFLOAT 1.0, "b",
+    // BININT1 2, "c", BININT1 1, "d", SETITEMS, STOP
+    let ops = ops(b"\x80\x04}(K\x01\x8c\x01aG?\xf0\x00\x00\x00\x00\x00\x00\x8c\x01bK\x02\x8c\x01cK\x01\x8c\x01du.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        val.clone().dedup(KeyMode::Strict),
+        Value::Dict(vec![
+            (Value::Int(1), s("d")),
+            (Value::Float(1.0), s("b")),
+            (Value::Int(2), s("c")),
+        ])
+    );
+    assert_eq!(
+        val.dedup(KeyMode::Python),
+        Value::Dict(vec![(Value::Int(1), s("d")), (Value::Int(2), s("c"))])
+    );
+    Ok(())
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use repugnant_pickle::{evaluate_with_options, EvalLimits, EvalOptions, LimitExceeded};
+
+use common::ops;
+
+// a = []
+// for _ in range(40): a = [a, a]
+// pickle.dumps(a, 2)
+const BOMB: &[u8] = b"\x80\x02]q\x00(]q\x01(]q\x02(]q\x03(]q\x04(]q\x05(]q\x06(]q\x07(]q\x08(]q\x09(]q\n(]q\x0b(]q\x0c(]q\x0d(]q\x0e(]q\x0f(]q\x10(]q\x11(]q\x12(]q\x13(]q\x14(]q\x15(]q\x16(]q\x17(]q\x18(]q\x19(]q\x1a(]q\x1b(]q\x1c(]q\x1d(]q\x1e(]q\x1f(]q (]q!(]q\"(]q#(]q$(]q%(]q&(]q'(]q(h(eh'eh&eh%eh$eh#eh\"eh!eh eh\x1feh\x1eeh\x1deh\x1ceh\x1beh\x1aeh\x19eh\x18eh\x17eh\x16eh\x15eh\x14eh\x13eh\x12eh\x11eh\x10eh\x0feh\x0eeh\x0deh\x0ceh\x0beh\neh\x09eh\x08eh\x07eh\x06eh\x05eh\x04eh\x03eh\x02eh\x01e.";
+
+// pickle.dumps(list(range(5)), 2)
+const RANGE: &[u8] = b"\x80\x02]q\x00(K\x00K\x01K\x02K\x03K\x04e.";
+
+// pickle.dumps([[1], [2], [3]], 2)
+const NESTED: &[u8] = b"\x80\x02]q\x00(]q\x01K\x01a]q\x02K\x02a]q\x03K\x03ae.";
+
+fn limit_err(data: &[u8], limits: EvalLimits) -> LimitExceeded {
+    let ops = ops(data);
+    let options = EvalOptions {
+        limits,
+        ..Default::default()
+    };
+    let err = evaluate_with_options(&ops, &options).unwrap_err();
+    err.downcast_ref::<LimitExceeded>()
+        .unwrap_or_else(|| panic!("Not a limit error: {err}"))
+        .clone()
+}
+
+#[test]
+fn bomb_hits_node_limit() {
+    // The default limit works too, it just takes a while to get there.
+    let limits = EvalLimits {
+        max_nodes: 100_000,
+        ..Default::default()
+    };
+    assert_eq!(limit_err(BOMB, limits), LimitExceeded::Nodes(100_000));
+
+    // It's only a problem once the references get resolved.
+    let ops = ops(BOMB);
+    let options = EvalOptions {
+        resolve_refs: false,
+        ..Default::default()
+    };
+    let (values, memo) = evaluate_with_options(&ops, &options).unwrap();
+    assert_eq!(values.len(), 1);
+    assert_eq!(memo.0.len(), 41);
+}
+
+#[test]
+fn stack_depth_limit() {
+    let limits = EvalLimits {
+        max_stack_depth: 3,
+        ..Default::default()
+    };
+    assert_eq!(limit_err(RANGE, limits), LimitExceeded::StackDepth(3));
+}
+
+#[test]
+fn memo_entry_limit() {
+    let limits = EvalLimits {
+        max_memo_entries: 2,
+        ..Default::default()
+    };
+    assert_eq!(limit_err(NESTED, limits), LimitExceeded::MemoEntries(2));
+}
+
+#[test]
+fn node_limit() {
+    let limits = EvalLimits {
+        max_nodes: 5,
+        ..Default::default()
+    };
+    assert_eq!(limit_err(RANGE, limits), LimitExceeded::Nodes(5));
+}
+
+#[test]
+fn within_limits() {
+    let ops = ops(NESTED);
+    let options = EvalOptions {
+        limits: EvalLimits {
+            max_stack_depth: 6,
+            max_memo_entries: 4,
+            max_nodes: 100,
+            max_nesting_depth: 2,
+        },
+        ..Default::default()
+    };
+    evaluate_with_options(&ops, &options).unwrap();
+}
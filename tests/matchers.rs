@@ -0,0 +1,114 @@
+mod common;
+
+use std::borrow::Cow;
+
+use anyhow::Result;
+
+use repugnant_pickle::{ops::PickleOp, EvalOptions, Value};
+
+use common::{eval_one, object_model, ops, s};
+
+// See data/state_dict.py.
+const STATE_DICT: &[u8] = include_bytes!("data/state_dict.pkl");
+const STATE_DICT_P4: &[u8] = include_bytes!("data/state_dict_p4.pkl");
+
+#[test]
+fn matchers_state_dict() -> Result<()> {
+    for (data, options) in [STATE_DICT, STATE_DICT_P4]
+        .into_iter()
+        .flat_map(|data| [(data, EvalOptions::default()), (data, object_model())])
+    {
+        let ops = ops(data);
+        let val = eval_one(&ops, &options)?;
+        let pairs = val.as_dict_pairs().expect("Expected a dict");
+        let keys = pairs.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                Some("emb.weight"),
+                Some("emb.bias"),
+                Some("head.weight"),
+                Some("head.bias")
+            ]
+        );
+        let args = pairs[0]
+            .1
+            .as_call("torch._utils", "_rebuild_tensor_v2")
+            .expect("Expected a tensor");
+        assert_eq!(args.len(), 6);
+        assert_eq!(args[1], Value::Int(0));
+        assert_eq!(args[2].as_tuple_n(), Some(&[Value::Int(3), Value::Int(4)]));
+        assert_eq!(args[2].as_tuple_n::<1>(), None);
+        assert_eq!(pairs[0].1.as_call("torch._utils", "nope"), None);
+        let Value::PersId(pid) = &args[0] else {
+            panic!("Expected a persistent ID, got {:?}", args[0]);
+        };
+        let [tag, styp, key, dev, _] = pid.as_tuple_n().expect("Expected a 5-tuple");
+        assert_eq!(tag.as_str(), Some("storage"));
+        assert_eq!(styp.as_global(), Some(("torch", "FloatStorage")));
+        assert_eq!(key.as_str(), Some("0"));
+        assert_eq!(dev.as_str(), Some("cpu"));
+        assert_eq!(styp.as_str(), None);
+        assert_eq!(tag.as_global(), None);
+    }
+    Ok(())
+}
+
+#[test]
+fn matchers_tuple() -> Result<()> {
+    // pickle.dumps(((1, 2), "x"), 2)
+    let ops = ops(b"\x80\x02K\x01K\x02\x86q\x00X\x01\x00\x00\x00xq\x01\x86q\x02.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    let [inner, x] = val.as_tuple_n().expect("Expected a pair");
+    assert_eq!(inner.as_tuple_n(), Some(&[Value::Int(1), Value::Int(2)]));
+    assert_eq!(x, &s("x"));
+    assert_eq!(val.as_dict_pairs(), None);
+    assert_eq!(val.as_call("builtins", "tuple"), None);
+    Ok(())
+}
+
+#[test]
+fn matchers_object_dict_pairs() -> Result<()> {
+    // class D(dict): pass
+    // pickle.dumps(D(a=1), 2)
+    let ops = ops(b"\x80\x02c__main__\nD\nq\x00)\x81q\x01X\x01\x00\x00\x00aq\x02K\x01s.");
+    let val = eval_one(&ops, &object_model())?;
+    assert_eq!(val.as_dict_pairs(), Some(&[(s("a"), Value::Int(1))][..]));
+    assert_eq!(val.as_call("__main__", "D"), Some(&[][..]));
+
+    // An object with no items isn't a dict.
+    // class P: pass
+    // pickle.dumps(P(), 2)
+    let ops = common::ops(b"\x80\x02c__main__\nP\nq\x00)\x81q\x01.");
+    let val = eval_one(&ops, &object_model())?;
+    assert_eq!(val.as_dict_pairs(), None);
+    assert_eq!(val.as_call("__main__", "P"), Some(&[][..]));
+    Ok(())
+}
+
+#[test]
+fn matchers_empty_ordered_dict() -> Result<()> {
+    // pickle.dumps(collections.OrderedDict(), 2)
+    let ops = ops(b"\x80\x02ccollections\nOrderedDict\nq\x00)Rq\x01.");
+    for options in [EvalOptions::default(), object_model()] {
+        let val = eval_one(&ops, &options)?;
+        assert_eq!(val.as_dict_pairs(), Some(&[][..]));
+    }
+    Ok(())
+}
+
+#[test]
+fn matchers_raw_strings() {
+    let raw = |op| Value::Raw(Cow::Owned(op));
+    assert_eq!(raw(PickleOp::SHORT_BINUNICODE("a")).as_str(), Some("a"));
+    assert_eq!(raw(PickleOp::UNICODE(b"a")).as_str(), Some("a"));
+    assert_eq!(raw(PickleOp::STRING("'a'")).as_str(), Some("a"));
+    assert_eq!(raw(PickleOp::SHORT_BINSTRING(b"a")).as_str(), Some("a"));
+
+    // These have to be decoded, so there's nothing to borrow.
+    assert_eq!(raw(PickleOp::UNICODE(b"C:\\u005ctmp")).as_str(), None);
+    assert_eq!(raw(PickleOp::UNICODE(b"h\xe9")).as_str(), None);
+    assert_eq!(raw(PickleOp::STRING("'a\\n'")).as_str(), None);
+    assert_eq!(raw(PickleOp::SHORT_BINSTRING(b"\xff")).as_str(), None);
+    assert_eq!(raw(PickleOp::BININT1(1)).as_str(), None);
+}
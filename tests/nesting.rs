@@ -0,0 +1,158 @@
+mod common;
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+
+use repugnant_pickle::{
+    evaluate_with_options, visit::ValueVisitor, DiffOptions, EvalLimits, EvalOptions, KeyMode,
+    LimitExceeded, Path, SequenceType, Value, ValueKey,
+};
+
+use common::ops;
+
+/// A pickle that's nested `cycles` times through a list, a dict and a call
+/// to `__main__.f`, with `extra` more lists inside that. The innermost
+/// value is the int 1. Every cycle is 4 levels deep since the call
+/// arguments are a tuple. Python can't pickle things this deep without
+/// raising the recursion limit, but `pickle.loads` is happy to load them.
+fn nested(cycles: usize, extra: usize) -> Vec<u8> {
+    let mut data = b"\x80\x02".to_vec();
+    for _ in 0..cycles {
+        data.extend_from_slice(b"]}X\x01\x00\x00\x00kc__main__\nf\n");
+    }
+    data.extend(std::iter::repeat_n(b']', extra));
+    data.extend_from_slice(b"K\x01");
+    data.extend(std::iter::repeat_n(b'a', extra));
+    for _ in 0..cycles {
+        data.extend_from_slice(b"\x85Rsa");
+    }
+    data.push(b'.');
+    data
+}
+
+/// Like `nested` but only lists, and each one goes through the memo.
+fn nested_refs(depth: u32) -> Vec<u8> {
+    let mut data = b"\x80\x02".to_vec();
+    for mid in 0..depth {
+        data.push(b']');
+        data.push(b'r');
+        data.extend_from_slice(&mid.to_le_bytes());
+    }
+    data.extend_from_slice(b"K\x01");
+    data.extend(std::iter::repeat_n(b'a', depth as usize));
+    data.push(b'.');
+    data
+}
+
+/// How deep a value is, without recursing.
+fn depth(val: &Value<'_>) -> usize {
+    let mut todo = vec![(val, 0)];
+    let mut max = 0;
+    while let Some((val, depth)) = todo.pop() {
+        max = max.max(depth);
+        match val {
+            Value::Seq(_, items) => todo.extend(items.iter().map(|v| (v, depth + 1))),
+            Value::Dict(items) => todo.extend(items.iter().map(|(_, v)| (v, depth + 1))),
+            Value::Global(_, args) => todo.extend(args.iter().map(|v| (v, depth + 1))),
+            _ => (),
+        }
+    }
+    max
+}
+
+/// Counts the leaves.
+struct Leaves(usize);
+
+impl<'v, 'a: 'v> ValueVisitor<'v, 'a> for Leaves {
+    fn visit_leaf(&mut self, _path: &Path<'v>, _val: &'v Value<'a>) {
+        self.0 += 1;
+    }
+}
+
+#[test]
+fn deep_nesting_resolves() -> Result<()> {
+    let options = EvalOptions {
+        limits: EvalLimits::unlimited(),
+        ..Default::default()
+    };
+    let data = nested_refs(5000);
+    let ops = ops(&data);
+    let (values, memo) = evaluate_with_options(&ops, &options)?;
+    assert_eq!(depth(&values[0]), 5000);
+    // Nothing is shared, so the lists all got moved out of the memo.
+    assert!(memo.0.is_empty());
+    Ok(())
+}
+
+#[test]
+fn shared_refs_stay_in_memo() -> Result<()> {
+    // a = [[1]]; pickle.dumps([a, a], 2)
+    let ops = ops(b"\x80\x02]q\x00(]q\x01]q\x02K\x01aah\x01e.");
+    let (values, memo) = evaluate_with_options(&ops, &EvalOptions::default())?;
+    let list = |items| Value::Seq(SequenceType::List, items);
+    let a = list(vec![list(vec![Value::Int(1)])]);
+    assert_eq!(values[0], list(vec![a.clone(), a]));
+    assert_eq!(memo.0.keys().copied().collect::<Vec<_>>(), [1, 2]);
+
+    // a = []; a.append(a); pickle.dumps(a, 2)
+    let ops = common::ops(b"\x80\x02]q\x00h\x00a.");
+    let (values, memo) = evaluate_with_options(&ops, &EvalOptions::default())?;
+    assert_eq!(values[0], list(vec![Value::Ref(0)]));
+    assert!(memo.0.contains_key(&0));
+    Ok(())
+}
+
+#[test]
+fn nesting_at_limit() -> Result<()> {
+    let max = EvalLimits::default().max_nesting_depth;
+    let data = nested(max / 4, max % 4);
+    let ops = ops(&data);
+    let (values, _) = evaluate_with_options(&ops, &EvalOptions::default())?;
+    let val = &values[0];
+    assert_eq!(depth(val), max);
+
+    // Everything that doesn't clone still works at this depth.
+    assert!(!format!("{val}").is_empty());
+    assert!(!format!("{val:#}").is_empty());
+    let keys = HashSet::from([ValueKey::new(val, KeyMode::Python)]);
+    assert!(keys.contains(&ValueKey::new(val, KeyMode::Python)));
+    assert!(val.diff(val, &DiffOptions::default()).is_empty());
+    let mut leaves = Leaves(0);
+    leaves.visit_value(&Path::default(), val);
+    assert_eq!(leaves.0, max / 4 + 1);
+    #[cfg(feature = "serde")]
+    {
+        // serde_json has its own recursion limit for reading, so this
+        // only checks writing.
+        assert!(!serde_json::to_string(val)?.is_empty());
+
+        // Calls can't be deserialized, so use plain lists for that.
+        let data = nested(0, max);
+        let list_ops = common::ops(&data);
+        let (values, _) = evaluate_with_options(&list_ops, &EvalOptions::default())?;
+        let plain: serde_json::Value = repugnant_pickle::from_value(&values[0])?;
+        assert!(plain.is_array());
+    }
+    Ok(())
+}
+
+#[test]
+fn nesting_over_limit() {
+    let max = EvalLimits::default().max_nesting_depth;
+    let data = nested(max / 4, max % 4 + 1);
+    let ops = ops(&data);
+    let err = evaluate_with_options(&ops, &EvalOptions::default()).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<LimitExceeded>(),
+        Some(&LimitExceeded::NestingDepth(max))
+    );
+
+    // The limit doesn't apply if references don't get resolved.
+    let options = EvalOptions {
+        resolve_refs: false,
+        ..Default::default()
+    };
+    let (values, _) = evaluate_with_options(&ops, &options).unwrap();
+    assert!(matches!(values[0], Value::Seq(SequenceType::List, _)));
+}
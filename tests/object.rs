@@ -0,0 +1,117 @@
+mod common;
+
+use anyhow::Result;
+
+use repugnant_pickle::{EvalOptions, PyObject, Value};
+
+use common::{eval_one, object_model, ops, s};
+
+fn object(val: Value<'_>) -> PyObject<'_> {
+    match val {
+        Value::PyObject(obj) => *obj,
+        val => panic!("Expected an object, got {val:?}"),
+    }
+}
+
+#[test]
+fn object_state() -> Result<()> {
+    // class P:
+    //     def __init__(self): self.x = 1
+    // pickle.dumps(P(), 2)
+    let ops = ops(b"\x80\x02c__main__\nP\nq\x00)\x81q\x01}q\x02X\x01\x00\x00\x00xq\x03K\x01sb.");
+    let obj = object(eval_one(&ops, &object_model())?);
+    assert_eq!(obj.class_name(), Some(("__main__", "P")));
+    assert!(obj.args.is_empty());
+    assert_eq!(obj.attr("x"), Some(&Value::Int(1)));
+    assert_eq!(obj.slotstate, None);
+    Ok(())
+}
+
+#[test]
+fn object_from_reconstructor() -> Result<()> {
+    // The same P, but pickle.dumps(P(), 1)
+    let ops = ops(b"ccopy_reg\n_reconstructor\nq\x00(c__main__\nP\nq\x01c__builtin__\nobject\nq\x02Ntq\x03Rq\x04}q\x05X\x01\x00\x00\x00xq\x06K\x01sb.");
+    let obj = object(eval_one(&ops, &object_model())?);
+    assert_eq!(obj.class_name(), Some(("__main__", "P")));
+    assert!(obj.args.is_empty());
+    assert_eq!(obj.attr("x"), Some(&Value::Int(1)));
+    Ok(())
+}
+
+#[test]
+fn object_slotstate() -> Result<()> {
+    // class S:
+    //     __slots__ = ("a",)
+    //     def __init__(self): self.a = 1
+    // pickle.dumps(S(), 2)
+    let ops = ops(
+        b"\x80\x02c__main__\nS\nq\x00)\x81q\x01N}q\x02X\x01\x00\x00\x00aq\x03K\x01s\x86q\x04b.",
+    );
+    let obj = object(eval_one(&ops, &object_model())?);
+    assert_eq!(obj.state, None);
+    assert_eq!(
+        obj.slotstate,
+        Some(Value::Dict(vec![(s("a"), Value::Int(1))]))
+    );
+    assert_eq!(obj.attr("a"), Some(&Value::Int(1)));
+    Ok(())
+}
+
+#[test]
+fn object_list_items() -> Result<()> {
+    // class L(list): pass
+    // l = L([1, 2]); l.y = 3; pickle.dumps(l, 2)
+    let ops = ops(
+        b"\x80\x02c__main__\nL\nq\x00)\x81q\x01(K\x01K\x02e}q\x02X\x01\x00\x00\x00yq\x03K\x03sb.",
+    );
+    let obj = object(eval_one(&ops, &object_model())?);
+    assert_eq!(obj.list_items, vec![Value::Int(1), Value::Int(2)]);
+    assert_eq!(obj.attr("y"), Some(&Value::Int(3)));
+    Ok(())
+}
+
+#[test]
+fn object_dict_items() -> Result<()> {
+    // class D(dict): pass
+    // pickle.dumps(D(a=1), 2)
+    let ops = ops(b"\x80\x02c__main__\nD\nq\x00)\x81q\x01X\x01\x00\x00\x00aq\x02K\x01s.");
+    let val = eval_one(&ops, &object_model())?;
+    assert_eq!(val.as_dict_pairs(), Some(&[(s("a"), Value::Int(1))][..]));
+    let obj = object(val);
+    assert_eq!(obj.dict_items, vec![(s("a"), Value::Int(1))]);
+    Ok(())
+}
+
+#[test]
+fn object_newobj_ex() -> Result<()> {
+    // class K:
+    //     def __getnewargs_ex__(self): return ((1,), {"k": 2})
+    // pickle.dumps(K.__new__(K), 4)
+    let ops = ops(b"\x80\x04\x95!\x00\x00\x00\x00\x00\x00\x00\x8c\x08__main__\x94\x8c\x01K\x94\x93\x94K\x01\x85\x94}\x94\x8c\x01k\x94K\x02s\x92\x94.");
+    let obj = object(eval_one(&ops, &object_model())?);
+    assert_eq!(obj.class_name(), Some(("__main__", "K")));
+    assert_eq!(obj.args, vec![Value::Int(1)]);
+    assert_eq!(obj.kwargs, vec![(s("k"), Value::Int(2))]);
+    Ok(())
+}
+
+#[test]
+fn object_from_reduce() -> Result<()> {
+    // class R:
+    //     def __reduce__(self): return (R, (1, 2))
+    // pickle.dumps(R(), 2)
+    let data = b"\x80\x02c__main__\nR\nq\x00K\x01K\x02\x86q\x01Rq\x02.";
+    let ops = ops(data);
+    let obj = object(eval_one(&ops, &object_model())?);
+    assert_eq!(obj.class_name(), Some(("__main__", "R")));
+    assert_eq!(obj.args, vec![Value::Int(1), Value::Int(2)]);
+
+    // Without the object model it's just a call.
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert!(matches!(val, Value::Global(..)), "{val:?}");
+    assert_eq!(
+        val.as_call("__main__", "R"),
+        Some(&[Value::Int(1), Value::Int(2)][..])
+    );
+    Ok(())
+}
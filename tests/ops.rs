@@ -0,0 +1,70 @@
+mod common;
+
+use anyhow::Result;
+
+use num_bigint::BigInt;
+use repugnant_pickle::{EvalOptions, SequenceType, Value};
+
+use common::{eval_one, ops};
+
+fn eval(data: &[u8]) -> Result<Value<'static>> {
+    let ops = ops(data);
+    // Scalars don't borrow anything from the ops.
+    Ok(match eval_one(&ops, &EvalOptions::default())? {
+        Value::Int(i) => Value::Int(i),
+        Value::BigInt(i) => Value::BigInt(i),
+        Value::Float(f) => Value::Float(f),
+        val => panic!("Unexpected value {val:?}"),
+    })
+}
+
+#[test]
+fn binfloat_is_big_endian() -> Result<()> {
+    // pickle.dumps(1.5, 1)
+    assert_eq!(eval(b"G?\xf8\x00\x00\x00\x00\x00\x00.")?, Value::Float(1.5));
+    Ok(())
+}
+
+#[test]
+fn long1_sign() -> Result<()> {
+    // pickle.dumps(-2**47, 2)
+    assert_eq!(
+        eval(b"\x80\x02\x8a\x06\x00\x00\x00\x00\x00\x80.")?,
+        Value::Int(-(1 << 47))
+    );
+    // pickle.dumps(2**46, 2)
+    assert_eq!(
+        eval(b"\x80\x02\x8a\x06\x00\x00\x00\x00\x00@.")?,
+        Value::Int(1 << 46)
+    );
+    Ok(())
+}
+
+#[test]
+fn long1_too_big_for_i64() -> Result<()> {
+    // pickle.dumps(-2**64, 2)
+    assert_eq!(
+        eval(b"\x80\x02\x8a\x09\x00\x00\x00\x00\x00\x00\x00\x00\xff.")?,
+        Value::BigInt(BigInt::from(-1) << 64)
+    );
+    Ok(())
+}
+
+#[test]
+fn empty_long1_is_zero() -> Result<()> {
+    // Python 2's pickle.dumps(0L, 2)
+    assert_eq!(eval(b"\x80\x02\x8a\x00.")?, Value::Int(0));
+    Ok(())
+}
+
+#[test]
+fn frame_is_skipped() -> Result<()> {
+    // pickle.dumps([1, 2], 4)
+    let ops = ops(b"\x80\x04\x95\x09\x00\x00\x00\x00\x00\x00\x00]\x94(K\x01K\x02e.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        val,
+        Value::Seq(SequenceType::List, vec![Value::Int(1), Value::Int(2)])
+    );
+    Ok(())
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use anyhow::Result;
+
+use repugnant_pickle::{evaluate_with_options, EvalOptions, Path, PathKey, Value, ValueView};
+
+use common::{eval_one, ops, s};
+
+// pickle.dumps({"a": {"b": [10, 20, {"c": 1}]}, 3: "three"}, 2)
+const NESTED: &[u8] = b"\x80\x02}q\x00(X\x01\x00\x00\x00aq\x01}q\x02X\x01\x00\x00\x00bq\x03]q\x04(K\nK\x14}q\x05X\x01\x00\x00\x00cq\x06K\x01sesK\x03X\x05\x00\x00\x00threeq\x07u.";
+
+#[test]
+fn path_lookups() -> Result<()> {
+    let ops = ops(NESTED);
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(val.at("a/b/2/c"), Some(&Value::Int(1)));
+    assert_eq!(val.at("a/b/-1/c"), Some(&Value::Int(1)));
+    assert_eq!(val.at("/a//b/0"), Some(&Value::Int(10)));
+    assert_eq!(val.at("a/b/3"), None);
+    assert_eq!(val.at("a/b/-4"), None);
+    assert_eq!(val.at("a/x"), None);
+    assert_eq!(val.at(Path::default()), Some(&val));
+    // A string that looks like a number finds int keys too.
+    assert_eq!(val.at("3"), Some(&s("three")));
+    assert_eq!(val.at([3]), Some(&s("three")));
+    assert_eq!(val.get(&Value::Int(3)), Some(&s("three")));
+    assert_eq!(val.get(&s("3")), None);
+
+    let b = val.at(["a", "b"]).unwrap();
+    assert_eq!(b.index(1), Some(&Value::Int(20)));
+    assert_eq!(b.index(3), None);
+    assert_eq!(b.index(usize::MAX), None);
+    assert_eq!(PathKey::from(usize::MAX), PathKey::Int(i64::MAX));
+    assert_eq!(
+        val.at([PathKey::from("a"), "b".into(), usize::MAX.into()]),
+        None
+    );
+    Ok(())
+}
+
+#[test]
+fn path_display() {
+    let path = Path::from("a/b").join(3).join("c/d");
+    assert_eq!(path.to_string(), "a/b/3/c/d");
+    assert_eq!(path.0.len(), 4);
+}
+
+#[test]
+fn path_ordered_dict() -> Result<()> {
+    // pickle.dumps(collections.OrderedDict([("k", [1, 2])]), 2)
+    let ops = ops(b"\x80\x02ccollections\nOrderedDict\nq\x00)Rq\x01X\x01\x00\x00\x00kq\x02]q\x03(K\x01K\x02es.");
+    for (collections, object_model) in [(true, false), (false, false), (false, true)] {
+        let options = EvalOptions {
+            collections,
+            object_model,
+            ..Default::default()
+        };
+        let val = eval_one(&ops, &options)?;
+        assert_eq!(val.at("k/1"), Some(&Value::Int(2)), "{val:?}");
+    }
+    Ok(())
+}
+
+#[test]
+fn path_attrs() -> Result<()> {
+    // class S:
+    //     __slots__ = ("x",)
+    //     def __init__(self): self.x = 5
+    // pickle.dumps(S(), 2)
+    let ops = ops(
+        b"\x80\x02c__main__\nS\nq\x00)\x81q\x01N}q\x02X\x01\x00\x00\x00xq\x03K\x05s\x86q\x04b.",
+    );
+    for object_model in [false, true] {
+        let options = EvalOptions {
+            object_model,
+            ..Default::default()
+        };
+        let val = eval_one(&ops, &options)?;
+        assert_eq!(val.attr("x"), Some(&Value::Int(5)));
+        assert_eq!(val.at("x"), Some(&Value::Int(5)));
+        assert_eq!(val.attr("y"), None);
+    }
+    Ok(())
+}
+
+#[test]
+fn path_call_args() -> Result<()> {
+    // class F:
+    //     def __reduce__(self): return (print, (1,))
+    // pickle.dumps(F(), 2)
+    let ops = ops(b"\x80\x02c__builtin__\nprint\nq\x00K\x01\x85q\x01Rq\x02.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(val.index(0), Some(&Value::Int(1)));
+
+    // class P(pickle.Pickler):
+    //     def persistent_id(self, obj):
+    //         return ("storage", "0") if obj == "X" else None
+    // P(f, 2).dump(["X"])
+    let ops = common::ops(
+        b"\x80\x02]q\x00X\x07\x00\x00\x00storageq\x01X\x01\x00\x00\x000q\x02\x86q\x03Qa.",
+    );
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(val.at("0/0/0"), Some(&s("storage")));
+    Ok(())
+}
+
+#[test]
+fn path_refs() -> Result<()> {
+    let ops = ops(NESTED);
+    let options = EvalOptions {
+        resolve_refs: false,
+        ..Default::default()
+    };
+    let (values, memo) = evaluate_with_options(&ops, &options)?;
+    // Lookups on values don't follow references, views do.
+    assert_eq!(values[0].at("a/b"), None);
+    let view = ValueView::new(&values[0], &memo);
+    assert_eq!(view.at("a/b/2/c").unwrap().fixed().as_ref(), &Value::Int(1));
+    assert_eq!(view.at("3").unwrap().fixed().as_ref(), &s("three"));
+    assert!(view.at("a/b").unwrap().index(usize::MAX).is_none());
+    Ok(())
+}
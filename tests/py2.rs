@@ -0,0 +1,192 @@
+mod common;
+
+use std::borrow::Cow;
+
+use anyhow::Result;
+use num_bigint::BigInt;
+
+use repugnant_pickle::{
+    evaluate_with_options, ops::PickleOp, value::fix_value, EvalMode, EvalOptions, SequenceType,
+    StringEncoding, Value,
+};
+
+use common::{eval_one, ops, s};
+
+// Python 2: pickle.dumps('ab\xff', 0)
+const STRING: &[u8] = b"S'ab\\xff'\np0\n.";
+// Python 2: pickle.dumps('ab\xff', 2)
+const SHORT_BINSTRING: &[u8] = b"\x80\x02U\x03ab\xffq\x00.";
+
+fn encoding(string_encoding: StringEncoding) -> EvalOptions {
+    EvalOptions {
+        string_encoding,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn string_default() -> Result<()> {
+    for data in [STRING, SHORT_BINSTRING] {
+        let ops = ops(data);
+        let val = eval_one(&ops, &EvalOptions::default())?;
+        assert_eq!(val, Value::Bytes(Cow::Borrowed(b"ab\xff")));
+    }
+
+    // Python 2: pickle.dumps('ab', 2)
+    let ops = common::ops(b"\x80\x02U\x02abq\x00.");
+    assert_eq!(eval_one(&ops, &EvalOptions::default())?, s("ab"));
+    Ok(())
+}
+
+#[test]
+fn string_encodings() -> Result<()> {
+    for data in [STRING, SHORT_BINSTRING] {
+        let ops = ops(data);
+        assert_eq!(
+            eval_one(&ops, &encoding(StringEncoding::Bytes))?,
+            Value::Bytes(Cow::Borrowed(b"ab\xff"))
+        );
+        assert_eq!(
+            eval_one(&ops, &encoding(StringEncoding::Latin1))?,
+            s("ab\u{ff}")
+        );
+        let err = eval_one(&ops, &encoding(StringEncoding::Ascii)).unwrap_err();
+        assert_eq!(err.to_string(), "Could not decode Python 2 string as ASCII");
+        let err = eval_one(&ops, &encoding(StringEncoding::Utf8)).unwrap_err();
+        assert_eq!(err.to_string(), "Could not decode Python 2 string as UTF8");
+    }
+    Ok(())
+}
+
+#[test]
+fn string_unescape() -> Result<()> {
+    // Python 2: pickle.dumps("a\n'b", 0)
+    let ops = ops(b"S\"a\\n'b\"\np0\n.");
+    for enc in [
+        StringEncoding::Ascii,
+        StringEncoding::Utf8,
+        StringEncoding::Latin1,
+    ] {
+        assert_eq!(eval_one(&ops, &encoding(enc))?, s("a\n'b"));
+    }
+    assert_eq!(
+        eval_one(&ops, &encoding(StringEncoding::Bytes))?,
+        Value::Bytes(Cow::Borrowed(b"a\n'b"))
+    );
+
+    let ops = common::ops(b"Sab\np0\n.");
+    let err = eval_one(&ops, &encoding(StringEncoding::Bytes)).unwrap_err();
+    assert_eq!(err.to_string(), "Bad quoting for STRING");
+    Ok(())
+}
+
+#[test]
+fn fix_imports() -> Result<()> {
+    // Python 2: class Foo(object): pass
+    // pickle.dumps(Foo(), 0)
+    let data = b"ccopy_reg\n_reconstructor\np0\n(c__main__\nFoo\np1\nc__builtin__\nobject\np2\nNtp3\nRp4\n.";
+    let ops = ops(data);
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    let args = val.as_call("copyreg", "_reconstructor").expect("Not fixed");
+    assert_eq!(args[1], Value::class("builtins", "object"));
+
+    let options = EvalOptions {
+        fix_imports: false,
+        ..Default::default()
+    };
+    let val = eval_one(&ops, &options)?;
+    let args = val
+        .as_call("copy_reg", "_reconstructor")
+        .expect("Fixed anyway");
+    assert_eq!(args[1], Value::class("__builtin__", "object"));
+
+    // Only protocols older than 3 get fixed, same as Python.
+    let ops = common::ops(b"\x80\x03ccopy_reg\n_reconstructor\nq\x00.");
+    assert_eq!(
+        eval_one(&ops, &EvalOptions::default())?,
+        Value::class("copy_reg", "_reconstructor")
+    );
+    Ok(())
+}
+
+#[test]
+fn protocol_0_numbers() -> Result<()> {
+    // Python 2: pickle.dumps([1, True, 123456789012345678901234567890, 1.5, -7, 5L], 0)
+    let ops = ops(b"(lp0\nI1\naI01\naL123456789012345678901234567890L\naF1.5\naI-7\naL5L\na.");
+    let big: BigInt = "123456789012345678901234567890".parse()?;
+    assert_eq!(
+        eval_one(&ops, &EvalOptions::default())?,
+        Value::Seq(
+            SequenceType::List,
+            vec![
+                Value::Int(1),
+                Value::Bool(true),
+                Value::BigInt(big),
+                Value::Float(1.5),
+                Value::Int(-7),
+                Value::Int(5),
+            ]
+        )
+    );
+
+    // Not something Python would make, but it shouldn't be mistaken for a number.
+    let ops = common::ops(b"Fnope\n.");
+    let (values, _) = evaluate_with_options(&ops, &EvalOptions::default())?;
+    assert_eq!(values, [Value::RawNum(PickleOp::FLOAT("nope"))]);
+    Ok(())
+}
+
+#[test]
+fn protocol_0_unicode() -> Result<()> {
+    // pickle.dumps({"path": "C:\\tmp", "h\xe9 \u20ac \U0001f600": "a\nb\r\x00\x1a", "x": 1}, 0)
+    let ops = ops(b"(dp0\nVpath\np1\nVC:\\u005ctmp\np2\nsVh\xe9 \\u20ac \\U0001f600\np3\nVa\\u000ab\\u000d\\u0000\\u001a\np4\nsVx\np5\nI1\ns.");
+    let strict = EvalOptions {
+        mode: EvalMode::Strict,
+        ..Default::default()
+    };
+    for options in [EvalOptions::default(), strict] {
+        let val = eval_one(&ops, &options)?;
+        assert_eq!(
+            val,
+            Value::Dict(vec![
+                (s("path"), s("C:\\tmp")),
+                (s("h\u{e9} \u{20ac} \u{1f600}"), s("a\nb\r\0\x1a")),
+                (s("x"), Value::Int(1)),
+            ])
+        );
+        assert_eq!(val.dict_get_str("path"), Some(&s("C:\\tmp")));
+    }
+
+    // Python 2: pickle.dumps({u"h\xe9 \u20ac": u"C:\\tmp\n", "k": "v\\n\xff"}, 0)
+    let ops = common::ops(
+        b"(dp0\nVh\xe9 \\u20ac\np1\nVC:\\u005ctmp\\u000a\np2\nsS'k'\np3\nS'v\\\\n\\xff'\np4\ns.",
+    );
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        val,
+        Value::Dict(vec![
+            (s("h\u{e9} \u{20ac}"), s("C:\\tmp\n")),
+            (s("k"), Value::Bytes(Cow::Borrowed(b"v\\n\xff"))),
+        ])
+    );
+    Ok(())
+}
+
+#[test]
+fn protocol_0_fix_value() -> Result<()> {
+    let raw = |op| Value::Raw(Cow::Owned(op));
+    assert_eq!(fix_value(raw(PickleOp::UNICODE(b"path")))?, s("path"));
+    assert_eq!(fix_value(raw(PickleOp::STRING("'path'")))?, s("path"));
+    assert_eq!(
+        fix_value(raw(PickleOp::UNICODE(b"C:\\u005ctmp")))?,
+        s("C:\\tmp")
+    );
+    assert_eq!(fix_value(raw(PickleOp::UNICODE(b"h\xe9")))?, s("h\u{e9}"));
+
+    // Bad escapes are left alone.
+    let bad = raw(PickleOp::UNICODE(b"\\u12"));
+    assert_eq!(fix_value(bad.clone())?, bad);
+    let bad = raw(PickleOp::STRING("'\\x1'"));
+    assert_eq!(fix_value(bad.clone())?, bad);
+    Ok(())
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use anyhow::Result;
+
+use repugnant_pickle::{EvalOptions, Query, Value};
+
+use common::{eval_one, ops};
+
+// See data/state_dict.py.
+const STATE_DICT: &[u8] = include_bytes!("data/state_dict.pkl");
+
+/// The matches as `path => value`.
+fn run(val: &Value<'_>, query: &str) -> Result<Vec<String>> {
+    Ok(val
+        .query(query)?
+        .iter()
+        .map(|m| format!("{} => {}", m.path, m.value))
+        .collect())
+}
+
+#[test]
+fn query_select() -> Result<()> {
+    let ops = ops(STATE_DICT);
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    let tensors = val.query(r#"..|select(class == "torch._utils._rebuild_tensor_v2")"#)?;
+    let paths = tensors
+        .iter()
+        .map(|m| m.path.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        ["emb.weight", "emb.bias", "head.weight", "head.bias"]
+    );
+    assert_eq!(val.query(".*")?, tensors);
+    assert_eq!(val.query(".[*]")?, tensors);
+    assert_eq!(val.query(".[]")?, tensors);
+
+    assert_eq!(
+        run(
+            &val,
+            r#"..|select(value == "cpu" and key == 3 or value == 12)"#
+        )?,
+        [
+            "emb.weight/0/0/3 => 'cpu'",
+            "emb.weight/0/0/4 => 12",
+            "emb.bias/0/0/3 => 'cpu'",
+            "head.weight/0/0/3 => 'cpu'",
+            "head.bias/0/0/3 => 'cpu'",
+        ]
+    );
+    assert_eq!(
+        run(
+            &val,
+            r#"..|select(type == "tuple" and len == 1 and not (key == 3))|.[0]"#
+        )?,
+        ["emb.bias/2/0 => 4", "head.bias/2/0 => 4"]
+    );
+    assert_eq!(
+        run(&val, ".*|.[0][0]|.[4]|select(value >= 8)")?,
+        [
+            "emb.weight/0/0/4 => 12",
+            "head.weight/0/0/4 => 8",
+            "head.bias/0/0/4 => 8"
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn query_keys() -> Result<()> {
+    let ops = ops(STATE_DICT);
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        run(&val, r#"."emb.bias"[0][0][2]"#)?,
+        ["emb.bias/0/0/2 => '1'"]
+    );
+    assert_eq!(
+        run(&val, r#"["head.bias"] | [-1]"#)?,
+        ["head.bias/-1 => collections.OrderedDict()"]
+    );
+    assert_eq!(run(&val, ".nope")?, Vec::<String>::new());
+    assert_eq!(run(&val, ".")?.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn query_project() -> Result<()> {
+    let ops = ops(STATE_DICT);
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        run(
+            &val,
+            r#".*|select(key =~ "*.weight")|{shape: .[2], off: .[1], nope}"#
+        )?,
+        [
+            "emb.weight => {'shape': (3, 4), 'off': 0, 'nope': None}",
+            "head.weight => {'shape': (2, 2), 'off': 0, 'nope': None}",
+        ]
+    );
+    // More than one result makes a list.
+    assert_eq!(
+        run(&val, r#"."emb.bias"|{dims: .[2][*]}"#)?,
+        ["emb.bias => {'dims': 4}"]
+    );
+    assert_eq!(
+        run(&val, r#"."emb.weight"|{dims: .[2][*]}"#)?,
+        ["emb.weight => {'dims': [3, 4]}"]
+    );
+    Ok(())
+}
+
+#[test]
+fn query_parse_errors() {
+    for (query, offset) in [("select(", 0), ("[1", 0), (".a b", 3)] {
+        let err = Query::new(query).unwrap_err().to_string();
+        assert!(
+            err.starts_with(&format!("Query parse error at offset {offset}:")),
+            "{query}: {err}"
+        );
+    }
+    assert!(Query::new(r#"select(type == "dict" or (len < 2 and class =~ "a?c*"))"#).is_ok());
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use anyhow::Result;
+
+use repugnant_pickle::{EvalOptions, ReprOptions, SequenceType, Value};
+
+use common::{eval_one, ops, s};
+
+// pickle.dumps([1, -2.5, 1e20, 1e-5, 1.0, float("inf"), "it's", "a\"b'c\n\x01",
+//               b"\x00'\xff", (1,), (), {1: "a"}, set(), {3}, frozenset(),
+//               frozenset([4]), 2**70, 1+2j, complex(0, -1), True, None, [[]],
+//               slice(1, None, 2), range(3)], 4)
+const LITERALS: &[u8] = b"\x80\x04\x95\xeb\x00\x00\x00\x00\x00\x00\x00]\x94(K\x01G\xc0\x04\x00\x00\x00\x00\x00\x00GD\x15\xaf\x1dx\xb5\x8c@G>\xe4\xf8\xb5\x88\xe3h\xf1G?\xf0\x00\x00\x00\x00\x00\x00G\x7f\xf0\x00\x00\x00\x00\x00\x00\x8c\x04it's\x94\x8c\x07a\"b'c\n\x01\x94C\x03\x00'\xff\x94K\x01\x85\x94)}\x94K\x01\x8c\x01a\x94s\x8f\x94\x8f\x94(K\x03\x90(\x91\x94(K\x04\x91\x94\x8a\x09\x00\x00\x00\x00\x00\x00\x00\x00@\x8c\x08builtins\x94\x8c\x07complex\x94\x93\x94G?\xf0\x00\x00\x00\x00\x00\x00G@\x00\x00\x00\x00\x00\x00\x00\x86\x94R\x94h\x0dG\x00\x00\x00\x00\x00\x00\x00\x00G\xbf\xf0\x00\x00\x00\x00\x00\x00\x86\x94R\x94\x88N]\x94]\x94ah\x0b\x8c\x05slice\x94\x93\x94K\x01NK\x02\x87\x94R\x94h\x0b\x8c\x05range\x94\x93\x94K\x00K\x03K\x01\x87\x94R\x94e.";
+
+#[test]
+fn repr_literals() -> Result<()> {
+    let ops = ops(LITERALS);
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    // What Python's repr gives, apart from the step of the range.
+    assert_eq!(
+        val.to_string(),
+        r#"[1, -2.5, 1e+20, 1e-05, 1.0, inf, "it's", 'a"b\'c\n\x01', b"\x00'\xff", (1,), (), {1: 'a'}, set(), {3}, frozenset(), frozenset({4}), 1180591620717411303424, (1+2j), -1j, True, None, [[]], slice(1, None, 2), range(0, 3, 1)]"#
+    );
+    assert_eq!(
+        format!("{val:#}"),
+        r#"[
+    1,
+    -2.5,
+    1e+20,
+    1e-05,
+    1.0,
+    inf,
+    "it's",
+    'a"b\'c\n\x01',
+    b"\x00'\xff",
+    (1,),
+    (),
+    {1: 'a'},
+    set(),
+    {3},
+    frozenset(),
+    frozenset({4}),
+    1180591620717411303424,
+    (1+2j),
+    -1j,
+    True,
+    None,
+    [[]],
+    slice(1, None, 2),
+    range(0, 3, 1),
+]"#
+    );
+    Ok(())
+}
+
+#[test]
+fn repr_collections() -> Result<()> {
+    // pickle.dumps([collections.OrderedDict(a=1), collections.OrderedDict(),
+    //               collections.defaultdict(list), collections.Counter("aab"),
+    //               collections.deque([1], maxlen=3), collections.deque(),
+    //               bytearray(b"x")], 4)
+    let ops = ops(b"\x80\x04\x95\xb1\x00\x00\x00\x00\x00\x00\x00]\x94(\x8c\x0bcollections\x94\x8c\x0bOrderedDict\x94\x93\x94)R\x94\x8c\x01a\x94K\x01sh\x03)R\x94h\x01\x8c\x0bdefaultdict\x94\x93\x94\x8c\x08builtins\x94\x8c\x04list\x94\x93\x94\x85\x94R\x94h\x01\x8c\x07Counter\x94\x93\x94}\x94(\x8c\x01a\x94K\x02\x8c\x01b\x94K\x01u\x85\x94R\x94h\x01\x8c\x05deque\x94\x93\x94)K\x03\x86\x94R\x94K\x01ah\x16)R\x94h\x09\x8c\x09bytearray\x94\x93\x94C\x01x\x94\x85\x94R\x94e.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(
+        val.to_string(),
+        "[collections.OrderedDict({'a': 1}), collections.OrderedDict(), \
+         collections.defaultdict(builtins.list, {}), collections.Counter({'a': 2, 'b': 1}), \
+         collections.deque([1], maxlen=3), collections.deque([]), bytearray(b'x')]"
+    );
+    Ok(())
+}
+
+#[test]
+fn repr_objects() -> Result<()> {
+    // class P:
+    //     def __init__(self): self.x = 1; self.y = 2.5
+    // pickle.dumps(P(), 2)
+    let ops = ops(b"\x80\x02c__main__\nP\nq\x00)\x81q\x01}q\x02(X\x01\x00\x00\x00xq\x03K\x01X\x01\x00\x00\x00yq\x04G@\x04\x00\x00\x00\x00\x00\x00ub.");
+    let expected = "__main__.P().__setstate__({'x': 1, 'y': 2.5})";
+    for object_model in [false, true] {
+        let options = EvalOptions {
+            object_model,
+            ..Default::default()
+        };
+        assert_eq!(eval_one(&ops, &options)?.to_string(), expected);
+    }
+
+    // Without resolving, you see references to the memo and raw ops.
+    let options = EvalOptions {
+        resolve_refs: false,
+        ..Default::default()
+    };
+    assert_eq!(
+        eval_one(&ops, &options)?.to_string(),
+        "__main__.P().__setstate__({memo[3]: <BININT1(1)>, memo[4]: <BINFLOAT(2.5)>})"
+    );
+
+    // pickle.dumps(collections.OrderedDict, 2) with the class in the
+    // extension registry.
+    let ops = common::ops(b"\x80\x02\x82\xf0.");
+    assert_eq!(
+        eval_one(&ops, &EvalOptions::default())?.to_string(),
+        "<EXT1(240)>"
+    );
+    Ok(())
+}
+
+#[test]
+fn repr_truncated() {
+    let val = Value::Seq(
+        SequenceType::List,
+        vec![
+            s("abcdef"),
+            Value::Bytes(b"abcdef".as_slice().into()),
+            Value::Dict(vec![(s("a"), Value::Int(1)), (s("b"), Value::Int(2))]),
+            Value::Int(4),
+        ],
+    );
+    let options = ReprOptions {
+        max_items: Some(3),
+        max_len: Some(3),
+        ..ReprOptions::exact()
+    };
+    assert_eq!(
+        val.repr(options).to_string(),
+        "['abc'..., b'abc'..., {'a': 1, 'b': 2}, ...]"
+    );
+    let options = ReprOptions {
+        max_items: Some(1),
+        ..ReprOptions::exact()
+    };
+    assert_eq!(val.repr(options).to_string(), "['abcdef', ...]");
+}
+
+#[test]
+fn repr_layout() {
+    let ints = |n| Value::Seq(SequenceType::List, (0..n).map(Value::Int).collect());
+    let val = Value::Dict(vec![(s("short"), ints(3)), (s("long"), ints(12))]);
+    let options = ReprOptions {
+        width: Some(30),
+        indent: 2,
+        ..Default::default()
+    };
+    // Things get split when they don't fit, and numbers get packed in.
+    assert_eq!(
+        val.repr(options).to_string(),
+        "{
+  'short': [0, 1, 2],
+  'long': [
+    0, 1, 2, 3, 4, 5, 6, 7, 8,
+    9, 10, 11,
+  ],
+}"
+    );
+    let options = ReprOptions {
+        width: Some(100),
+        ..Default::default()
+    };
+    assert_eq!(
+        val.repr(options).to_string(),
+        "{'short': [0, 1, 2], 'long': [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]}"
+    );
+}
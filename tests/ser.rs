@@ -0,0 +1,280 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use std::borrow::Cow;
+
+use anyhow::Result;
+use num_bigint::BigInt;
+
+use repugnant_pickle::value::fix_value;
+use repugnant_pickle::{
+    ops::PickleOp, ArrayData, Collection, CollectionKind, EvalOptions, PyArray, PyObject,
+    SequenceType, Value,
+};
+
+use common::{eval_one, ops, s};
+
+fn list<'a>(items: Vec<Value<'a>>) -> Value<'a> {
+    Value::Seq(SequenceType::List, items)
+}
+
+fn raw(op: PickleOp<'_>) -> Value<'_> {
+    Value::Raw(Cow::Owned(op))
+}
+
+fn coll<'a>(kind: CollectionKind, value: Value<'a>, extra: Value<'a>) -> Value<'a> {
+    Value::Collection(Box::new(Collection {
+        kind,
+        class: Value::class("collections", kind.name()),
+        value,
+        extra,
+    }))
+}
+
+fn array<'a>(typecode: char, data: ArrayData) -> Value<'a> {
+    Value::Array(Box::new(PyArray { typecode, data }))
+}
+
+/// At least one of every kind of value, with the different ways they
+/// can be written.
+fn every_value<'a>() -> Vec<Value<'a>> {
+    let pair = |k: Value<'a>, v| (k, v);
+    let call = || Box::new(Value::class("__main__", "f"));
+    let mut obj = PyObject::new(Value::class("__main__", "C"), vec![Value::Int(1)]);
+    obj.kwargs = vec![pair(s("k"), Value::Int(2))];
+    obj.state = Some(Value::Dict(vec![pair(s("a"), Value::None)]));
+    obj.slotstate = Some(Value::Dict(vec![pair(s("b"), Value::Bool(false))]));
+    obj.list_items = vec![Value::Int(3)];
+    obj.dict_items = vec![pair(Value::Int(4), s("x"))];
+    vec![
+        Value::None,
+        Value::Bool(true),
+        Value::Int(-5),
+        Value::Int(i64::MAX),
+        Value::BigInt(BigInt::from(1) << 100u32),
+        Value::BigInt(-(BigInt::from(1) << 70u32)),
+        Value::Float(1.5),
+        Value::Float(f64::INFINITY),
+        Value::Float(f64::NEG_INFINITY),
+        s("hello \"there\" \u{ff}"),
+        s("$not a tag"),
+        Value::Bytes(Cow::Borrowed(b"\x00\xff")),
+        Value::ByteArray(Cow::Borrowed(b"ab")),
+        Value::Complex(1.0, f64::INFINITY),
+        list(vec![Value::Int(1), list(vec![])]),
+        Value::Seq(SequenceType::Tuple, vec![Value::Int(1)]),
+        Value::Seq(SequenceType::Set, vec![s("a")]),
+        Value::Seq(SequenceType::FrozenSet, vec![]),
+        Value::Dict(vec![]),
+        Value::Dict(vec![pair(s("a"), Value::Int(1)), pair(s("b"), Value::None)]),
+        Value::Dict(vec![pair(Value::Int(1), s("a"))]),
+        Value::Dict(vec![pair(s("$tuple"), Value::Int(1))]),
+        Value::Dict(vec![
+            pair(s("a"), Value::Int(1)),
+            pair(s("a"), Value::Int(2)),
+        ]),
+        Value::Slice(
+            Box::new(Value::Int(1)),
+            Box::new(Value::None),
+            Box::new(Value::Int(2)),
+        ),
+        Value::Range(
+            Box::new(Value::Int(0)),
+            Box::new(Value::Int(10)),
+            Box::new(Value::Int(3)),
+        ),
+        Value::class("collections", "OrderedDict"),
+        Value::Global(call(), vec![Value::Seq(SequenceType::Tuple, vec![])]),
+        Value::App(call(), vec![Value::Int(1)]),
+        Value::Object(call(), vec![]),
+        Value::Build(call(), Box::new(Value::Dict(vec![]))),
+        Value::PersId(Box::new(Value::Seq(
+            SequenceType::Tuple,
+            vec![s("storage")],
+        ))),
+        Value::Ref(7),
+        Value::PyObject(Box::new(obj)),
+        Value::PyObject(Box::new(PyObject::new(
+            Value::class("__main__", "D"),
+            vec![],
+        ))),
+        coll(
+            CollectionKind::OrderedDict,
+            Value::Dict(vec![pair(s("a"), Value::Int(1))]),
+            Value::None,
+        ),
+        coll(
+            CollectionKind::DefaultDict,
+            Value::Dict(vec![]),
+            Value::class("builtins", "list"),
+        ),
+        coll(
+            CollectionKind::Counter,
+            Value::Dict(vec![pair(s("a"), Value::Int(2))]),
+            Value::None,
+        ),
+        coll(CollectionKind::Deque, list(vec![]), Value::Int(5)),
+        coll(
+            CollectionKind::NamedTuple,
+            Value::Seq(SequenceType::Tuple, vec![Value::Int(1)]),
+            Value::None,
+        ),
+        Value::Enum {
+            class: Box::new(Value::class("__main__", "Color")),
+            value: Box::new(Value::Int(1)),
+            name: Some(Cow::Borrowed("RED")),
+        },
+        Value::Enum {
+            class: Box::new(Value::class("__main__", "Color")),
+            value: Box::new(s("x")),
+            name: None,
+        },
+        array('b', ArrayData::I8(vec![-1, 2])),
+        array('B', ArrayData::U8(vec![255])),
+        array('h', ArrayData::I16(vec![-300])),
+        array('H', ArrayData::U16(vec![60000])),
+        array('i', ArrayData::I32(vec![i32::MIN])),
+        array('I', ArrayData::U32(vec![u32::MAX])),
+        array('q', ArrayData::I64(vec![i64::MIN])),
+        array('Q', ArrayData::U64(vec![u64::MAX])),
+        array('f', ArrayData::F32(vec![0.5, f32::INFINITY])),
+        array('d', ArrayData::F64(vec![])),
+        array('u', ArrayData::Unicode("h\u{e9}".to_string())),
+        raw(PickleOp::MARK),
+        raw(PickleOp::GLOBAL("__main__", "f")),
+        raw(PickleOp::PERSID("0")),
+        raw(PickleOp::FRAME(u64::MAX)),
+        Value::RawNum(PickleOp::INT("nope")),
+        Value::RawNum(PickleOp::FLOAT("1.5.5")),
+    ]
+}
+
+#[test]
+fn json_round_trip() -> Result<()> {
+    let json: Vec<String> = every_value()
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<_, _>>()?;
+    for (json, val) in json.iter().zip(every_value()) {
+        assert_eq!(serde_json::from_str::<Value>(json)?, val, "{json}");
+    }
+
+    // NaN isn't equal to itself.
+    let json = serde_json::to_string(&Value::Float(f64::NAN))?;
+    assert_eq!(json, r#"{"$float":"nan"}"#);
+    let val: Value = serde_json::from_str(&json)?;
+    assert!(matches!(val, Value::Float(f) if f.is_nan()));
+
+    let json = serde_json::to_string(&raw(PickleOp::BINFLOAT(f64::NAN)))?;
+    assert_eq!(json, r#"{"$float":"nan"}"#);
+    Ok(())
+}
+
+/// Raw ops that stand for a value, and the value they come back as.
+fn raw_fixed<'a>() -> Vec<(Value<'a>, Value<'a>)> {
+    let bytes = |b: &'a [u8]| Value::Bytes(Cow::Borrowed(b));
+    vec![
+        (raw(PickleOp::BININT1(3)), Value::Int(3)),
+        (raw(PickleOp::BINUNICODE("abc")), s("abc")),
+        (
+            raw(PickleOp::UNICODE(b"C:\\u005ctmp\xe9")),
+            s("C:\\tmp\u{e9}"),
+        ),
+        (raw(PickleOp::STRING("'a\\nb'")), s("a\nb")),
+        (raw(PickleOp::BINFLOAT(2.5)), Value::Float(2.5)),
+        (
+            raw(PickleOp::BINFLOAT(f64::NEG_INFINITY)),
+            Value::Float(f64::NEG_INFINITY),
+        ),
+        (raw(PickleOp::BINBYTES(b"\x00\x01")), bytes(b"\x00\x01")),
+        (raw(PickleOp::SHORT_BINBYTES(b"a")), bytes(b"a")),
+        (raw(PickleOp::BINBYTES8(b"")), bytes(b"")),
+        (raw(PickleOp::SHORT_BINSTRING(b"ab")), s("ab")),
+        (raw(PickleOp::BINSTRING(b"\xff")), bytes(b"\xff")),
+        (raw(PickleOp::LONG1(b"\xff")), Value::Int(-1)),
+        (
+            raw(PickleOp::LONG4(b"\x00\x00\x00\x00\x00\x00\x00\x00\x01")),
+            Value::BigInt(BigInt::from(1) << 64u32),
+        ),
+        (
+            raw(PickleOp::BYTEARRAY8(b"ab")),
+            Value::ByteArray(Cow::Borrowed(b"ab")),
+        ),
+    ]
+}
+
+#[test]
+fn json_round_trip_raw_fixed() -> Result<()> {
+    let json: Vec<String> = raw_fixed()
+        .iter()
+        .map(|(val, _)| serde_json::to_string(val))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(json[0], "3");
+    assert_eq!(json[2], r#""C:\\tmpé""#);
+    for (json, (_, expected)) in json.iter().zip(raw_fixed()) {
+        assert_eq!(serde_json::from_str::<Value>(json)?, expected, "{json}");
+    }
+    Ok(())
+}
+
+#[test]
+fn json_round_trip_pickle() -> Result<()> {
+    // pickle.dumps([b"\x00\xff", float("nan"), 2**70, {1: (None,)}], 3)
+    let ops = ops(b"\x80\x03]q\x00(C\x02\x00\xffq\x01G\x7f\xf8\x00\x00\x00\x00\x00\x00\x8a\x09\x00\x00\x00\x00\x00\x00\x00\x00@}q\x02K\x01N\x85q\x03se.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    let json = serde_json::to_string(&val)?;
+    let back: Value = serde_json::from_str(&json)?;
+    // Same thing again, because of the NaN.
+    assert_eq!(serde_json::to_string(&back)?, json);
+    let Value::Seq(_, items) = back else {
+        panic!("Expected a list, got {back:?}")
+    };
+    assert_eq!(items[0], Value::Bytes(Cow::Borrowed(b"\x00\xff")));
+    assert!(matches!(items[1], Value::Float(f) if f.is_nan()));
+
+    // Every op in it on its own, as you'd find them without resolving.
+    // The ones that stand for a value come back fixed.
+    for op in &ops {
+        if let PickleOp::BINFLOAT(_) = op {
+            continue;
+        }
+        let val = Value::Raw(Cow::Borrowed(op));
+        let json = serde_json::to_string(&val)?;
+        assert_eq!(
+            serde_json::from_str::<Value>(&json)?,
+            fix_value(val)?,
+            "{json}"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn json_round_trip_protocol_0() -> Result<()> {
+    // Text ops in protocols 0 and 1 have escapes, and JSON needs its own.
+    // v = ["C:\\tmp", "a\nb\t\"q\"", "\u00e9\u20ac\U0001f600", b"\x00\xff",
+    //      {"k\\": (1, 2**70)}, -1.5, None, True]
+    // pickle.dumps(v, 0)
+    let proto0 = b"(lp0\nVC:\\u005ctmp\np1\naVa\\u000ab\x09\"q\"\np2\naV\xe9\\u20ac\\U0001f600\np3\nac_codecs\nencode\np4\n(V\\u0000\xff\np5\nVlatin1\np6\ntp7\nRp8\na(dp9\nVk\\u005c\np10\n(I1\nL1180591620717411303424L\ntp11\nsaF-1.5\naNaI01\na.";
+    // pickle.dumps(v, 1)
+    let proto1 = b"]q\x00(X\x06\x00\x00\x00C:\\tmpq\x01X\x07\x00\x00\x00a\nb\x09\"q\"q\x02X\x09\x00\x00\x00\xc3\xa9\xe2\x82\xac\xf0\x9f\x98\x80q\x03c_codecs\nencode\nq\x04(X\x03\x00\x00\x00\x00\xc3\xbfq\x05X\x06\x00\x00\x00latin1q\x06tq\x07Rq\x08}q\x09X\x02\x00\x00\x00k\\q\n(K\x01L1180591620717411303424L\ntq\x0bsG\xbf\xf8\x00\x00\x00\x00\x00\x00NI01\ne.";
+    // Python 2: pickle.dumps(['C:\\tmp', u'C:\\tmp\u20ac\n', 'a\nb\xff', {'k\\': 2**70}], 0)
+    let py2 = b"(lp0\nS'C:\\\\tmp'\np1\naVC:\\u005ctmp\\u20ac\\u000a\np2\naS'a\\nb\\xff'\np3\na(dp4\nS'k\\\\'\np5\nL1180591620717411303424L\nsa.";
+    for data in [&proto0[..], &proto1[..], &py2[..]] {
+        let ops = ops(data);
+        let val = eval_one(&ops, &EvalOptions::default())?;
+        let json = serde_json::to_string(&val)?;
+        assert_eq!(serde_json::from_str::<Value>(&json)?, val, "{json}");
+
+        // Without resolving, the strings are still raw ops.
+        let options = EvalOptions {
+            resolve_refs: false,
+            ..EvalOptions::default()
+        };
+        let json = serde_json::to_string(&eval_one(&ops, &options)?)?;
+        let back: Value = serde_json::from_str(&json)?;
+        assert_eq!(serde_json::to_string(&back)?, json);
+    }
+    Ok(())
+}
@@ -0,0 +1,284 @@
+mod common;
+
+use anyhow::Result;
+use num_bigint::BigInt;
+
+use repugnant_pickle::{Date, EvalOptions, StdValue, StringEncoding, Time, TimeDelta, Value};
+
+use common::{both_models, eval_one, ops};
+
+fn items<'v, 'a>(val: &'v Value<'a>) -> &'v [Value<'a>] {
+    match val {
+        Value::Seq(_, items) => items,
+        val => panic!("Expected a sequence, got {val:?}"),
+    }
+}
+
+fn date(year: u16, month: u8, day: u8) -> Date {
+    Date { year, month, day }
+}
+
+fn time(hour: u8, minute: u8, second: u8, microsecond: u32, fold: bool) -> Time {
+    Time {
+        hour,
+        minute,
+        second,
+        microsecond,
+        fold,
+    }
+}
+
+/// `timezone(timedelta(hours=2), "X")`
+fn check_tz(tzinfo: Option<&Value<'_>>) {
+    assert_eq!(
+        tzinfo.and_then(Value::as_std),
+        Some(StdValue::TimeZone {
+            offset: TimeDelta {
+                days: 0,
+                seconds: 7200,
+                microseconds: 0
+            },
+            name: Some("X"),
+        })
+    );
+}
+
+#[test]
+fn std_types() -> Result<()> {
+    // tz = datetime.timezone(datetime.timedelta(hours=2), "X")
+    // pickle.dumps([
+    //     datetime.datetime(2024, 1, 31, 12, 34, 56, 123456, tzinfo=tz),
+    //     datetime.datetime(2024, 11, 3, 1, 30, fold=1),
+    //     datetime.date(2024, 1, 31),
+    //     datetime.time(12, 34, 56, 7, tzinfo=tz),
+    //     datetime.timedelta(-1, 5, 10),
+    //     decimal.Decimal("1.50"),
+    //     uuid.UUID("12345678-1234-5678-1234-567812345678"),
+    //     fractions.Fraction(3, 4),
+    //     pathlib.PurePosixPath("/usr/lib"),
+    //     pathlib.PureWindowsPath("C:/x/y"),
+    // ], 4)
+    let ops = ops(b"\x80\x04\x95{\x01\x00\x00\x00\x00\x00\x00]\x94(\x8c\x08datetime\x94\x8c\x08datetime\x94\x93\x94C\n\x07\xe8\x01\x1f\x0c\"8\x01\xe2@\x94h\x01\x8c\x08timezone\x94\x93\x94h\x01\x8c\x09timedelta\x94\x93\x94K\x00M \x1cK\x00\x87\x94R\x94\x8c\x01X\x94\x86\x94R\x94\x86\x94R\x94h\x03C\n\x07\xe8\x8b\x03\x01\x1e\x00\x00\x00\x00\x94\x85\x94R\x94h\x01\x8c\x04date\x94\x93\x94C\x04\x07\xe8\x01\x1f\x94\x85\x94R\x94h\x01\x8c\x04time\x94\x93\x94C\x06\x0c\"8\x00\x00\x07\x94h\x0d\x86\x94R\x94h\x08J\xff\xff\xff\xffK\x05K\n\x87\x94R\x94\x8c\x07decimal\x94\x8c\x07Decimal\x94\x93\x94\x8c\x041.50\x94\x85\x94R\x94\x8c\x04uuid\x94\x8c\x04UUID\x94\x93\x94)\x81\x94}\x94\x8c\x03int\x94\x8a\x10xV4\x12xV4\x12xV4\x12xV4\x12sb\x8c\x09fractions\x94\x8c\x08Fraction\x94\x93\x94K\x03K\x04\x86\x94R\x94\x8c\x07pathlib\x94\x8c\x0dPurePosixPath\x94\x93\x94\x8c\x01/\x94\x8c\x03usr\x94\x8c\x03lib\x94\x87\x94R\x94h0\x8c\x0fPureWindowsPath\x94\x93\x94\x8c\x03C:\\\x94\x8c\x01x\x94\x8c\x01y\x94\x87\x94R\x94e.");
+    for options in both_models() {
+        let val = eval_one(&ops, &options)?;
+        let found = items(&val)
+            .iter()
+            .map(|v| v.as_std().expect("Expected a standard type"))
+            .collect::<Vec<_>>();
+        let [dt, dt_fold, d, t, td, dec, uuid, frac, posix, windows] = found.as_slice() else {
+            panic!("Expected 10 items, got {found:?}");
+        };
+
+        let StdValue::DateTime {
+            date: dt_date,
+            time: dt_time,
+            tzinfo,
+        } = dt
+        else {
+            panic!("Expected a datetime, got {dt:?}");
+        };
+        assert_eq!(*dt_date, date(2024, 1, 31));
+        assert_eq!(*dt_time, time(12, 34, 56, 123456, false));
+        assert_eq!(format!("{dt_date} {dt_time}"), "2024-01-31 12:34:56.123456");
+        check_tz(*tzinfo);
+
+        assert_eq!(
+            *dt_fold,
+            StdValue::DateTime {
+                date: date(2024, 11, 3),
+                time: time(1, 30, 0, 0, true),
+                tzinfo: None,
+            }
+        );
+        assert_eq!(*d, StdValue::Date(date(2024, 1, 31)));
+
+        let StdValue::Time { time: t, tzinfo } = t else {
+            panic!("Expected a time, got {t:?}");
+        };
+        assert_eq!(*t, time(12, 34, 56, 7, false));
+        assert_eq!(t.to_string(), "12:34:56.000007");
+        check_tz(*tzinfo);
+
+        let StdValue::TimeDelta(td) = td else {
+            panic!("Expected a timedelta, got {td:?}");
+        };
+        assert_eq!(
+            *td,
+            TimeDelta {
+                days: -1,
+                seconds: 5,
+                microseconds: 10
+            }
+        );
+        assert_eq!(td.to_string(), "-1 day, 0:00:05.000010");
+
+        assert_eq!(*dec, StdValue::Decimal("1.50"));
+        assert_eq!(
+            *uuid,
+            StdValue::Uuid(0x12345678_1234_5678_1234_567812345678)
+        );
+        assert_eq!(*frac, StdValue::Fraction(BigInt::from(3), BigInt::from(4)));
+        assert_eq!(
+            *posix,
+            StdValue::Path {
+                class: "PurePosixPath",
+                path: "/usr/lib".to_string()
+            }
+        );
+        assert_eq!(
+            *windows,
+            StdValue::Path {
+                class: "PureWindowsPath",
+                path: "C:\\x\\y".to_string()
+            }
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn std_datetime_codecs() -> Result<()> {
+    // Protocol 2 has no bytes, so the state goes through _codecs.encode.
+    // Python only pickles fold for protocol 4 and up.
+    // pickle.dumps([
+    //     datetime.datetime(2024, 1, 31, 12, 34, 56, 123456, tzinfo=tz),
+    //     datetime.datetime(2024, 11, 3, 1, 30, fold=1),
+    //     datetime.date(2024, 1, 31),
+    // ], 2)
+    let ops = ops(b"\x80\x02]q\x00(cdatetime\ndatetime\nq\x01c_codecs\nencode\nq\x02X\x0c\x00\x00\x00\x07\xc3\xa8\x01\x1f\x0c\"8\x01\xc3\xa2@q\x03X\x06\x00\x00\x00latin1q\x04\x86q\x05Rq\x06cdatetime\ntimezone\nq\x07cdatetime\ntimedelta\nq\x08K\x00M \x1cK\x00\x87q\x09Rq\nX\x01\x00\x00\x00Xq\x0b\x86q\x0cRq\x0d\x86q\x0eRq\x0fh\x01h\x02X\x0b\x00\x00\x00\x07\xc3\xa8\x0b\x03\x01\x1e\x00\x00\x00\x00q\x10h\x04\x86q\x11Rq\x12\x85q\x13Rq\x14cdatetime\ndate\nq\x15h\x02X\x05\x00\x00\x00\x07\xc3\xa8\x01\x1fq\x16h\x04\x86q\x17Rq\x18\x85q\x19Rq\x1ae.");
+    for options in both_models() {
+        let val = eval_one(&ops, &options)?;
+        let found = items(&val)
+            .iter()
+            .map(|v| v.as_std().expect("Expected a standard type"))
+            .collect::<Vec<_>>();
+        let [StdValue::DateTime {
+            date: dt_date,
+            time: dt_time,
+            tzinfo,
+        }, dt_fold, d] = found.as_slice()
+        else {
+            panic!("Expected a datetime and two more, got {found:?}");
+        };
+        assert_eq!(*dt_date, date(2024, 1, 31));
+        assert_eq!(*dt_time, time(12, 34, 56, 123456, false));
+        check_tz(*tzinfo);
+        assert_eq!(
+            *dt_fold,
+            StdValue::DateTime {
+                date: date(2024, 11, 3),
+                time: time(1, 30, 0, 0, false),
+                tzinfo: None,
+            }
+        );
+        assert_eq!(*d, StdValue::Date(date(2024, 1, 31)));
+    }
+    Ok(())
+}
+
+#[test]
+fn std_protocol_0() -> Result<()> {
+    // Text in protocol 0 is all UNICODE ops, and protocol 1 isn't much
+    // different. The UUID goes through copyreg._reconstructor.
+    // pickle.dumps([
+    //     datetime.datetime(2024, 1, 31, 12, 34, 56, 123456, tzinfo=tz),
+    //     datetime.date(2024, 1, 31),
+    //     datetime.time(12, 34, 56, 7, tzinfo=tz),
+    //     datetime.timedelta(-1, 5, 10),
+    //     decimal.Decimal("1.50"),
+    //     uuid.UUID("12345678-1234-5678-1234-567812345678"),
+    //     fractions.Fraction(3, 4),
+    //     pathlib.PurePosixPath("/usr/lib"),
+    // ], 0)
+    let proto0 = b"(lp0\ncdatetime\ndatetime\np1\n(c_codecs\nencode\np2\n(V\x07\xe8\x01\x1f\x0c\"8\x01\xe2@\np3\nVlatin1\np4\ntp5\nRp6\ncdatetime\ntimezone\np7\n(cdatetime\ntimedelta\np8\n(I0\nI7200\nI0\ntp9\nRp10\nVX\np11\ntp12\nRp13\ntp14\nRp15\nacdatetime\ndate\np16\n(g2\n(V\x07\xe8\x01\x1f\np17\ng4\ntp18\nRp19\ntp20\nRp21\nacdatetime\ntime\np22\n(g2\n(V\x0c\"8\\u0000\\u0000\x07\np23\ng4\ntp24\nRp25\ng13\ntp26\nRp27\nag8\n(I-1\nI5\nI10\ntp28\nRp29\nacdecimal\nDecimal\np30\n(V1.50\np31\ntp32\nRp33\naccopy_reg\n_reconstructor\np34\n(cuuid\nUUID\np35\nc__builtin__\nobject\np36\nNtp37\nRp38\n(dp39\nVint\np40\nL24197857161011715162171839636988778104L\nsbacfractions\nFraction\np41\n(I3\nI4\ntp42\nRp43\nacpathlib\nPurePosixPath\np44\n(V/\np45\nVusr\np46\nVlib\np47\ntp48\nRp49\na.";
+    // The same with pickle.dumps(..., 1)
+    let proto1 = b"]q\x00(cdatetime\ndatetime\nq\x01(c_codecs\nencode\nq\x02(X\x0c\x00\x00\x00\x07\xc3\xa8\x01\x1f\x0c\"8\x01\xc3\xa2@q\x03X\x06\x00\x00\x00latin1q\x04tq\x05Rq\x06cdatetime\ntimezone\nq\x07(cdatetime\ntimedelta\nq\x08(K\x00M \x1cK\x00tq\x09Rq\nX\x01\x00\x00\x00Xq\x0btq\x0cRq\x0dtq\x0eRq\x0fcdatetime\ndate\nq\x10(h\x02(X\x05\x00\x00\x00\x07\xc3\xa8\x01\x1fq\x11h\x04tq\x12Rq\x13tq\x14Rq\x15cdatetime\ntime\nq\x16(h\x02(X\x06\x00\x00\x00\x0c\"8\x00\x00\x07q\x17h\x04tq\x18Rq\x19h\x0dtq\x1aRq\x1bh\x08(J\xff\xff\xff\xffK\x05K\ntq\x1cRq\x1dcdecimal\nDecimal\nq\x1e(X\x04\x00\x00\x001.50q\x1ftq Rq!ccopy_reg\n_reconstructor\nq\"(cuuid\nUUID\nq#c__builtin__\nobject\nq$Ntq%Rq&}q\'X\x03\x00\x00\x00intq(L24197857161011715162171839636988778104L\nsbcfractions\nFraction\nq)(K\x03K\x04tq*Rq+cpathlib\nPurePosixPath\nq,(X\x01\x00\x00\x00/q-X\x03\x00\x00\x00usrq.X\x03\x00\x00\x00libq/tq0Rq1e.";
+    for data in [&proto0[..], &proto1[..]] {
+        let ops = ops(data);
+        for options in both_models() {
+            let val = eval_one(&ops, &options)?;
+            let found = items(&val)
+                .iter()
+                .map(|v| v.as_std().expect("Expected a standard type"))
+                .collect::<Vec<_>>();
+            let [StdValue::DateTime {
+                date: dt_date,
+                time: dt_time,
+                tzinfo,
+            }, d, StdValue::Time {
+                time: t,
+                tzinfo: t_tzinfo,
+            }, td, dec, uuid, frac, path] = found.as_slice()
+            else {
+                panic!("Expected a datetime, a date, a time and 5 more, got {found:?}");
+            };
+            assert_eq!(*dt_date, date(2024, 1, 31));
+            assert_eq!(*dt_time, time(12, 34, 56, 123456, false));
+            check_tz(*tzinfo);
+            assert_eq!(*d, StdValue::Date(date(2024, 1, 31)));
+            assert_eq!(*t, time(12, 34, 56, 7, false));
+            check_tz(*t_tzinfo);
+            assert_eq!(
+                *td,
+                StdValue::TimeDelta(TimeDelta {
+                    days: -1,
+                    seconds: 5,
+                    microseconds: 10
+                })
+            );
+            assert_eq!(*dec, StdValue::Decimal("1.50"));
+            assert_eq!(
+                *uuid,
+                StdValue::Uuid(0x12345678_1234_5678_1234_567812345678)
+            );
+            assert_eq!(*frac, StdValue::Fraction(BigInt::from(3), BigInt::from(4)));
+            assert_eq!(
+                *path,
+                StdValue::Path {
+                    class: "PurePosixPath",
+                    path: "/usr/lib".to_string()
+                }
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn std_datetime_python2() -> Result<()> {
+    // Python 2 pickles the state as a str.
+    // Python 2: pickle.dumps(datetime.date(2024, 1, 31), 0)
+    let ops = ops(b"cdatetime\ndate\np0\n(S'\\x07\\xe8\\x01\\x1f'\np1\ntp2\nRp3\n.");
+    for string_encoding in [StringEncoding::Latin1, StringEncoding::Bytes] {
+        let options = EvalOptions {
+            string_encoding,
+            ..Default::default()
+        };
+        let val = eval_one(&ops, &options)?;
+        assert_eq!(val.as_std(), Some(StdValue::Date(date(2024, 1, 31))));
+    }
+
+    // Python 2: pickle.dumps(datetime.date(2024, 1, 31), 2)
+    let ops =
+        common::ops(b"\x80\x02cdatetime\ndate\nq\x00U\x04\x07\xe8\x01\x1fq\x01\x85q\x02Rq\x03.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(val.as_std(), Some(StdValue::Date(date(2024, 1, 31))));
+    Ok(())
+}
+
+#[test]
+fn std_not_recognized() -> Result<()> {
+    // The state is the wrong length.
+    // PROTO 3, GLOBAL datetime date, SHORT_BINBYTES b"\x07\xe8\x01", TUPLE1,
+    // REDUCE, STOP
+    let ops = ops(b"\x80\x03cdatetime\ndate\nC\x03\x07\xe8\x01\x85R.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(val.as_std(), None);
+
+    // pickle.dumps([1, "x"], 2)
+    let ops = common::ops(b"\x80\x02]q\x00(K\x01X\x01\x00\x00\x00xq\x01e.");
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    assert_eq!(val.as_std(), None);
+    assert!(items(&val).iter().all(|v| v.as_std().is_none()));
+    Ok(())
+}
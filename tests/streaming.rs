@@ -0,0 +1,171 @@
+mod common;
+
+use anyhow::Result;
+
+use repugnant_pickle::{
+    evaluate_bytes, evaluate_streaming, EvalOptions, SequenceType, StreamItem, Value,
+};
+
+use common::s;
+
+// pickle.dumps([[i, "x"] for i in range(1200)], 2)
+const LIST: &[u8] = include_bytes!("data/stream.pkl");
+
+// pickle.dumps({str(i): [i] for i in range(3)}, 2)
+const DICT: &[u8] = b"\x80\x02}q\x00(X\x01\x00\x00\x000q\x01]q\x02K\x00aX\x01\x00\x00\x001q\x03]q\x04K\x01aX\x01\x00\x00\x002q\x05]q\x06K\x02au.";
+
+// a = [1]; pickle.dumps([a, a], 0)
+const SHARED: &[u8] = b"(lp0\n(lp1\nI1\naag1\na.";
+
+fn stream(data: &[u8], keep_memo: bool) -> Result<(Vec<StreamItem<'_>>, Value<'_>, usize)> {
+    let mut items = vec![];
+    let (mut values, memo) =
+        evaluate_streaming(data, &EvalOptions::default(), keep_memo, |item| {
+            items.push(item);
+            Ok(())
+        })?;
+    assert_eq!(values.len(), 1);
+    Ok((items, values.pop().unwrap(), memo.0.len()))
+}
+
+#[test]
+fn stream_list_matches_evaluate() -> Result<()> {
+    let (items, container, memo_len) = stream(LIST, false)?;
+    assert_eq!(container, Value::Seq(SequenceType::List, vec![]));
+    // Only the list itself and the "x" are left.
+    assert_eq!(memo_len, 2);
+
+    let (values, _) = evaluate_bytes(LIST, true)?;
+    let Value::Seq(SequenceType::List, expected) = &values[0] else {
+        panic!("Expected a list, got {:?}", values[0])
+    };
+    assert_eq!(expected.len(), 1200);
+    let items = items
+        .into_iter()
+        .map(|item| match item {
+            StreamItem::Item(v) => v,
+            item => panic!("Unexpected item {item:?}"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(&items, expected);
+    assert_eq!(
+        items[1199],
+        Value::Seq(SequenceType::List, vec![Value::Int(1199), s("x")])
+    );
+
+    let (_, _, memo_len) = stream(LIST, true)?;
+    assert_eq!(memo_len, 1202);
+    Ok(())
+}
+
+#[test]
+fn stream_dict() -> Result<()> {
+    let (items, container, memo_len) = stream(DICT, false)?;
+    assert_eq!(container, Value::Dict(vec![]));
+    assert_eq!(memo_len, 4);
+    let expected = (0..3)
+        .map(|i| {
+            StreamItem::Entry(
+                s(&i.to_string()),
+                Value::Seq(SequenceType::List, vec![Value::Int(i)]),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(items, expected);
+    Ok(())
+}
+
+#[test]
+fn stream_other_containers() -> Result<()> {
+    // pickle.dumps({1, 2}, 4)
+    let (items, _, _) = stream(
+        b"\x80\x04\x95\x09\x00\x00\x00\x00\x00\x00\x00\x8f\x94(K\x01K\x02\x90.",
+        false,
+    )?;
+    assert_eq!(
+        items,
+        vec![
+            StreamItem::Item(Value::Int(1)),
+            StreamItem::Item(Value::Int(2))
+        ]
+    );
+
+    // pickle.dumps(collections.OrderedDict(a=[1]), 2)
+    let (items, _, _) = stream(
+        b"\x80\x02ccollections\nOrderedDict\nq\x00)Rq\x01X\x01\x00\x00\x00aq\x02]q\x03K\x01as.",
+        false,
+    )?;
+    assert_eq!(
+        items,
+        vec![StreamItem::Entry(
+            s("a"),
+            Value::Seq(SequenceType::List, vec![Value::Int(1)])
+        )]
+    );
+
+    // pickle.dumps([], 2)
+    let (items, container, _) = stream(b"\x80\x02]q\x00.", false)?;
+    assert!(items.is_empty());
+    assert_eq!(container, Value::Seq(SequenceType::List, vec![]));
+    Ok(())
+}
+
+#[test]
+fn stream_shared_items() -> Result<()> {
+    let inner = Value::Seq(SequenceType::List, vec![Value::Int(1)]);
+    for keep_memo in [false, true] {
+        let (items, _, _) = stream(SHARED, keep_memo)?;
+        assert_eq!(
+            items,
+            vec![
+                StreamItem::Item(inner.clone()),
+                StreamItem::Item(inner.clone())
+            ]
+        );
+    }
+
+    // Shared items that have memoized things inside them.
+    for (data, memo_len) in [
+        // a = [[1]]; pickle.dumps([a, [2], a], 0)
+        (&b"(lp0\n(lp1\n(lp2\nI1\naaa(lp3\nI2\naag1\na."[..], 1),
+        // b = {"k": (1, [2])}; pickle.dumps([b, [3], b], 0)
+        (
+            b"(lp0\n(dp1\nVk\np2\n(I1\n(lp3\nI2\natp4\nsa(lp5\nI3\naag1\na.",
+            2,
+        ),
+    ] {
+        let (items, _, len) = stream(data, false)?;
+        let (values, _) = evaluate_bytes(data, true)?;
+        let Value::Seq(SequenceType::List, expected) = &values[0] else {
+            panic!("Expected a list, got {:?}", values[0])
+        };
+        let items = items
+            .into_iter()
+            .map(|item| match item {
+                StreamItem::Item(val) => val,
+                item => panic!("Expected an item, got {item:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(&items, expected);
+        // Once the last reference is done, only the list itself and the
+        // strings are left.
+        assert_eq!(len, memo_len);
+    }
+    Ok(())
+}
+
+#[test]
+fn stream_rejects_other_top_level_values() {
+    // pickle.dumps((1, 2), 2)
+    let err = stream(b"\x80\x02K\x01K\x02\x86q\x00.", false).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Expected a list or dict at the top level when streaming, got tuple"
+    );
+    // pickle.dumps(1, 2)
+    let err = stream(b"\x80\x02K\x01.", false).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Expected a list or dict at the top level when streaming, got int"
+    );
+}
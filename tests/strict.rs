@@ -0,0 +1,81 @@
+mod common;
+
+use anyhow::Result;
+
+use repugnant_pickle::{evaluate_bytes_with_options, EvalMode, EvalOptions, SequenceType, Value};
+
+use common::{eval_one, ops};
+
+fn strict() -> EvalOptions {
+    EvalOptions {
+        mode: EvalMode::Strict,
+        ..Default::default()
+    }
+}
+
+fn strict_err(data: &[u8]) -> String {
+    let ops = ops(data);
+    eval_one(&ops, &strict()).unwrap_err().to_string()
+}
+
+#[test]
+fn strict_accepts_normal_pickle() -> Result<()> {
+    // pickle.dumps([1, 2], 4)
+    let ops = ops(b"\x80\x04\x95\x09\x00\x00\x00\x00\x00\x00\x00]\x94(K\x01K\x02e.");
+    let val = eval_one(&ops, &strict())?;
+    assert_eq!(
+        val,
+        Value::Seq(SequenceType::List, vec![Value::Int(1), Value::Int(2)])
+    );
+    Ok(())
+}
+
+#[test]
+fn strict_rejects_unhandled_op() {
+    // copyreg.add_extension("collections", "OrderedDict", 240)
+    // pickle.dumps(collections.OrderedDict, 2)
+    let err = strict_err(b"\x80\x02\x82\xf0.");
+    assert!(
+        err.starts_with("Unhandled op in strict mode: EXT1"),
+        "{err}"
+    );
+
+    // Lenient mode just leaves it raw.
+    let ops = ops(b"\x80\x02\x82\xf0.");
+    let val = eval_one(&ops, &EvalOptions::default()).unwrap();
+    assert!(matches!(val, Value::Raw(_)), "{val:?}");
+}
+
+#[test]
+fn strict_rejects_leftover_mark() {
+    // MARK, BININT1 1, STOP
+    assert_eq!(strict_err(b"(K\x01."), "Leftover MARK at STOP");
+}
+
+#[test]
+fn strict_rejects_extra_stack_items() {
+    // BININT1 1, BININT1 2, STOP
+    assert_eq!(
+        strict_err(b"K\x01K\x02."),
+        "Expected exactly one item on the stack at STOP, got 2"
+    );
+}
+
+#[test]
+fn strict_rejects_missing_stop() {
+    // pickle.dumps(1, 2) without the STOP
+    assert_eq!(strict_err(b"\x80\x02K\x01"), "Missing STOP");
+}
+
+#[test]
+fn strict_rejects_parse_error() {
+    // pickle.dumps(1, 2) with a truncated BININT instead of the STOP
+    let data = b"\x80\x02K\x01J\x01\x00";
+    let err = evaluate_bytes_with_options(data, &strict()).unwrap_err();
+    assert!(
+        err.to_string().starts_with("Parse error at offset 4"),
+        "{err}"
+    );
+    let (values, _) = evaluate_bytes_with_options(data, &EvalOptions::default()).unwrap();
+    assert_eq!(values, vec![Value::Int(1)]);
+}
@@ -0,0 +1,103 @@
+#![cfg(feature = "torch")]
+
+use std::{fs::File, io::Write, path::PathBuf};
+
+use anyhow::Result;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use repugnant_pickle::{RepugnantTorchTensors, TensorType};
+
+// See data/state_dict.py. The first one is protocol 2 and the second one is
+// protocol 4, which uses STACK_GLOBAL and MEMOIZE like `torch.save` with
+// `pickle_protocol=4`. The last one is protocol 0, where the keys are
+// UNICODE ops.
+const STATE_DICT: &[u8] = include_bytes!("data/state_dict.pkl");
+const STATE_DICT_P4: &[u8] = include_bytes!("data/state_dict_p4.pkl");
+const STATE_DICT_P0: &[u8] = include_bytes!("data/state_dict_p0.pkl");
+
+/// Write a checkpoint with `data.pkl` and the storages into a
+/// temporary file.
+fn checkpoint(name: &str, data: &[u8], storages: &[(&str, usize)]) -> Result<PathBuf> {
+    let path = std::env::temp_dir().join(format!(
+        "repugnant-pickle-{}-{name}.pth",
+        std::process::id()
+    ));
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut zw = ZipWriter::new(File::create(&path)?);
+    zw.start_file("archive/data.pkl", options)?;
+    zw.write_all(data)?;
+    for (key, len) in storages {
+        zw.start_file(format!("archive/data/{key}"), options)?;
+        zw.write_all(&vec![0; len * 4])?;
+    }
+    zw.finish()?;
+    Ok(path)
+}
+
+#[test]
+fn torch_state_dict() -> Result<()> {
+    for (name, data) in [
+        ("state-dict", STATE_DICT),
+        ("state-dict-p4", STATE_DICT_P4),
+        ("state-dict-p0", STATE_DICT_P0),
+    ] {
+        let path = checkpoint(name, data, &[("0", 12), ("1", 4), ("2", 8)])?;
+        let tensors = RepugnantTorchTensors::new_from_file(&path);
+        std::fs::remove_file(&path)?;
+        let tensors = tensors?.0;
+        let summary = tensors
+            .iter()
+            .map(|t| {
+                assert_eq!(t.device, "cpu");
+                assert_eq!(t.tensor_type, TensorType::Float32);
+                assert!(!t.requires_grad);
+                (
+                    t.name.as_str(),
+                    t.storage.as_str(),
+                    t.storage_len,
+                    t.storage_offset,
+                    t.shape.clone(),
+                    t.stride.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "emb.weight",
+                    "archive/data/0",
+                    12,
+                    0,
+                    vec![3, 4],
+                    vec![4, 1]
+                ),
+                ("emb.bias", "archive/data/1", 4, 0, vec![4], vec![1]),
+                (
+                    "head.weight",
+                    "archive/data/2",
+                    8,
+                    0,
+                    vec![2, 2],
+                    vec![2, 1]
+                ),
+                ("head.bias", "archive/data/2", 8, 16, vec![4], vec![1]),
+            ],
+            "{name}"
+        );
+        assert!(tensors[1].absolute_offset > tensors[0].absolute_offset);
+    }
+    Ok(())
+}
+
+#[test]
+fn torch_not_a_dict() -> Result<()> {
+    // class P: pass
+    // pickle.dumps(P(), 2)
+    let path = checkpoint("not-a-dict", b"\x80\x02c__main__\nP\nq\x00)\x81q\x01.", &[])?;
+    let tensors = RepugnantTorchTensors::new_from_file(&path);
+    std::fs::remove_file(&path)?;
+    let err = tensors.expect_err("Expected an error");
+    assert!(err.to_string().contains("expected a dict"), "{err}");
+    Ok(())
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use anyhow::Result;
+
+use repugnant_pickle::{
+    evaluate, evaluate_with_options, EvalOptions, SequenceType, Value, ValueView,
+};
+
+use common::{ops, s};
+
+// a = [1]; pickle.dumps({"a": a, "b": a}, 4)
+const SHARED: &[u8] = b"\x80\x04\x95\x14\x00\x00\x00\x00\x00\x00\x00}\x94(\x8c\x01a\x94]\x94K\x01a\x8c\x01b\x94h\x02u.";
+
+#[test]
+fn view_follows_refs() -> Result<()> {
+    let ops = ops(SHARED);
+    let (values, memo) = evaluate(&ops, false)?;
+    let view = ValueView::new(&values[0], &memo);
+    assert!(matches!(values[0], Value::Ref(_)));
+    assert_eq!(view.len(), Some(2));
+
+    let b = view.get_str("b").unwrap();
+    assert_eq!(b.len(), Some(1));
+    assert_eq!(*b.index(0).unwrap().fixed(), Value::Int(1));
+    assert!(b.index(1).is_none());
+
+    let keys = view
+        .dict_items()
+        .unwrap()
+        .map(|(k, _)| k.fixed().into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(keys, vec![s("a"), s("b")]);
+    assert!(view.get_str("c").is_none());
+    Ok(())
+}
+
+#[test]
+fn view_to_resolved_matches_evaluate() -> Result<()> {
+    let ops = ops(SHARED);
+    let (raw, memo) = evaluate(&ops, false)?;
+    let (resolved, _) = evaluate(&ops, true)?;
+    let view = ValueView::new(&raw[0], &memo);
+    assert_eq!(view.to_resolved()?, resolved[0]);
+
+    let b = view.get_str("b").unwrap().to_resolved()?;
+    assert_eq!(b, Value::Seq(SequenceType::List, vec![Value::Int(1)]));
+    Ok(())
+}
+
+#[test]
+fn view_containers() -> Result<()> {
+    // class L(list): pass
+    // d = collections.OrderedDict(a=1)
+    // pickle.dumps([d, collections.deque([1, 2]), L([3]), d], 2)
+    let ops = ops(b"\x80\x02]q\x00(ccollections\nOrderedDict\nq\x01)Rq\x02X\x01\x00\x00\x00aq\x03K\x01sccollections\ndeque\nq\x04)Rq\x05(K\x01K\x02ec__main__\nL\nq\x06)\x81q\x07K\x03ah\x02e.");
+    let options = EvalOptions {
+        resolve_refs: false,
+        object_model: true,
+        ..EvalOptions::default()
+    };
+    let (values, memo) = evaluate_with_options(&ops, &options)?;
+    let view = ValueView::new(&values[0], &memo);
+    let items = view.items().unwrap().collect::<Vec<_>>();
+    assert_eq!(items.len(), 4);
+
+    for od in [items[0], items[3]] {
+        assert_eq!(od.len(), Some(1));
+        let (k, v) = od.dict_items().unwrap().next().unwrap();
+        assert_eq!(*k.fixed(), s("a"));
+        assert_eq!(*v.fixed(), Value::Int(1));
+        assert_eq!(od.items().map(Iterator::count), None);
+    }
+
+    let deque = items[1];
+    assert_eq!(deque.len(), Some(2));
+    let deque_items = deque
+        .items()
+        .unwrap()
+        .map(|v| v.fixed().into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(deque_items, vec![Value::Int(1), Value::Int(2)]);
+
+    let obj = items[2];
+    assert!(matches!(obj.value(), Value::PyObject(_)));
+    assert_eq!(obj.len(), Some(1));
+    let obj_items = obj
+        .items()
+        .unwrap()
+        .map(|v| v.fixed().into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(obj_items, vec![Value::Int(3)]);
+    Ok(())
+}
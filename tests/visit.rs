@@ -0,0 +1,170 @@
+mod common;
+
+use anyhow::{bail, Result};
+
+use repugnant_pickle::{
+    visit::{walk_call, ValueFolder, ValueVisitor},
+    EvalOptions, Path, Value,
+};
+
+use common::{eval_one, object_model, ops, s};
+
+// See data/state_dict.py.
+const STATE_DICT: &[u8] = include_bytes!("data/state_dict.pkl");
+
+/// Remembers the path of every leaf.
+#[derive(Default)]
+struct Leaves(Vec<String>);
+
+impl<'v, 'a: 'v> ValueVisitor<'v, 'a> for Leaves {
+    fn visit_leaf(&mut self, path: &Path<'v>, val: &'v Value<'a>) {
+        self.0.push(format!("{path} = {val}"));
+    }
+}
+
+#[test]
+fn visit_leaves() -> Result<()> {
+    let ops = ops(STATE_DICT);
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    let mut leaves = Leaves::default();
+    leaves.visit_value(&Path::default(), &val);
+    let emb_bias = leaves
+        .0
+        .iter()
+        .filter(|l| l.starts_with("emb.bias"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        emb_bias,
+        [
+            "emb.bias = torch._utils._rebuild_tensor_v2",
+            "emb.bias/0/0/0 = 'storage'",
+            "emb.bias/0/0/1 = torch.FloatStorage",
+            "emb.bias/0/0/2 = '1'",
+            "emb.bias/0/0/3 = 'cpu'",
+            "emb.bias/0/0/4 = 4",
+            "emb.bias/1 = 0",
+            "emb.bias/2/0 = 4",
+            "emb.bias/3/0 = 1",
+            "emb.bias/4 = False",
+            "emb.bias/5 = collections.OrderedDict",
+            "emb.bias/5 = None",
+        ]
+    );
+    // The paths are the ones `Value::at` follows.
+    assert_eq!(val.at("emb.bias/0/0/2"), Some(&s("1")));
+    assert_eq!(val.at("emb.bias/2/0"), Some(&Value::Int(4)));
+
+    // The object model puts things in different places, but the paths
+    // are the same.
+    let options = object_model();
+    let val = eval_one(&ops, &options)?;
+    let mut leaves2 = Leaves::default();
+    leaves2.visit_value(&Path::default(), &val);
+    assert_eq!(leaves2.0, leaves.0);
+    Ok(())
+}
+
+#[test]
+fn visit_pyobject_paths() -> Result<()> {
+    let options = object_model();
+    // class L(list):
+    //     def __reduce_ex__(self, p):
+    //         return (copyreg.__newobj_ex__, (L, (1,), {"k": 2}), None, iter(self))
+    // pickle.dumps(L([3]), 4)
+    let ops = ops(b"\x80\x04\x95$\x00\x00\x00\x00\x00\x00\x00\x8c\x08__main__\x94\x8c\x01L\x94\x93\x94K\x01\x85\x94}\x94\x8c\x01k\x94K\x02s\x92\x94K\x03a.");
+    let val = eval_one(&ops, &options)?;
+    let mut leaves = Leaves::default();
+    leaves.visit_value(&Path::default(), &val);
+    // Only the list items get indexes, like with `Value::at`.
+    assert_eq!(leaves.0, [" = __main__.L", " = 1", " = 2", "0 = 3"]);
+    assert_eq!(val.at("0"), Some(&Value::Int(3)));
+
+    // The same thing without list items, so the arguments get indexes.
+    // return (copyreg.__newobj_ex__, (O, (1, 2), {"k": 3}))
+    let ops = common::ops(b"\x80\x04\x95#\x00\x00\x00\x00\x00\x00\x00\x8c\x08__main__\x94\x8c\x01O\x94\x93\x94K\x01K\x02\x86\x94}\x94\x8c\x01k\x94K\x03s\x92\x94.");
+    let val = eval_one(&ops, &options)?;
+    let mut leaves = Leaves::default();
+    leaves.visit_value(&Path::default(), &val);
+    assert_eq!(leaves.0, [" = __main__.O", "0 = 1", "1 = 2", " = 3"]);
+    assert_eq!(val.at("1"), Some(&Value::Int(2)));
+    Ok(())
+}
+
+/// Finds tensors without looking inside them.
+#[derive(Default)]
+struct Tensors(Vec<String>);
+
+impl<'v, 'a: 'v> ValueVisitor<'v, 'a> for Tensors {
+    fn visit_call(
+        &mut self,
+        path: &Path<'v>,
+        call: &'v Value<'a>,
+        target: &'v Value<'a>,
+        args: &'v [Value<'a>],
+    ) {
+        match call.as_call("torch._utils", "_rebuild_tensor_v2") {
+            Some(_) => self.0.push(path.to_string()),
+            None => walk_call(self, path, target, args),
+        }
+    }
+
+    fn visit_leaf(&mut self, path: &Path<'v>, _val: &'v Value<'a>) {
+        assert!(path.0.len() < 2, "Looked inside a tensor: {path}");
+    }
+}
+
+#[test]
+fn visit_override() -> Result<()> {
+    let ops = ops(STATE_DICT);
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    let mut tensors = Tensors::default();
+    tensors.visit_value(&Path::default(), &val);
+    assert_eq!(
+        tensors.0,
+        ["emb.weight", "emb.bias", "head.weight", "head.bias"]
+    );
+    Ok(())
+}
+
+/// Replaces persistent IDs with the storage key, and upper cases dict keys.
+struct Storages;
+
+impl<'a> ValueFolder<'a> for Storages {
+    fn fold_persid(&mut self, pid: Value<'a>) -> Result<Value<'a>> {
+        match pid.as_tuple_n::<5>() {
+            Some([_, _, key, _, _]) => Ok(key.clone()),
+            None => bail!("Bad persistent ID: {pid}"),
+        }
+    }
+
+    fn fold_leaf(&mut self, val: Value<'a>) -> Result<Value<'a>> {
+        match val {
+            Value::String(st) if st.contains('.') => Ok(s(&st.to_uppercase())),
+            val => Ok(val),
+        }
+    }
+}
+
+#[test]
+fn fold_persids() -> Result<()> {
+    let ops = ops(STATE_DICT);
+    let val = eval_one(&ops, &EvalOptions::default())?;
+    let folded = Storages.fold_value(val)?;
+    assert_eq!(
+        folded.to_string(),
+        "collections.OrderedDict({\
+         'EMB.WEIGHT': torch._utils._rebuild_tensor_v2('0', 0, (3, 4), (4, 1), False, collections.OrderedDict()), \
+         'EMB.BIAS': torch._utils._rebuild_tensor_v2('1', 0, (4,), (1,), False, collections.OrderedDict()), \
+         'HEAD.WEIGHT': torch._utils._rebuild_tensor_v2('2', 0, (2, 2), (2, 1), False, collections.OrderedDict()), \
+         'HEAD.BIAS': torch._utils._rebuild_tensor_v2('2', 4, (4,), (1,), False, collections.OrderedDict())})"
+    );
+
+    // Errors stop the fold.
+    let bad = Value::Seq(
+        repugnant_pickle::SequenceType::List,
+        vec![Value::PersId(Box::new(s("nope")))],
+    );
+    let err = Storages.fold_value(bad).unwrap_err();
+    assert_eq!(err.to_string(), "Bad persistent ID: 'nope'");
+    Ok(())
+}